@@ -0,0 +1,73 @@
+use std::fmt::Write;
+
+use assembler::parser::Parser;
+use assembler::pass::Pass;
+use assembler::stream;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+// Generates a program shaped like compiler output: short blocks of stack
+// manipulation, with labels and jumps referring back and forth.
+fn generate(lines: usize) -> String {
+    let mut source = String::new();
+    let mut block = 0;
+
+    while source.lines().count() < lines {
+        write!(
+            source,
+            "\
+            (BLOCK_{block})\n\
+            @SP\n\
+            AM=M-1\n\
+            D=M\n\
+            A=A-1\n\
+            M=D+M\n\
+            @var_{}\n\
+            M=D // store\n\
+            @BLOCK_{}\n\
+            D;JGT\n\
+            @{}\n\
+            D=A\n\
+            @SP\n\
+            A=M\n\
+            M=D\n\
+            @SP\n\
+            M=M+1\n\
+            ",
+            block % 500,
+            block + 1,
+            block % 30000,
+        )
+        .unwrap();
+        block += 1;
+    }
+
+    writeln!(source, "(BLOCK_{block})\n@BLOCK_{block}\n0;JMP").unwrap();
+    source
+}
+
+fn bench_pipeline(c: &mut Criterion) {
+    let mut group = c.benchmark_group("assemble");
+    group.sample_size(10);
+
+    for lines in [10_000, 100_000, 1_000_000] {
+        let source = generate(lines);
+        group.throughput(Throughput::Bytes(source.len() as u64));
+
+        group.bench_with_input(BenchmarkId::new("parser_pass", lines), &source, |b, source| {
+            b.iter(|| {
+                let mut parser = Parser::new(source);
+                let insts = parser.parse();
+                Pass::translate(&insts)
+            })
+        });
+
+        group.bench_with_input(BenchmarkId::new("stream", lines), &source, |b, source| {
+            b.iter(|| stream::assemble(source.as_bytes()).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_pipeline);
+criterion_main!(benches);
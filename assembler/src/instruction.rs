@@ -1,3 +1,5 @@
+use std::fmt;
+
 use crate::{token::Token, register::Register};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -49,6 +51,117 @@ pub enum Computation {
     Sub{lhs: CompValue, rhs: CompValue},
     And{lhs: CompValue, rhs: CompValue},
     Or{lhs: CompValue, rhs: CompValue},
+    // raw ALU control bits in the order `a zx nx zy ny f no`
+    Raw(u8),
+}
+
+use CompValue::*;
+
+/// The 28 computations listed in the book, with their `a zx nx zy ny f no` bits.
+const CANONICAL_COMPUTATIONS: [(Computation, u8); 28] = [
+    (Computation::Literal(Zero), 0b0101010),
+    (Computation::Literal(One), 0b0111111),
+    (Computation::Negative(One), 0b0111010),
+    (Computation::Literal(RegD), 0b0001100),
+    (Computation::Literal(RegA), 0b0110000),
+    (Computation::Literal(RegM), 0b1110000),
+    (Computation::Not(RegD), 0b0001101),
+    (Computation::Not(RegA), 0b0110001),
+    (Computation::Not(RegM), 0b1110001),
+    (Computation::Negative(RegD), 0b0001111),
+    (Computation::Negative(RegA), 0b0110011),
+    (Computation::Negative(RegM), 0b1110011),
+    (Computation::Add { lhs: RegD, rhs: One }, 0b0011111),
+    (Computation::Add { lhs: RegA, rhs: One }, 0b0110111),
+    (Computation::Add { lhs: RegM, rhs: One }, 0b1110111),
+    (Computation::Sub { lhs: RegD, rhs: One }, 0b0001110),
+    (Computation::Sub { lhs: RegA, rhs: One }, 0b0110010),
+    (Computation::Sub { lhs: RegM, rhs: One }, 0b1110010),
+    (Computation::Add { lhs: RegD, rhs: RegA }, 0b0000010),
+    (Computation::Add { lhs: RegD, rhs: RegM }, 0b1000010),
+    (Computation::Sub { lhs: RegD, rhs: RegA }, 0b0010011),
+    (Computation::Sub { lhs: RegD, rhs: RegM }, 0b1010011),
+    (Computation::Sub { lhs: RegA, rhs: RegD }, 0b0000111),
+    (Computation::Sub { lhs: RegM, rhs: RegD }, 0b1000111),
+    (Computation::And { lhs: RegD, rhs: RegA }, 0b0000000),
+    (Computation::And { lhs: RegD, rhs: RegM }, 0b1000000),
+    (Computation::Or { lhs: RegD, rhs: RegA }, 0b0010101),
+    (Computation::Or { lhs: RegD, rhs: RegM }, 0b1010101),
+];
+
+impl Computation {
+    /// Rewrites commutative spellings such as `A+D`, `M&D` or `1+A` into the
+    /// operand order used by the book. Returns `true` if anything was swapped.
+    pub fn normalize(&mut self) -> bool {
+        use Computation::*;
+
+        match self {
+            Add { lhs, rhs } | And { lhs, rhs } | Or { lhs, rhs } => {
+                let swap = matches!(
+                    (&*lhs, &*rhs),
+                    (RegA | RegM, RegD) | (One, RegA | RegD | RegM)
+                );
+                if swap {
+                    std::mem::swap(lhs, rhs);
+                }
+                swap
+            }
+            _ => false,
+        }
+    }
+
+    /// `a zx nx zy ny f no` bits of this computation, if the ALU can perform it.
+    pub fn encode(&self) -> Option<u8> {
+        if let Computation::Raw(bits) = self {
+            return Some(*bits);
+        }
+
+        CANONICAL_COMPUTATIONS
+            .iter()
+            .find(|(comp, _)| comp == self)
+            .map(|(_, bits)| *bits)
+    }
+
+    /// The canonical computation that the given control bits perform, if there is one.
+    pub fn from_bits(bits: u8) -> Option<Computation> {
+        CANONICAL_COMPUTATIONS
+            .iter()
+            .find(|(_, b)| *b == bits)
+            .map(|(comp, _)| comp.clone())
+    }
+}
+
+impl fmt::Display for CompValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            RegA => "A",
+            RegD => "D",
+            RegM => "M",
+            Zero => "0",
+            One => "1",
+        };
+        write!(f, "{s}")
+    }
+}
+
+impl fmt::Display for Computation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Computation::*;
+
+        match self {
+            Literal(v) => write!(f, "{v}"),
+            Not(v) => write!(f, "!{v}"),
+            Negative(v) => write!(f, "-{v}"),
+            Add { lhs, rhs } => write!(f, "{lhs}+{rhs}"),
+            Sub { lhs, rhs } => write!(f, "{lhs}-{rhs}"),
+            And { lhs, rhs } => write!(f, "{lhs}&{rhs}"),
+            Or { lhs, rhs } => write!(f, "{lhs}|{rhs}"),
+            Raw(bits) => {
+                let reg = if bits & 0b1000000 != 0 { "M" } else { "A" };
+                write!(f, "%{reg}{:06b}", bits & 0b111111)
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -62,3 +175,19 @@ pub enum CommandJump {
     JLE,
     JMP,
 }
+
+impl fmt::Display for CommandJump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            CommandJump::NULL => "null",
+            CommandJump::JGT => "JGT",
+            CommandJump::JEQ => "JEQ",
+            CommandJump::JGE => "JGE",
+            CommandJump::JLT => "JLT",
+            CommandJump::JNE => "JNE",
+            CommandJump::JLE => "JLE",
+            CommandJump::JMP => "JMP",
+        };
+        write!(f, "{s}")
+    }
+}
@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+
+/// Index of an interned symbol name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SymbolId(pub u32);
+
+/// Stores every distinct symbol name once, handing out small ids for them.
+#[derive(Debug, Default)]
+pub struct Interner {
+    ids: HashMap<Box<str>, SymbolId>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn intern(&mut self, name: &str) -> SymbolId {
+        if let Some(id) = self.ids.get(name) {
+            return *id;
+        }
+
+        let id = SymbolId(self.ids.len() as u32);
+        self.ids.insert(name.into(), id);
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Interner, SymbolId};
+
+    #[test]
+    fn test_intern() {
+        let mut interner = Interner::new();
+        let foo = interner.intern("foo");
+        let bar = interner.intern("bar");
+
+        assert_ne!(foo, bar);
+        assert_eq!(interner.intern("foo"), foo);
+        assert_eq!(interner.intern("bar"), bar);
+        assert_eq!(interner.intern("baz"), SymbolId(2));
+    }
+}
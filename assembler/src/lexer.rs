@@ -35,7 +35,7 @@ impl Lexer {
     }
 
     pub fn all_tokens(&mut self) -> Vec<Token> {
-        if self.source.len() == 0 {
+        if self.source.is_empty() {
             return vec![Token::Eof];
         }
 
@@ -67,7 +67,12 @@ impl Lexer {
                 '!' => break Token::Bang,
                 '(' => break Token::LParen,
                 ')' => break Token::RParen,
+                '%' => break self.raw_comp(),
                 '\r' | '\n' => {
+                    // treat "\r\n" as a single line break
+                    if c == '\r' && self.peek() == '\n' {
+                        self.position += 1;
+                    }
                     self.line += 1;
                     break Token::Newline;
                 }
@@ -161,12 +166,38 @@ impl Lexer {
         Token::Number(num)
     }
 
+    // raw ALU computations are written as `%` followed by the register selected by the
+    // `a` bit and the six control bits, e.g. `%M000111` for M-D
+    fn raw_comp(&mut self) -> Token {
+        let a = match self.advance() {
+            'A' => 0,
+            'M' => 1,
+            c => panic!("Expected 'A' or 'M' after '%' in raw computation. Encountered {:?}", c),
+        };
+
+        let mut bits = a;
+        for _ in 0..6 {
+            bits = match self.advance() {
+                '0' => bits << 1,
+                '1' => (bits << 1) | 1,
+                c => panic!("Raw computations need exactly six binary digits after the register. Encountered {:?}", c),
+            };
+        }
+
+        if self.peek().is_ascii_alphanumeric() {
+            panic!("Raw computations need exactly six binary digits after the register.");
+        }
+
+        Token::RawComp(bits)
+    }
+
     fn is_at_end(&self) -> bool {
         self.position >= self.source.len()
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::needless_borrow)]
 mod tests {
     use super::Lexer;
     use crate::token::Token;
@@ -355,4 +386,22 @@ mod tests {
         ];
         assert_eq!(tokens, expected);
     }
+
+    #[test]
+    fn test_raw_comp() {
+        use Token::*;
+
+        let input = "D=%A010011;JMP %M000111";
+        let tokens = lex_and_get_tokens(input);
+        let expected = vec![
+            RegD,
+            Equal,
+            RawComp(0b0010011),
+            Semicolon,
+            JMP,
+            RawComp(0b1000111),
+            Eof,
+        ];
+        assert_eq!(tokens, expected);
+    }
 }
@@ -1,6 +1,8 @@
 pub mod instruction;
+pub mod interner;
 pub mod lexer;
 pub mod parser;
 pub mod pass;
 pub mod register;
+pub mod stream;
 pub mod token;
@@ -1,22 +1,34 @@
-use std::fs;
+use std::fs::File;
 use std::env;
+use std::io::{self, BufReader, BufWriter, Write};
 
-use assembler::parser::Parser;
-use assembler::pass::Pass;
+use assembler::stream::StreamAssembler;
 
 fn main() -> std::io::Result<()> {
     let args: Vec<String> = env::args().collect();
 
-    if args.len() == 1 {
-        println!("help: assembler <filename>");
+    // `--report-noncanonical` lists every accepted spelling that is not in the book
+    let report = args.iter().any(|arg| arg == "--report-noncanonical");
+    let filenames: Vec<&String> = args.iter().skip(1).filter(|arg| !arg.starts_with("--")).collect();
+
+    if filenames.is_empty() {
+        println!("help: assembler [--report-noncanonical] <filename>");
         panic!("Please input filename as argument.");
     }
 
-    let input = fs::read_to_string(&args[1])?;
-    let mut parser = Parser::new(&input);
-    let instructions: Vec<assembler::instruction::Instruction> = parser.parse();
-    let minsts = Pass::translate(&instructions);
-    println!("{}", minsts.join("\n"));
+    let mut assembler = StreamAssembler::new();
+    assembler.feed(BufReader::new(File::open(filenames[0])?))?;
+
+    if report {
+        for nc in assembler.noncanonical() {
+            eprintln!("{}:{}: '{}' is not canonical (canonical: {})", filenames[0], nc.line, nc.form, nc.canonical);
+        }
+    }
+
+    let mut out = BufWriter::new(io::stdout().lock());
+    for word in assembler.finish() {
+        writeln!(out, "{:016b}", word)?;
+    }
 
     Ok(())
 }
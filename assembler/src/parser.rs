@@ -2,27 +2,41 @@ use crate::instruction::{CommandDest, CommandJump, CompValue, Computation, Instr
 use crate::lexer::Lexer;
 use crate::token::Token;
 
+/// A spelling that the parser accepted even though it is not one of the forms listed in the
+/// book, along with the canonical spelling it was rewritten to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NonCanonical {
+    pub line: usize,
+    pub form: String,
+    pub canonical: String,
+}
+
 pub struct Parser {
-    lines: Vec<Vec<Token>>,
+    lines: Vec<(usize, Vec<Token>)>,
+    noncanonical: Vec<NonCanonical>,
 }
 
 impl Parser {
     pub fn new(input: &str) -> Self {
         let mut lexer = Lexer::new(input);
         let lines = Self::split_at_newline(lexer.all_tokens());
-        Parser { lines }
+        let noncanonical = Vec::new();
+        Parser { lines, noncanonical }
     }
 
-    fn split_at_newline(tokens: Vec<Token>) -> Vec<Vec<Token>> {
+    // groups tokens by line, keeping the (1-based) source line number of each group
+    fn split_at_newline(tokens: Vec<Token>) -> Vec<(usize, Vec<Token>)> {
         let mut groups = Vec::new();
 
         let mut i = 0;
+        let mut line = 1;
         while i < tokens.len() {
             let mut group = Vec::new();
 
             // skip newlines
             while i < tokens.len() {
                 if let Token::Newline = &tokens[i] {
+                    line += 1;
                     i += 1;
                 } else {
                     break;
@@ -46,7 +60,7 @@ impl Parser {
             }
 
             if !group.is_empty() {
-                groups.push(group);
+                groups.push((line, group));
             }
         }
 
@@ -56,15 +70,32 @@ impl Parser {
     pub fn parse(&mut self) -> Vec<Instruction> {
         let mut parsed = Vec::new();
 
-        for line in &self.lines {
-            let inst = Self::parse_line(line);
+        for (line, tokens) in &self.lines {
+            let mut notes = Vec::new();
+            let inst = Self::parse_line_noting(tokens, &mut notes);
+            self.noncanonical
+                .extend(notes.into_iter().map(|(form, canonical)| NonCanonical {
+                    line: *line,
+                    form,
+                    canonical,
+                }));
             parsed.push(inst);
         }
 
         parsed
     }
 
-    pub fn parse_line(line: &Vec<Token>) -> Instruction {
+    /// Non-canonical spellings accepted by the last call to `parse`, in source order.
+    pub fn noncanonical(&self) -> &[NonCanonical] {
+        &self.noncanonical
+    }
+
+    pub fn parse_line(line: &[Token]) -> Instruction {
+        Self::parse_line_noting(line, &mut Vec::new())
+    }
+
+    // parses a single line, pushing (form, canonical form) for every non-canonical spelling
+    pub(crate) fn parse_line_noting(line: &[Token], notes: &mut Vec<(String, String)>) -> Instruction {
         use Token::*;
 
         match line[0] {
@@ -109,6 +140,15 @@ impl Parser {
                         let comp = Self::parse_comp_value(&line[position + 1]);
                         (Computation::Not(comp), position + 2)
                     },
+                    RawComp(bits) => {
+                        let comp = Computation::Raw(bits);
+                        let canonical = match Computation::from_bits(bits) {
+                            Some(c) => c.to_string(),
+                            None => String::from("-"),
+                        };
+                        notes.push((comp.to_string(), canonical));
+                        (comp, position + 1)
+                    },
                     // binary operation
                     RegA | RegD | RegM | Number(_) => {
                         if position == line.len() - 1 {
@@ -121,7 +161,7 @@ impl Parser {
                             let lhs = Self::parse_comp_value(&line[position]);
                             let rhs = Self::parse_comp_value(&line[position + 2]);
 
-                            let comp = match line[position + 1] {
+                            let mut comp = match line[position + 1] {
                                 Plus => Computation::Add{lhs, rhs},
                                 Minus => Computation::Sub{lhs, rhs},
                                 Ampersand => Computation::And{lhs, rhs},
@@ -129,6 +169,11 @@ impl Parser {
                                 ref t => panic!("Invalid operator {:?}. An operator must be one of '+', '-', '&', '|'", t),
                             };
 
+                            let form = comp.to_string();
+                            if comp.normalize() {
+                                notes.push((form, comp.to_string()));
+                            }
+
                             (comp, position + 3)
                         }
                     },
@@ -142,6 +187,9 @@ impl Parser {
                 assert_eq!(line[position], Semicolon);
                 let position = position + 1;
                 let jump = Self::parse_jump(&line[position]);
+                if let Symbol(ref alias) = line[position] {
+                    notes.push((alias.clone(), jump.to_string()));
+                }
                 Instruction::Command(dest, comp, jump)
             }
         }
@@ -171,6 +219,10 @@ impl Parser {
             JNE => CommandJump::JNE,
             JLE => CommandJump::JLE,
             JMP => CommandJump::JMP,
+            // aliases accepted by some course variants
+            Symbol(s) if s == "JZ" => CommandJump::JEQ,
+            Symbol(s) if s == "JNZ" => CommandJump::JNE,
+            Symbol(s) if s == "JUMP" => CommandJump::JMP,
             t => panic!("Unexpected token {:?} while parsing jump token.", t),
         }
     }
@@ -190,9 +242,9 @@ mod tests {
         let input = "foobar 30 &|\nfoo\n\nbar\n";
         let groups = Parser::split_at_newline(Lexer::new(input).all_tokens());
         let expected = vec![
-            vec![Symbol(String::from("foobar")), Number(30), Ampersand, Pipe],
-            vec![Symbol(String::from("foo"))],
-            vec![Symbol(String::from("bar"))],
+            (1, vec![Symbol(String::from("foobar")), Number(30), Ampersand, Pipe]),
+            (2, vec![Symbol(String::from("foo"))]),
+            (4, vec![Symbol(String::from("bar"))]),
         ];
         assert_eq!(groups, expected);
     }
@@ -307,6 +359,56 @@ mod tests {
         assert_eq!(insts, expected);
     }
 
+    #[test]
+    fn test_comp_commutative() {
+        use CompValue::*;
+
+        let input = "D=A+D\nM=M&D\nD=A|D\nA=1+M\nD=D|A";
+        let mut parser = Parser::new(input);
+        let insts = parser.parse();
+        let expected = vec![
+            Instruction::Command(CommandDest::D, Computation::Add{lhs: RegD, rhs: RegA}, CommandJump::NULL),
+            Instruction::Command(CommandDest::M, Computation::And{lhs: RegD, rhs: RegM}, CommandJump::NULL),
+            Instruction::Command(CommandDest::D, Computation::Or{lhs: RegD, rhs: RegA}, CommandJump::NULL),
+            Instruction::Command(CommandDest::A, Computation::Add{lhs: RegM, rhs: One}, CommandJump::NULL),
+            Instruction::Command(CommandDest::D, Computation::Or{lhs: RegD, rhs: RegA}, CommandJump::NULL),
+        ];
+        assert_eq!(insts, expected);
+
+        let forms = parser
+            .noncanonical()
+            .iter()
+            .map(|n| (n.line, n.form.as_str(), n.canonical.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            forms,
+            vec![(1, "A+D", "D+A"), (2, "M&D", "D&M"), (3, "A|D", "D|A"), (4, "1+M", "M+1")]
+        );
+    }
+
+    #[test]
+    fn test_comp_raw() {
+        let input = "D=%A010011\nM=%M111111;JZ\n0;JUMP";
+        let mut parser = Parser::new(input);
+        let insts = parser.parse();
+        let expected = vec![
+            Instruction::Command(CommandDest::D, Computation::Raw(0b0010011), CommandJump::NULL),
+            Instruction::Command(CommandDest::M, Computation::Raw(0b1111111), CommandJump::JEQ),
+            Instruction::Command(CommandDest::NULL, Computation::Literal(CompValue::Zero), CommandJump::JMP),
+        ];
+        assert_eq!(insts, expected);
+
+        let forms = parser
+            .noncanonical()
+            .iter()
+            .map(|n| (n.line, n.form.as_str(), n.canonical.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            forms,
+            vec![(1, "%A010011", "D-A"), (2, "%M111111", "-"), (2, "JZ", "JEQ"), (3, "JUMP", "JMP")]
+        );
+    }
+
     #[test]
     fn test_comp_jump() {
         use CompValue::*;
@@ -1,15 +1,42 @@
 use std::collections::HashMap;
 
 use crate::{
-    instruction::{CommandDest, CommandJump, Instruction, CompValue, Computation},
+    instruction::{CommandDest, CommandJump, Instruction, Computation},
     token::Token,
 };
 
+/// Symbols every Hack program starts out with.
+pub const PREDEFINED_SYMBOLS: [(&str, u16); 23] = [
+    ("SP", 0x0),
+    ("LCL", 0x1),
+    ("ARG", 0x2),
+    ("THIS", 0x3),
+    ("THAT", 0x4),
+    ("R0", 0x0),
+    ("R1", 0x1),
+    ("R2", 0x2),
+    ("R3", 0x3),
+    ("R4", 0x4),
+    ("R5", 0x5),
+    ("R6", 0x6),
+    ("R7", 0x7),
+    ("R8", 0x8),
+    ("R9", 0x9),
+    ("R10", 0xa),
+    ("R11", 0xb),
+    ("R12", 0xc),
+    ("R13", 0xd),
+    ("R14", 0xe),
+    ("R15", 0xf),
+    ("SCREEN", 0x4000),
+    ("KBD", 0x6000),
+];
+
 pub struct Pass;
 
 impl Pass {
     // associate line numbers with each instruction, starting from 0
-    pub fn line_resolution(insts: &Vec<Instruction>) -> Vec<(u16, Instruction)> {
+    pub fn line_resolution(insts: &[Instruction]) -> Vec<(u16, Instruction)> {
         let mut i = 0;
         let mut line = 0;
 
@@ -30,8 +57,8 @@ impl Pass {
                 }
 
                 i += 1;
-                for l in start..i {
-                    line_insts.push((line, insts[l].clone()));
+                for inst in &insts[start..i] {
+                    line_insts.push((line, inst.clone()));
                 }
                 line += 1;
             } else {
@@ -44,36 +71,15 @@ impl Pass {
         line_insts
     }
 
-    pub fn symbol_resolution(insts: &Vec<Instruction>) -> HashMap<String, u16> {
+    pub fn symbol_resolution(insts: &[Instruction]) -> HashMap<String, u16> {
         use Token::*;
 
         let mut variable_address = 0x10;
         let line_insts = Self::line_resolution(insts);
-        let mut table = HashMap::from([
-            ("SP".into(), 0x0),
-            ("LCL".into(), 0x1),
-            ("ARG".into(), 0x2),
-            ("THIS".into(), 0x3),
-            ("THAT".into(), 0x4),
-            ("R0".into(), 0x0),
-            ("R1".into(), 0x1),
-            ("R2".into(), 0x2),
-            ("R3".into(), 0x3),
-            ("R4".into(), 0x4),
-            ("R5".into(), 0x5),
-            ("R6".into(), 0x6),
-            ("R7".into(), 0x7),
-            ("R8".into(), 0x8),
-            ("R9".into(), 0x9),
-            ("R10".into(), 0xa),
-            ("R11".into(), 0xb),
-            ("R12".into(), 0xc),
-            ("R13".into(), 0xd),
-            ("R14".into(), 0xe),
-            ("R15".into(), 0xf),
-            ("SCREEN".into(), 0x4000),
-            ("KBD".into(), 0x6000),
-        ]);
+        let mut table: HashMap<String, u16> = PREDEFINED_SYMBOLS
+            .iter()
+            .map(|(name, address)| (name.to_string(), *address))
+            .collect();
 
         // list of label names and their line numbers.
         // we want to populate the table before running the actual pass
@@ -90,8 +96,8 @@ impl Pass {
         table.extend(labels);
 
         for inst in &line_insts {
-            match inst {
-                (_, Instruction::Address(name)) => match name {
+            if let (_, Instruction::Address(name)) = inst {
+                match name {
                     Number(_) => (),
                     Symbol(name) => {
                         variable_address = Self::handle_symbol(&mut table, name, variable_address);
@@ -120,15 +126,14 @@ impl Pass {
                         variable_address = Self::handle_symbol(&mut table, name, variable_address);
                     }
                     _ => (),
-                },
-                _ => (),
+                }
             }
         }
 
         table
     }
 
-    pub fn translate(insts: &Vec<Instruction>) -> Vec<String> {
+    pub fn translate(insts: &[Instruction]) -> Vec<String> {
         use Token::*;
 
         let table = Self::symbol_resolution(insts);
@@ -172,11 +177,7 @@ impl Pass {
                     _ => panic!("Only symbols and numbers are allowed in A-instructions."),
                 },
                 Instruction::Command(dest, comp, jump) => {
-                    let dest = Self::encode_dest(dest);
-                    let comp = Self::encode_comp(comp);
-                    let jump = Self::encode_jump(jump);
-
-                    let minst = format!("111{}{:03b}{:03b}", comp, dest, jump);
+                    let minst = format!("{:016b}", Self::encode_command(dest, comp, jump));
                     machine_insts.push(minst);
                 }
                 // skip label
@@ -197,6 +198,15 @@ impl Pass {
         }
     }
 
+    /// Encodes a C-instruction into its 16-bit machine word.
+    pub fn encode_command(dest: &CommandDest, comp: &Computation, jump: &CommandJump) -> u16 {
+        let dest = Self::encode_dest(dest) as u16;
+        let comp = Self::encode_comp(comp) as u16;
+        let jump = Self::encode_jump(jump) as u16;
+
+        (0b111 << 13) | (comp << 6) | (dest << 3) | jump
+    }
+
     fn encode_dest(dest: &CommandDest) -> u8 {
         match dest {
             CommandDest::NULL => 0,
@@ -210,41 +220,9 @@ impl Pass {
         }
     }
 
-    fn encode_comp(comp: &Computation) -> &'static str {
-        use Computation::*;
-        use CompValue::*;
-
-        match comp {
-            Literal(Zero) => "0101010",
-            Literal(One) =>  "0111111",
-            Negative(One) => "0111010",
-            Literal(RegD) => "0001100",
-            Literal(RegA) => "0110000",
-            Literal(RegM) => "1110000",
-            Not(RegD) => "0001101",
-            Not(RegA) => "0110001",
-            Not(RegM) => "1110001",
-            Negative(RegD) => "0001111",
-            Negative(RegA) => "0110011",
-            Negative(RegM) => "1110011",
-            Add {lhs: RegD, rhs: One} => "0011111",
-            Add {lhs: RegA, rhs: One} => "0110111",
-            Add {lhs: RegM, rhs: One} => "1110111",
-            Sub {lhs: RegD, rhs: One} => "0001110",
-            Sub {lhs: RegA, rhs: One} => "0110010",
-            Sub {lhs: RegM, rhs: One} => "1110010",
-            Add {lhs: RegD, rhs: RegA} => "0000010",
-            Add {lhs: RegD, rhs: RegM} => "1000010",
-            Sub {lhs: RegD, rhs: RegA} => "0010011",
-            Sub {lhs: RegD, rhs: RegM} => "1010011",
-            Sub {lhs: RegA, rhs: RegD} => "0000111",
-            Sub {lhs: RegM, rhs: RegD} => "1000111",
-            And {lhs: RegD, rhs: RegA} => "0000000",
-            And {lhs: RegD, rhs: RegM} => "1000000",
-            Or {lhs: RegA, rhs: RegD} => "0010101",
-            Or {lhs: RegD, rhs: RegM} => "1010101",
-            _ => panic!("Invalid operation encountered: {:?}", comp),
-        }
+    fn encode_comp(comp: &Computation) -> u8 {
+        comp.encode()
+            .unwrap_or_else(|| panic!("Invalid operation encountered: {}", comp))
     }
 
     fn encode_jump(jump: &CommandJump) -> u8 {
@@ -417,4 +395,26 @@ mod tests {
         let minsts = Pass::translate(&insts);
         println!("{}", minsts.join("\n"));
     }
+
+    #[test]
+    fn test_translate_extended_comps() {
+        let input = "
+            D=D|A
+            D=A|D
+            M=M+D
+            D=%A010011
+            D=%M000111;JNZ
+        ";
+        let mut parser = Parser::new(input);
+        let insts = parser.parse();
+        let minsts = Pass::translate(&insts);
+        let expected = vec![
+            "1110010101010000",
+            "1110010101010000",
+            "1111000010001000",
+            "1110010011010000",
+            "1111000111010101",
+        ];
+        assert_eq!(minsts, expected);
+    }
 }
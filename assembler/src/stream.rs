@@ -0,0 +1,315 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead};
+
+use crate::instruction::Instruction;
+use crate::interner::{Interner, SymbolId};
+use crate::lexer::Lexer;
+use crate::parser::{NonCanonical, Parser};
+use crate::pass::{Pass, PREDEFINED_SYMBOLS};
+use crate::token::Token;
+
+/// Number of words in the ROM, which no program may exceed.
+const ROM_SIZE: usize = 0x8000;
+
+// an instruction after the first pass: either fully encoded, or an A-instruction
+// waiting for its symbol to be resolved
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Word(u16),
+    Symbol(SymbolId),
+}
+
+/// Assembles a program line by line, without keeping the source or its tokens around.
+///
+/// Symbol names are interned, every instruction is stored as (at most) a machine word
+/// or a symbol id, and C-instructions are encoded once per distinct spelling.
+pub struct StreamAssembler {
+    interner: Interner,
+    // address of each interned symbol, indexed by `SymbolId`
+    addresses: Vec<Option<u16>>,
+    ops: Vec<Op>,
+    // encoded C-instructions, keyed by their source text.
+    // the flag is set if the spelling is not canonical.
+    commands: HashMap<Box<str>, (u16, bool)>,
+    noncanonical: Vec<NonCanonical>,
+    line: usize,
+}
+
+impl StreamAssembler {
+    pub fn new() -> Self {
+        let mut assembler = StreamAssembler {
+            interner: Interner::new(),
+            addresses: Vec::new(),
+            ops: Vec::new(),
+            commands: HashMap::new(),
+            noncanonical: Vec::new(),
+            line: 0,
+        };
+
+        for (name, address) in PREDEFINED_SYMBOLS {
+            let id = assembler.symbol(name);
+            assembler.addresses[id.0 as usize] = Some(address);
+        }
+
+        assembler
+    }
+
+    /// Runs the first pass over a single source line.
+    pub fn feed_line(&mut self, line: &str) {
+        self.line += 1;
+
+        let code = match line.find("//") {
+            Some(i) => &line[..i],
+            None => line,
+        }
+        .trim();
+
+        if code.is_empty() {
+            return;
+        }
+
+        if let Some(operand) = code.strip_prefix('@') {
+            let operand = operand.trim();
+            self.check_rom();
+            let op = if operand.starts_with(|c: char| c.is_ascii_digit()) {
+                let num = operand
+                    .parse::<u16>()
+                    .ok()
+                    .filter(|num| *num <= 0x7fff)
+                    .unwrap_or_else(|| panic!("Line {}: '{}' is not a valid 15-bit address.", self.line, operand));
+                Op::Word(num)
+            } else {
+                Self::check_symbol(operand, self.line);
+                Op::Symbol(self.symbol(operand))
+            };
+            self.ops.push(op);
+        } else if let Some(label) = code.strip_prefix('(') {
+            let Some(label) = label.strip_suffix(')') else {
+                panic!("Line {}: expected ')' to close label.", self.line);
+            };
+            let label = label.trim();
+            Self::check_symbol(label, self.line);
+
+            let id = self.symbol(label);
+            let address = &mut self.addresses[id.0 as usize];
+            if address.is_some() {
+                panic!("Line {}: the symbol '{}' is already defined.", self.line, label);
+            }
+            if self.ops.len() >= ROM_SIZE {
+                panic!("Line {}: the label '{}' is past the end of the ROM.", self.line, label);
+            }
+            *address = Some(self.ops.len() as u16);
+        } else {
+            self.check_rom();
+            let word = self.command(code);
+            self.ops.push(Op::Word(word));
+        }
+    }
+
+    /// Runs the first pass over every line `reader` yields.
+    pub fn feed<R: BufRead>(&mut self, mut reader: R) -> io::Result<()> {
+        let mut line = String::new();
+        while reader.read_line(&mut line)? != 0 {
+            self.feed_line(&line);
+            line.clear();
+        }
+        Ok(())
+    }
+
+    /// Non-canonical spellings seen so far, in source order.
+    pub fn noncanonical(&self) -> &[NonCanonical] {
+        &self.noncanonical
+    }
+
+    /// Runs the second pass, allocating variables in order of first use.
+    pub fn finish(mut self) -> Vec<u16> {
+        let mut variable_address = 0x10;
+
+        let mut words = Vec::with_capacity(self.ops.len());
+        for op in &self.ops {
+            let word = match *op {
+                Op::Word(word) => word,
+                Op::Symbol(id) => *self.addresses[id.0 as usize].get_or_insert_with(|| {
+                    variable_address += 1;
+                    variable_address - 1
+                }),
+            };
+            words.push(word);
+        }
+
+        words
+    }
+
+    // the ROM has room for one more instruction
+    fn check_rom(&self) {
+        if self.ops.len() >= ROM_SIZE {
+            panic!("Line {}: the program exceeds the {} instructions of the ROM.", self.line, ROM_SIZE);
+        }
+    }
+
+    fn symbol(&mut self, name: &str) -> SymbolId {
+        let id = self.interner.intern(name);
+        if id.0 as usize == self.addresses.len() {
+            self.addresses.push(None);
+        }
+        id
+    }
+
+    fn command(&mut self, code: &str) -> u16 {
+        if let Some((word, noncanonical)) = self.commands.get(code) {
+            let word = *word;
+            if *noncanonical {
+                // only spellings that are reported take the slow path again
+                self.parse_command(code);
+            }
+            return word;
+        }
+
+        let (word, noncanonical) = self.parse_command(code);
+        self.commands.insert(code.into(), (word, noncanonical));
+        word
+    }
+
+    fn parse_command(&mut self, code: &str) -> (u16, bool) {
+        let mut tokens = Lexer::new(code).all_tokens();
+        if let Some(Token::Eof) = tokens.last() {
+            tokens.pop();
+        }
+
+        let mut notes = Vec::new();
+        let Instruction::Command(dest, comp, jump) = Parser::parse_line_noting(&tokens, &mut notes) else {
+            panic!("Line {}: expected a C-instruction.", self.line);
+        };
+
+        let noncanonical = !notes.is_empty();
+        let line = self.line;
+        self.noncanonical
+            .extend(notes.into_iter().map(|(form, canonical)| NonCanonical { line, form, canonical }));
+
+        (Pass::encode_command(&dest, &comp, &jump), noncanonical)
+    }
+
+    // symbols consist of letters, digits, '_', '.', '$' and ':', and do not begin with a digit
+    fn check_symbol(name: &str, line: usize) {
+        let valid = !name.is_empty()
+            && !name.starts_with(|c: char| c.is_ascii_digit())
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '$' | ':'));
+
+        if !valid {
+            panic!("Line {}: '{}' is not a valid symbol.", line, name);
+        }
+    }
+}
+
+impl Default for StreamAssembler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Assembles everything `reader` yields into machine words.
+pub fn assemble<R: BufRead>(reader: R) -> io::Result<Vec<u16>> {
+    let mut assembler = StreamAssembler::new();
+    assembler.feed(reader)?;
+    Ok(assembler.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{assemble, StreamAssembler};
+    use crate::parser::Parser;
+    use crate::pass::Pass;
+
+    fn assemble_both(input: &str) -> (Vec<String>, Vec<String>) {
+        let mut parser = Parser::new(input);
+        let expected = Pass::translate(&parser.parse());
+        let words = assemble(input.as_bytes())
+            .unwrap()
+            .iter()
+            .map(|w| format!("{:016b}", w))
+            .collect();
+        (words, expected)
+    }
+
+    #[test]
+    fn test_matches_pass() {
+        let input = "
+            // Computes sum = 1 + ... + 100
+            @i
+            M=1 // i = 1
+            @sum
+            M=0
+            (LOOP)
+            @i
+            D=M
+            @100
+            D=D-A
+            @END
+            D;JGT
+            @i
+            D=M
+            @sum
+            M=D+M
+            @i
+            M=M+1
+            @LOOP
+            0;JMP
+            (END)
+            @END
+            0;JMP
+        ";
+        let (words, expected) = assemble_both(input);
+        assert_eq!(words, expected);
+    }
+
+    #[test]
+    fn test_forward_labels_and_predefined() {
+        let input = "@R13\r\nM=A+D\r\n@SCREEN\r\n@foo\r\n@JUMP\r\n(JUMP)\r\n@foo\r\nD=%M000111;JZ\r\n";
+        let (words, expected) = assemble_both(input);
+        assert_eq!(words, expected);
+    }
+
+    #[test]
+    fn test_noncanonical() {
+        let mut assembler = StreamAssembler::new();
+        for line in ["D=A+D", "D=A+D", "0;JMP", "0;JZ"] {
+            assembler.feed_line(line);
+        }
+        let lines = assembler
+            .noncanonical()
+            .iter()
+            .map(|n| (n.line, n.form.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![(1, "A+D"), (2, "A+D"), (4, "JZ")]);
+    }
+
+    #[test]
+    fn test_rom_size() {
+        let full = "D=0\n".repeat(0x8000);
+        assert_eq!(assemble(full.as_bytes()).unwrap().len(), 0x8000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Line 32769: the program exceeds the 32768 instructions of the ROM.")]
+    fn test_rom_overflow() {
+        let program = "D=0\n".repeat(0x8000) + "@0\n";
+        let _ = assemble(program.as_bytes());
+    }
+
+    #[test]
+    #[should_panic(expected = "Line 32769: the label 'END' is past the end of the ROM.")]
+    fn test_label_past_rom() {
+        let program = "D=0\n".repeat(0x8000) + "(END)\n";
+        let _ = assemble(program.as_bytes());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_duplicate_label() {
+        let mut assembler = StreamAssembler::new();
+        assembler.feed_line("(LOOP)");
+        assembler.feed_line("(LOOP)");
+    }
+}
@@ -26,6 +26,8 @@ pub enum Token {
     JLE,
     JMP,
     Number(u16),
+    // `%A010011` / `%M000111`: raw `a zx nx zy ny f no` bits
+    RawComp(u8),
     Newline,
     Eof,
 }
@@ -6,12 +6,14 @@ use crate::{
 };
 
 use super::{
-    class_var_dec::ClassVarDec, subroutine_dec::SubroutineDec, variable_scope::VariableScope,
+    class_var_dec::ClassVarDec, const_dec::ConstDec, subroutine_dec::SubroutineDec,
+    variable_scope::VariableScope,
 };
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct Class {
     pub name: String,
+    pub constants: Vec<ConstDec>,
     pub variables: Vec<ClassVarDec>,
     pub subroutines: Vec<SubroutineDec>,
 }
@@ -23,6 +25,7 @@ impl XmlPrinter for Class {
         print_tag(file, "keyword", "class");
         print_tag(file, "identifier", &self.name);
         print_symbol(file, "{");
+        self.constants.iter().for_each(|c| c.print_xml(file));
         self.variables.iter().for_each(|v| v.print_xml(file));
         self.subroutines.iter().for_each(|s| s.print_xml(file));
         print_symbol(file, "}");
@@ -42,17 +45,20 @@ impl CodeGen for Class {
             .variables
             .iter()
             // only count fields, not static variables
-            .filter(|cv| {
-                if let VariableScope::Field = cv.scope {
-                    true
-                } else {
-                    false
-                }
-            })
+            .filter(|cv| matches!(cv.scope, VariableScope::Field))
             .map(|cv| cv.vars.len())
             .sum::<usize>() as u16;
         compiler.set_current_class(&self.name, size);
+        self.constants
+            .iter()
+            .for_each(|c| c.write_code(out, compiler, symbol_table));
         self.variables.write_code(out, compiler, symbol_table);
         self.subroutines.write_code(out, compiler, symbol_table);
+        compiler.warn_unused(symbol_table.unused_statics());
+        // the pool takes the static slot after the declared static variables
+        compiler.write_string_pool(out, symbol_table.static_count());
+        if compiler.options.debug_checks {
+            compiler.write_debug_checks(out);
+        }
     }
 }
@@ -9,6 +9,8 @@ pub struct ClassVarDec {
     pub scope: VariableScope,
     pub typ: VariableType,
     pub vars: Vec<String>,
+    /// Line of the declaration, for warnings.
+    pub line: usize,
 }
 
 impl XmlPrinter for ClassVarDec {
@@ -42,7 +44,7 @@ impl CodeGen for ClassVarDec {
         };
 
         self.vars.iter().for_each(|v| {
-            symbol_table.add_variable(v, &self.typ, scope);
+            symbol_table.add_variable(v, &self.typ, scope, self.line);
         });
     }
 }
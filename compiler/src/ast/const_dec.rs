@@ -0,0 +1,47 @@
+use std::{fs::File, io::Write};
+
+use crate::{
+    codegen::{CodeGen, Compiler, SymbolTable},
+    xml_printer::{print_closing, print_opening, print_symbol, print_tag, XmlPrinter},
+};
+
+use super::{expr::Expr, variable_type::VariableType};
+
+/// `const int NAME = value;` of extended Jack. The value is computed at compile time, and
+/// every use of the constant is replaced by it, so a constant takes no static variable.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ConstDec {
+    pub typ: VariableType,
+    pub name: String,
+    pub value: Expr,
+}
+
+impl XmlPrinter for ConstDec {
+    fn print_xml(&self, file: &mut File) {
+        print_opening(file, "constDec");
+
+        print_tag(file, "keyword", "const");
+        self.typ.print_xml(file);
+        print_tag(file, "identifier", &self.name);
+        print_symbol(file, "=");
+        self.value.print_xml(file);
+        print_symbol(file, ";");
+
+        print_closing(file, "constDec");
+    }
+}
+
+impl CodeGen for ConstDec {
+    fn write_code(
+        &self,
+        _out: &mut impl Write,
+        _compiler: &mut Compiler,
+        symbol_table: &mut SymbolTable,
+    ) {
+        // only constants declared before this one are known
+        let value = self.value.constant_value(symbol_table).unwrap_or_else(|| {
+            panic!("The value of constant '{}' must be a constant expression.", self.name)
+        });
+        symbol_table.add_constant(&self.name, value);
+    }
+}
@@ -1,16 +1,101 @@
 use std::{fs::File, io::Write};
 
 use crate::{
-    codegen::{CodeGen, Compiler, SymbolTable},
+    codegen::{pop, push, push_value, AsmSection, CodeGen, Compiler, SymbolTable},
     xml_printer::{print_closing, print_opening, print_symbol, XmlPrinter},
 };
 
 use super::expr_term::ExprTerm;
 
-#[derive(Debug, PartialEq, Eq)]
+/// The comparisons of extended Jack, which are spelled with two characters, are
+/// represented by a single character operator.
+pub const NOT_EQUAL: char = '≠';
+pub const LESS_EQUAL: char = '≤';
+pub const GREATER_EQUAL: char = '≥';
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Expr {
     pub lhs: ExprTerm,
     pub rhs: Vec<(char, ExprTerm)>,
+    /// Line the expression starts on, for error messages.
+    pub line: usize,
+}
+
+impl Expr {
+    /// Returns `true` if evaluating the operators left to right, as Jack does, groups the
+    /// terms differently than conventional precedence would, as in `1 + 2 * 3`.
+    pub fn mixes_precedence(&self) -> bool {
+        self.rhs
+            .windows(2)
+            .any(|pair| precedence(pair[1].0) > precedence(pair[0].0))
+    }
+
+    /// The value of the expression if it only involves literals and constants of the class.
+    pub fn constant_value(&self, symbol_table: &SymbolTable) -> Option<u16> {
+        self.rhs
+            .iter()
+            .try_fold(self.lhs.constant_value(symbol_table)?, |value, (op, term)| {
+                apply_operator(*op, value, term.constant_value(symbol_table)?)
+            })
+    }
+}
+
+/// Applies a binary operator at compile time, with the same result as the VM code and the
+/// OS would give at run time. Returns `None` where that result is not certain: a division
+/// by zero is left to `Math.divide`, and the Hack code of `lt` and `gt` compares the sign of
+/// `x - y`, which differs from the true order when the subtraction overflows.
+pub fn apply_operator(op: char, a: u16, b: u16) -> Option<u16> {
+    let (x, y) = (a as i16, b as i16);
+    let truth = |condition: bool| if condition { 0xffff } else { 0 };
+    Some(match op {
+        '+' => a.wrapping_add(b),
+        '-' => a.wrapping_sub(b),
+        '*' => a.wrapping_mul(b),
+        '/' if y == 0 => return None,
+        '/' => x.wrapping_div(y) as u16,
+        '&' => a & b,
+        '|' => a | b,
+        '=' => truth(x == y),
+        NOT_EQUAL => truth(x != y),
+        '<' | '>' | LESS_EQUAL | GREATER_EQUAL if x.checked_sub(y).is_none() => return None,
+        '<' => truth(x < y),
+        '>' => truth(x > y),
+        LESS_EQUAL => truth(x <= y),
+        GREATER_EQUAL => truth(x >= y),
+        _ => panic!("Operator '{op}' not supported as binary operator."),
+    })
+}
+
+/// Conventional precedence of a binary operator, from `|` (lowest) to `*` and `/`.
+pub fn precedence(op: char) -> u8 {
+    match op {
+        '*' | '/' => 5,
+        '+' | '-' => 4,
+        '<' | '>' | LESS_EQUAL | GREATER_EQUAL => 3,
+        '=' | NOT_EQUAL => 2,
+        '&' => 1,
+        '|' => 0,
+        _ => panic!("Operator '{op}' not supported as binary operator."),
+    }
+}
+
+/// VM code that applies a binary operator to the two values on top of the stack.
+pub fn operator_code(op: char) -> &'static str {
+    match op {
+        '+' => "add",
+        '-' => "sub",
+        '*' => "call Math.multiply 2",
+        '/' => "call Math.divide 2",
+        '&' => "and",
+        '|' => "or",
+        '<' => "lt",
+        '>' => "gt",
+        '=' => "eq",
+        NOT_EQUAL => "eq\nnot",
+        LESS_EQUAL => "gt\nnot",
+        GREATER_EQUAL => "lt\nnot",
+        _ => panic!("Operator '{op}' not supported as binary operator."),
+    }
 }
 
 impl XmlPrinter for Expr {
@@ -50,24 +135,82 @@ impl CodeGen for Expr {
         compiler: &mut Compiler,
         symbol_table: &mut SymbolTable,
     ) {
-        self.lhs.write_code(out, compiler, symbol_table);
+        compiler.line = self.line;
+        if !compiler.options.optimize {
+            self.lhs.write_code(out, compiler, symbol_table);
 
-        self.rhs.iter().for_each(|(op, expr)| {
-            expr.write_code(out, compiler, symbol_table);
-            let inst = match op {
-                '+' => "add",
-                '-' => "sub",
-                '*' => "call Math.multiply 2",
-                '/' => "call Math.divide 2",
-                '&' => "and",
-                '|' => "or",
-                '<' => "lt",
-                '>' => "gt",
-                '=' => "eq",
-                _ => panic!("Operator '{op}' not supported as unary operator."),
-            };
-            writeln!(out, "{}", inst).unwrap();
-        });
+            self.rhs.iter().for_each(|(op, expr)| {
+                expr.write_code(out, compiler, symbol_table);
+                writeln!(out, "{}", operator_code(*op)).unwrap();
+            });
+            return;
+        }
+
+        let mut rhs = self.rhs.iter().peekable();
+        match self.lhs.constant_value(symbol_table) {
+            Some(mut value) => {
+                // fold the terms up to the first one that is not constant
+                while let Some(next) = rhs.peek().and_then(|(op, term)| {
+                    apply_operator(*op, value, term.constant_value(symbol_table)?)
+                }) {
+                    rhs.next();
+                    value = next;
+                }
+                match rhs.next() {
+                    None => push_value(out, value),
+                    // multiplication commutes, so the constant can still be strength reduced
+                    Some(('*', term)) => {
+                        term.write_code(out, compiler, symbol_table);
+                        write_constant_operation(out, '*', value);
+                    }
+                    Some((op, term)) => {
+                        push_value(out, value);
+                        term.write_code(out, compiler, symbol_table);
+                        writeln!(out, "{}", operator_code(*op)).unwrap();
+                    }
+                }
+            }
+            None => self.lhs.write_code(out, compiler, symbol_table),
+        }
+
+        for (op, term) in rhs {
+            match term.constant_value(symbol_table) {
+                Some(value) => write_constant_operation(out, *op, value),
+                None => {
+                    term.write_code(out, compiler, symbol_table);
+                    writeln!(out, "{}", operator_code(*op)).unwrap();
+                }
+            }
+        }
+    }
+}
+
+/// Applies `op` to the value on the stack and a constant. Multiplication by a power of two
+/// is reduced to additions, which cost far less than calling `Math.multiply`. Division is
+/// left to `Math.divide`, since the VM cannot shift and adding up the bits of the quotient
+/// takes more code than the call.
+pub fn write_constant_operation(out: &mut impl Write, op: char, value: u16) {
+    match op {
+        '*' if value.is_power_of_two() => double(out, value.trailing_zeros()),
+        '*' if value.wrapping_neg().is_power_of_two() => {
+            double(out, value.wrapping_neg().trailing_zeros());
+            writeln!(out, "neg").unwrap();
+        }
+        '/' if value == 1 => {}
+        _ => {
+            push_value(out, value);
+            writeln!(out, "{}", operator_code(op)).unwrap();
+        }
+    }
+}
+
+// multiplies the value on the stack by 2^k, adding it to itself k times
+fn double(out: &mut impl Write, k: u32) {
+    for _ in 0..k {
+        pop(out, AsmSection::Temp, 0);
+        push(out, AsmSection::Temp, 0);
+        push(out, AsmSection::Temp, 0);
+        writeln!(out, "add").unwrap();
     }
 }
 
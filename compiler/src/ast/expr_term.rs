@@ -1,15 +1,20 @@
 use std::{fs::File, io::Write};
 
 use crate::{
+    charset,
     codegen::{
-        call_function, pop, push, push_constant, AsmSection, CodeGen, Compiler, SymbolTable,
+        call_function, new_string, pop, push, push_constant, push_value, AsmSection, CodeGen, Compiler,
+        SymbolTable, STRING_POOL,
     },
     xml_printer::{print_closing, print_opening, print_symbol, print_tag, XmlPrinter},
 };
 
-use super::{expr::Expr, subroutine_call::SubroutineCall};
+use super::{
+    expr::{apply_operator, operator_code, write_constant_operation, Expr},
+    subroutine_call::SubroutineCall,
+};
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ExprTerm {
     Integer(u16),
     Str(String),
@@ -22,6 +27,32 @@ pub enum ExprTerm {
     Call(SubroutineCall),
     Group(Box<Expr>),
     Unary(char, Box<ExprTerm>),
+    /// Operator applied to two terms. Only built with `--precedence`; otherwise the
+    /// operators of an expression are kept in the flat `Expr::rhs`.
+    Binary(char, Box<ExprTerm>, Box<ExprTerm>),
+}
+
+impl ExprTerm {
+    /// The value of the term if it only involves literals and constants of the class.
+    pub fn constant_value(&self, symbol_table: &SymbolTable) -> Option<u16> {
+        use ExprTerm::*;
+
+        match self {
+            Integer(num) => Some(*num),
+            True => Some(0xffff),
+            False | Null => Some(0),
+            Variable(v) => symbol_table.resolve_constant(v),
+            Group(expr) => expr.constant_value(symbol_table),
+            Unary('-', term) => term.constant_value(symbol_table).map(u16::wrapping_neg),
+            Unary('~', term) => term.constant_value(symbol_table).map(|value| !value),
+            Binary(op, lhs, rhs) => apply_operator(
+                *op,
+                lhs.constant_value(symbol_table)?,
+                rhs.constant_value(symbol_table)?,
+            ),
+            _ => None,
+        }
+    }
 }
 
 impl XmlPrinter for ExprTerm {
@@ -54,6 +85,13 @@ impl XmlPrinter for ExprTerm {
                 op.print_xml(file);
                 term.print_xml(file);
             }
+            Binary(op, lhs, rhs) => {
+                print_opening(file, "expression");
+                lhs.print_xml(file);
+                op.print_xml(file);
+                rhs.print_xml(file);
+                print_closing(file, "expression");
+            }
         }
 
         print_closing(file, "term");
@@ -70,26 +108,32 @@ impl CodeGen for ExprTerm {
     ) {
         use ExprTerm::*;
 
+        if compiler.options.optimize {
+            if let Some(value) = self.constant_value(symbol_table) {
+                push_value(out, value);
+                return;
+            }
+        }
+
         match self {
-            Integer(num) => writeln!(out, "push constant {num}").unwrap(),
+            Integer(num) => push_value(out, *num),
             Str(ref s) => {
-                let chars = s.chars().collect::<Vec<char>>();
-
-                // Create string object first.
-                push_constant(
-                    out,
-                    chars
-                        .len()
-                        .try_into()
-                        .expect("String length is limited to 16 bits."),
-                );
-                call_function(out, "String.new", 1);
+                // the lexer already rejected literals that cannot be decoded
+                let codes = if compiler.options.escapes {
+                    charset::decode(s)
+                } else {
+                    charset::encode(s)
+                };
+                let codes = codes.unwrap_or_else(|e| panic!("{e} in string literal \"{s}\""));
 
-                chars.iter().for_each(|c| {
-                    let codepoint = *c as u16;
-                    push_constant(out, codepoint);
-                    call_function(out, "String.appendChar", 2);
-                });
+                if compiler.options.string_pool {
+                    let class = &compiler.current_class.as_ref().expect("The class is known.").name;
+                    let function = format!("{class}.{STRING_POOL}");
+                    push_constant(out, compiler.pool_string(codes));
+                    call_function(out, &function, 1);
+                } else {
+                    new_string(out, &codes);
+                }
             }
             True => {
                 push_constant(out, 0);
@@ -98,11 +142,24 @@ impl CodeGen for ExprTerm {
             False => push_constant(out, 0),
             Null => push_constant(out, 0),
             This => writeln!(out, "push pointer 0").unwrap(),
-            Variable(v) => {
+            Variable(v) => match symbol_table.resolve_constant(v) {
+                Some(value) => push_value(out, value),
+                None => {
+                    let entry = symbol_table
+                        .resolve_variable(v)
+                        .unwrap_or_else(|| panic!("Variable '{v}' not found in symbol table."));
+                    push(out, entry.scope.into(), entry.id);
+                }
+            },
+            Access(v, expr) if compiler.options.debug_checks => {
                 let entry = symbol_table
                     .resolve_variable(v)
                     .unwrap_or_else(|| panic!("Variable '{v}' not found in symbol table."));
                 push(out, entry.scope.into(), entry.id);
+                expr.write_code(out, compiler, symbol_table);
+                compiler.check_index(out);
+                pop(out, AsmSection::Pointer, 1);
+                push(out, AsmSection::That, 0);
             }
             Access(v, expr) => {
                 expr.write_code(out, compiler, symbol_table);
@@ -116,6 +173,8 @@ impl CodeGen for ExprTerm {
             }
             Call(sc) => sc.write_code(out, compiler, symbol_table),
             Group(expr) => expr.write_code(out, compiler, symbol_table),
+            // -32768 is its own negation
+            Unary('-', term) if **term == Integer(32768) => push_value(out, 32768),
             Unary(op, expr) => {
                 expr.write_code(out, compiler, symbol_table);
                 match op {
@@ -124,6 +183,28 @@ impl CodeGen for ExprTerm {
                     _ => panic!("Operator '{op}' not supported as unary operator."),
                 }
             }
+            Binary(op, lhs, rhs) if compiler.options.optimize => {
+                match (lhs.constant_value(symbol_table), *op, rhs.constant_value(symbol_table)) {
+                    (_, op, Some(value)) => {
+                        lhs.write_code(out, compiler, symbol_table);
+                        write_constant_operation(out, op, value);
+                    }
+                    (Some(value), '*', None) => {
+                        rhs.write_code(out, compiler, symbol_table);
+                        write_constant_operation(out, '*', value);
+                    }
+                    _ => {
+                        lhs.write_code(out, compiler, symbol_table);
+                        rhs.write_code(out, compiler, symbol_table);
+                        writeln!(out, "{}", operator_code(*op)).unwrap();
+                    }
+                }
+            }
+            Binary(op, lhs, rhs) => {
+                lhs.write_code(out, compiler, symbol_table);
+                rhs.write_code(out, compiler, symbol_table);
+                writeln!(out, "{}", operator_code(*op)).unwrap();
+            }
         }
     }
 }
@@ -1,5 +1,6 @@
 pub mod class;
 pub mod class_var_dec;
+pub mod const_dec;
 pub mod expr;
 pub mod expr_term;
 pub mod statement;
@@ -1,18 +1,23 @@
 use std::{fs::File, io::Write};
 
 use crate::{
-    codegen::{pop, push, AsmSection, CodeGen, Compiler, SymbolTable},
+    codegen::{pop, push, push_value, AsmSection, CodeGen, Compiler, SymbolTable},
     xml_printer::{print_closing, print_opening, print_symbol, print_tag, XmlPrinter},
 };
 
 use super::{expr::Expr, subroutine_call::SubroutineCall};
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Statement {
     Let {
         name: String,
         access: Option<Expr>,
+        /// The operator of a compound assignment to an array element, as in `a[i] += x`,
+        /// whose `expr` is then `x`. The address of the element is computed once.
+        compound: Option<char>,
         expr: Expr,
+        /// Line of the assigned variable, for diagnostics.
+        line: usize,
     },
     If {
         condition: Expr,
@@ -23,11 +28,32 @@ pub enum Statement {
         condition: Expr,
         statements: Vec<Statement>,
     },
+    /// `for (init; condition; step) { statements }` of extended Jack, where `init` and
+    /// `step` are assignments. A missing condition always holds.
+    For {
+        init: Option<Box<Statement>>,
+        condition: Option<Expr>,
+        step: Option<Box<Statement>>,
+        statements: Vec<Statement>,
+    },
+    /// `switch (selector) { case 1, 2: statements ... default: statements }` of extended
+    /// Jack. Case values are constant expressions, and cases do not fall through.
+    Switch {
+        selector: Expr,
+        cases: Vec<(Vec<Expr>, Vec<Statement>)>,
+        default: Option<Vec<Statement>>,
+    },
+    Break,
+    Continue,
     Do {
         call: SubroutineCall,
+        /// Line of the called subroutine, for diagnostics.
+        line: usize,
     },
     Return {
         value: Option<Expr>,
+        /// Line of the value, or of the `;` without one, for diagnostics.
+        line: usize,
     },
 }
 
@@ -37,7 +63,9 @@ impl XmlPrinter for Statement {
             Statement::Let {
                 ref name,
                 access,
+                compound,
                 expr,
+                ..
             } => {
                 print_opening(file, "letStatement");
                 print_tag(file, "keyword", "let");
@@ -47,7 +75,10 @@ impl XmlPrinter for Statement {
                     access_expr.print_xml(file);
                     print_symbol(file, "]");
                 }
-                print_symbol(file, "=");
+                match compound {
+                    Some(op) => print_symbol(file, &format!("{op}=")),
+                    None => print_symbol(file, "="),
+                }
                 expr.print_xml(file);
                 print_symbol(file, ";");
                 print_closing(file, "letStatement");
@@ -89,14 +120,81 @@ impl XmlPrinter for Statement {
                 print_symbol(file, "}");
                 print_closing(file, "whileStatement");
             }
-            Statement::Do { call } => {
+            Statement::For {
+                init,
+                condition,
+                step,
+                statements,
+            } => {
+                print_opening(file, "forStatement");
+                print_tag(file, "keyword", "for");
+                print_symbol(file, "(");
+                if let Some(init) = init {
+                    init.print_xml(file);
+                }
+                print_symbol(file, ";");
+                if let Some(condition) = condition {
+                    condition.print_xml(file);
+                }
+                print_symbol(file, ";");
+                if let Some(step) = step {
+                    step.print_xml(file);
+                }
+                print_symbol(file, ")");
+                print_symbol(file, "{");
+                statements.print_xml(file);
+                print_symbol(file, "}");
+                print_closing(file, "forStatement");
+            }
+            Statement::Switch {
+                selector,
+                cases,
+                default,
+            } => {
+                print_opening(file, "switchStatement");
+                print_tag(file, "keyword", "switch");
+                print_symbol(file, "(");
+                selector.print_xml(file);
+                print_symbol(file, ")");
+                print_symbol(file, "{");
+                for (values, statements) in cases {
+                    print_tag(file, "keyword", "case");
+                    values[0].print_xml(file);
+                    values.iter().skip(1).for_each(|value| {
+                        print_symbol(file, ",");
+                        value.print_xml(file);
+                    });
+                    print_symbol(file, ":");
+                    statements.print_xml(file);
+                }
+                if let Some(default) = default {
+                    print_tag(file, "keyword", "default");
+                    print_symbol(file, ":");
+                    default.print_xml(file);
+                }
+                print_symbol(file, "}");
+                print_closing(file, "switchStatement");
+            }
+            Statement::Break => {
+                print_opening(file, "breakStatement");
+                print_tag(file, "keyword", "break");
+                print_symbol(file, ";");
+                print_closing(file, "breakStatement");
+            }
+            Statement::Continue => {
+                print_opening(file, "continueStatement");
+                print_tag(file, "keyword", "continue");
+                print_symbol(file, ";");
+                print_closing(file, "continueStatement");
+            }
+            Statement::Do { call, .. } => {
                 print_opening(file, "doStatement");
                 print_tag(file, "keyword", "do");
                 call.print_xml(file);
                 print_symbol(file, ";");
                 print_closing(file, "doStatement");
             }
-            Statement::Return { value } => {
+            Statement::Return { value, .. } => {
                 print_opening(file, "returnStatement");
                 print_tag(file, "keyword", "return");
                 if let Some(expr) = value {
@@ -127,22 +225,75 @@ impl CodeGen for Statement {
         use Statement::*;
 
         match self {
-            Let { name, access, expr } => {
+            Let {
+                name,
+                access: Some(access_expr),
+                compound: Some(op),
+                expr,
+                line,
+            } => {
+                compiler.line = *line;
+                let entry = symbol_table
+                    .resolve_variable(name)
+                    .unwrap_or_else(|| panic!("Variable '{name}' not found in symbol table."));
+                let (section, index) = (entry.scope.into(), entry.id);
+                if compiler.options.debug_checks {
+                    push(out, section, index);
+                    access_expr.write_code(out, compiler, symbol_table);
+                    compiler.check_index(out);
+                } else {
+                    access_expr.write_code(out, compiler, symbol_table);
+                    push(out, section, index);
+                    writeln!(out, "add").unwrap();
+                }
+                // the address stays on the stack while the element and `expr` are evaluated
+                pop(out, AsmSection::Pointer, 1);
+                push(out, AsmSection::Pointer, 1);
+                push(out, AsmSection::That, 0);
+                expr.write_code(out, compiler, symbol_table);
+                match op {
+                    '+' => writeln!(out, "add").unwrap(),
+                    '-' => writeln!(out, "sub").unwrap(),
+                    op => unreachable!("No compound assignment with '{op}'."),
+                }
+                pop(out, AsmSection::Temp, 0);
+                pop(out, AsmSection::Pointer, 1);
+                push(out, AsmSection::Temp, 0);
+                pop(out, AsmSection::That, 0);
+            }
+            Let {
+                name,
+                access,
+                expr,
+                line,
+                ..
+            } => {
+                compiler.line = *line;
                 expr.write_code(out, compiler, symbol_table);
 
                 if let Some(access_expr) = access {
-                    access_expr.write_code(out, compiler, symbol_table);
-
                     let entry = symbol_table
                         .resolve_variable(name)
                         .unwrap_or_else(|| panic!("Variable '{name}' not found in symbol table."));
-                    push(out, entry.scope.into(), entry.id);
-                    writeln!(out, "add").unwrap();
+                    let (section, index) = (entry.scope.into(), entry.id);
+                    if compiler.options.debug_checks {
+                        push(out, section, index);
+                        access_expr.write_code(out, compiler, symbol_table);
+                        compiler.check_index(out);
+                    } else {
+                        access_expr.write_code(out, compiler, symbol_table);
+                        push(out, section, index);
+                        writeln!(out, "add").unwrap();
+                    }
                     pop(out, AsmSection::Pointer, 1);
                     pop(out, AsmSection::That, 0);
                 } else {
+                    if symbol_table.resolve_constant(name).is_some() {
+                        panic!("Cannot assign to constant '{name}'.");
+                    }
+                    // assigning a variable does not count as using it
                     let entry = symbol_table
-                        .resolve_variable(name)
+                        .resolve_target(name)
                         .unwrap_or_else(|| panic!("Variable '{name}' not found in symbol table."));
                     pop(out, entry.scope.into(), entry.id);
                 }
@@ -195,17 +346,111 @@ impl CodeGen for Statement {
                 writeln!(out, "if-goto WHILE_END{label}").unwrap();
 
                 // otherwise, run the body.
+                compiler.enter_loop(format!("WHILE_EXP{label}"), format!("WHILE_END{label}"));
                 statements.write_code(out, compiler, symbol_table);
+                compiler.exit_loop();
 
                 writeln!(out, "goto WHILE_EXP{label}").unwrap();
                 writeln!(out, "label WHILE_END{label}").unwrap();
             }
-            Do { call } => {
+            For {
+                init,
+                condition,
+                step,
+                statements,
+            } => {
+                let label = compiler.get_new_branch_counter();
+                if let Some(init) = init {
+                    init.write_code(out, compiler, symbol_table);
+                }
+
+                writeln!(out, "label FOR_EXP{label}").unwrap();
+                if let Some(condition) = condition {
+                    condition.write_code(out, compiler, symbol_table);
+                    writeln!(out, "not").unwrap();
+                    writeln!(out, "if-goto FOR_END{label}").unwrap();
+                }
+
+                compiler.enter_loop(format!("FOR_STEP{label}"), format!("FOR_END{label}"));
+                statements.write_code(out, compiler, symbol_table);
+                compiler.exit_loop();
+
+                writeln!(out, "label FOR_STEP{label}").unwrap();
+                if let Some(step) = step {
+                    step.write_code(out, compiler, symbol_table);
+                }
+                writeln!(out, "goto FOR_EXP{label}").unwrap();
+                writeln!(out, "label FOR_END{label}").unwrap();
+            }
+            Switch {
+                selector,
+                cases,
+                default,
+            } => {
+                let label = compiler.get_new_branch_counter();
+                selector.write_code(out, compiler, symbol_table);
+                pop(out, AsmSection::Temp, 0);
+
+                // compare the selector with each value in turn. The VM has no computed jump,
+                // so a jump table cannot be expressed.
+                let mut seen = Vec::new();
+                for (i, (values, _)) in cases.iter().enumerate() {
+                    for value in values {
+                        let value = value
+                            .constant_value(symbol_table)
+                            .expect("Case values must be constant expressions.");
+                        if seen.contains(&value) {
+                            panic!("Duplicate case value {} in switch statement.", value as i16);
+                        }
+                        seen.push(value);
+
+                        push(out, AsmSection::Temp, 0);
+                        push_value(out, value);
+                        writeln!(out, "eq").unwrap();
+                        writeln!(out, "if-goto SWITCH_CASE{label}_{i}").unwrap();
+                    }
+                }
+                if default.is_some() {
+                    writeln!(out, "goto SWITCH_DEFAULT{label}").unwrap();
+                } else {
+                    writeln!(out, "goto SWITCH_END{label}").unwrap();
+                }
+
+                compiler.enter_switch(format!("SWITCH_END{label}"));
+                for (i, (_, statements)) in cases.iter().enumerate() {
+                    writeln!(out, "label SWITCH_CASE{label}_{i}").unwrap();
+                    statements.write_code(out, compiler, symbol_table);
+                    writeln!(out, "goto SWITCH_END{label}").unwrap();
+                }
+                if let Some(default) = default {
+                    writeln!(out, "label SWITCH_DEFAULT{label}").unwrap();
+                    default.write_code(out, compiler, symbol_table);
+                }
+                compiler.exit_loop();
+
+                writeln!(out, "label SWITCH_END{label}").unwrap();
+            }
+            Break => {
+                let (_, end) = compiler
+                    .current_loop()
+                    .expect("'break' must be inside a loop or switch.");
+                writeln!(out, "goto {end}").unwrap();
+            }
+            Continue => {
+                let next = compiler
+                    .current_loop()
+                    .and_then(|(next, _)| next)
+                    .expect("'continue' must be inside a loop.");
+                writeln!(out, "goto {next}").unwrap();
+            }
+            Do { call, line } => {
+                compiler.line = *line;
                 call.write_code(out, compiler, symbol_table);
                 // move the return value to a temporary variable
                 pop(out, AsmSection::Temp, 0);
             }
-            Return { value } => {
+            Return { value, line } => {
+                compiler.line = *line;
                 if let Some(expr) = value {
                     expr.write_code(out, compiler, symbol_table);
                 } else {
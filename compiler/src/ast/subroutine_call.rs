@@ -1,13 +1,13 @@
 use std::{fs::File, io::Write};
 
 use crate::{
-    codegen::{push, push_this, CodeGen, Compiler, SymbolTable},
+    codegen::{call_function, push, push_this, CodeGen, Compiler, SymbolTable, ARRAY_DISPOSE, ARRAY_NEW, ARRAY_TAGGED},
     xml_printer::{print_symbol, print_tag, XmlPrinter}, ast::{variable_type::VariableType, subroutine_kind::SubroutineKind},
 };
 
 use super::expr::Expr;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SubroutineCall {
     Function(String, Vec<Expr>),
     Method(String, String, Vec<Expr>),
@@ -86,6 +86,26 @@ impl CodeGen for SubroutineCall {
                     // ex> compilation of bat.dispose() should result in
                     // pushing bat's `this` to the stack first.
                     push(out, entry.scope.into(), entry.id);
+                    if compiler.options.debug_checks {
+                        compiler.check_null(out);
+                    }
+
+                    // with debug checks, any variable may hold an array with a header, which
+                    // only `ARRAY_DISPOSE` frees correctly.
+                    if compiler.options.debug_checks && method == "dispose" && args.is_empty() {
+                        let class = compiler.current_class.as_ref().unwrap().name.clone();
+                        let counter = compiler.get_new_branch_counter();
+                        call_function(out, &format!("{class}.{}", ARRAY_TAGGED.0), ARRAY_TAGGED.1);
+                        writeln!(out, "if-goto DISPOSE_ARRAY{counter}").unwrap();
+                        push(out, entry.scope.into(), entry.id);
+                        writeln!(out, "{}", call_inst).unwrap();
+                        writeln!(out, "goto DISPOSE_END{counter}").unwrap();
+                        writeln!(out, "label DISPOSE_ARRAY{counter}").unwrap();
+                        push(out, entry.scope.into(), entry.id);
+                        call_function(out, &format!("{class}.{}", ARRAY_DISPOSE.0), ARRAY_DISPOSE.1);
+                        writeln!(out, "label DISPOSE_END{counter}").unwrap();
+                        return;
+                    }
                     // push rest of arguments.
                     args.write_code(out, compiler, symbol_table);
 
@@ -97,8 +117,20 @@ impl CodeGen for SubroutineCall {
                     // push arguments to stack
                     args.write_code(out, compiler, symbol_table);
 
-                    // call function
-                    writeln!(out, "call {namespace}.{method} {}", args.len()).unwrap();
+                    // call function. with debug checks, arrays get a header holding their length.
+                    // freeing memory goes through a check for that header.
+                    let class = &compiler.current_class.as_ref().unwrap().name;
+                    let dispose = matches!(
+                        (namespace.as_str(), method.as_str()),
+                        ("Memory", "deAlloc") | ("Array", "dispose")
+                    );
+                    if compiler.options.debug_checks && namespace == "Array" && method == "new" {
+                        writeln!(out, "call {class}.{} {}", ARRAY_NEW.0, ARRAY_NEW.1).unwrap();
+                    } else if compiler.options.debug_checks && dispose && args.len() == 1 {
+                        writeln!(out, "call {class}.{} {}", ARRAY_DISPOSE.0, ARRAY_DISPOSE.1).unwrap();
+                    } else {
+                        writeln!(out, "call {namespace}.{method} {}", args.len()).unwrap();
+                    }
                 }
             }
         }
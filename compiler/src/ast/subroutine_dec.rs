@@ -16,6 +16,8 @@ pub struct SubroutineDec {
     pub name: String,
     pub parameters: Vec<(VariableType, String)>,
     pub body: SubroutineBody,
+    /// Line of the subroutine name, for warnings.
+    pub line: usize,
 }
 
 impl XmlPrinter for SubroutineDec {
@@ -81,7 +83,7 @@ impl CodeGen for SubroutineDec {
 
         compiler.current_subroutine_kind = Some(self.kind);
         self.parameters.iter().for_each(|(typ, param)| {
-            symbol_table.add_variable(param, typ, SymbolScope::Argument);
+            symbol_table.add_variable(param, typ, SymbolScope::Argument, self.line);
         });
 
         match self.kind {
@@ -92,19 +94,21 @@ impl CodeGen for SubroutineDec {
                 writeln!(out, "call Memory.alloc 1").unwrap();
                 pop(out, AsmSection::Pointer, 0);
             }
-            Function =>
-            /* Do nothing */
-            {
-                ()
-            }
+            Function => { /* Do nothing */ }
             Method => {
                 // set `this` to the provided `this`
                 push(out, AsmSection::Argument, 0);
+                if compiler.options.debug_checks {
+                    // fields are accessed through `this`
+                    compiler.line = self.line;
+                    compiler.check_null(out);
+                }
                 pop(out, AsmSection::Pointer, 0);
             }
         }
 
         self.body.write_code(out, compiler, symbol_table);
+        compiler.warn_unused(symbol_table.unused_locals());
     }
 }
 
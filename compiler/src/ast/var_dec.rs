@@ -8,6 +8,8 @@ use super::variable_type::VariableType;
 pub struct VarDec {
     pub typ: VariableType,
     pub vars: Vec<String>,
+    /// Line of the declaration, for warnings.
+    pub line: usize,
 }
 
 impl XmlPrinter for VarDec {
@@ -37,7 +39,7 @@ impl CodeGen for VarDec {
         // we have default types char, int, boolean
         // and we have reference types.
         self.vars.iter().for_each(|v| {
-            symbol_table.add_variable(v, &self.typ, SymbolScope::Local);
+            symbol_table.add_variable(v, &self.typ, SymbolScope::Local, self.line);
         });
     }
 }
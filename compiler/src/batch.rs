@@ -0,0 +1,151 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::ast::class::Class;
+use crate::build_cache::Interface;
+use crate::codegen::{
+    Compiler, ARRAY_DISPOSE, ARRAY_NEW, ARRAY_TAGGED, CHECK_INDEX, CHECK_NULL, STRING_POOL,
+};
+use crate::cross_check::interface;
+use crate::options::Options;
+use crate::parser::Parser;
+use crate::type_check::declarations;
+use crate::warnings::{unused_assignments, Lint, Pragmas, Warning};
+
+/// Result of compiling a single class.
+#[derive(Debug)]
+pub struct CompiledClass {
+    pub vm: Vec<u8>,
+    pub interface: Interface,
+    /// Warnings not allowed by a pragma, by line.
+    pub warnings: Vec<Warning>,
+    /// Line of each subroutine, for the project-wide check for subroutines that are never
+    /// called. Subroutines allowed to be unused by a pragma are left out.
+    pub subroutine_lines: BTreeMap<String, usize>,
+    /// See `type_check::declarations`.
+    pub declarations: String,
+}
+
+pub fn compile_class(source: &str, options: &Options) -> CompiledClass {
+    let (pragmas, unknown) = Pragmas::parse(source);
+    if !unknown.is_empty() {
+        panic!("{}", unknown.join("\n"));
+    }
+
+    let mut parser = Parser::with_options(source, options);
+    let ast: Class = parser.parse();
+
+    let mut vm = Vec::new();
+    let mut compiler = Compiler::with_options(options.clone());
+    compiler.compile(&ast, &mut vm);
+
+    let mut interface = interface(&ast);
+    if compiler.has_string_pool() {
+        interface.insert(STRING_POOL.to_string(), 1);
+    }
+    if options.debug_checks {
+        for (name, args) in [CHECK_NULL, CHECK_INDEX, ARRAY_NEW, ARRAY_TAGGED, ARRAY_DISPOSE] {
+            interface.insert(name.to_string(), args);
+        }
+    }
+
+    let mut warnings = parser.warnings().to_vec();
+    warnings.extend_from_slice(compiler.warnings());
+    ast.subroutines
+        .iter()
+        .for_each(|subroutine| warnings.extend(unused_assignments(subroutine)));
+    warnings.retain(|w| !pragmas.allows(w.lint, w.line));
+    warnings.sort_by_key(|w| w.line);
+
+    let subroutine_lines = ast
+        .subroutines
+        .iter()
+        .filter(|s| !pragmas.allows(Lint::UnusedSubroutine, s.line))
+        .map(|s| (s.name.clone(), s.line))
+        .collect();
+
+    CompiledClass {
+        vm,
+        interface,
+        warnings,
+        subroutine_lines,
+        declarations: declarations(&ast),
+    }
+}
+
+/// Compiles every source on a pool of `jobs` threads.
+///
+/// Each class gets its own `Compiler`, so labels do not depend on which thread
+/// compiled a class or in which order. Results are returned in the order of `sources`.
+pub fn compile_all(sources: &[String], jobs: usize, options: &Options) -> Vec<CompiledClass> {
+    let jobs = jobs.clamp(1, sources.len().max(1));
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..sources.len()).map(|_| None).collect::<Vec<_>>());
+
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                if i >= sources.len() {
+                    break;
+                }
+
+                let compiled = compile_class(&sources[i], options);
+                results.lock().unwrap()[i] = Some(compiled);
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|compiled| compiled.expect("Every class is compiled once the pool finishes."))
+        .collect()
+}
+
+/// Number of threads to use when none is requested.
+pub fn default_jobs() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::compile_all;
+    use crate::options::Options;
+
+    #[test]
+    fn test_deterministic() {
+        let sources = (0..16)
+            .map(|i| {
+                format!(
+                    "class C{i} {{
+                        function int f(int x) {{
+                            while (x > 0) {{
+                                if (x = {i}) {{ return x; }} else {{ let x = x - 1; }}
+                            }}
+                            return C{}.f(x);
+                        }}
+                    }}",
+                    (i + 1) % 16
+                )
+            })
+            .collect::<Vec<String>>();
+
+        let serial = compile_all(&sources, 1, &Options::default());
+        let parallel = compile_all(&sources, 4, &Options::default());
+
+        assert_eq!(serial.len(), 16);
+        for (s, p) in serial.iter().zip(&parallel) {
+            assert_eq!(s.vm, p.vm);
+            assert_eq!(s.interface, p.interface);
+        }
+
+        let first = String::from_utf8(serial[0].vm.clone()).unwrap();
+        assert!(first.starts_with("function C0.f 0\n"));
+        assert!(first.contains("label WHILE_EXP1\n"));
+        assert!(first.contains("if-goto IF_TRUE2\n"));
+    }
+}
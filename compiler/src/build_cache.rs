@@ -0,0 +1,274 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::options::Options;
+use crate::type_check::Strictness;
+use crate::warnings::{Lint, Warning};
+
+/// Name of the manifest written next to the generated .vm files.
+pub const CACHE_FILENAME: &str = ".jack-build-cache";
+
+/// Subroutine names of a class, mapped to how many arguments a `call` to them passes.
+pub type Interface = BTreeMap<String, u16>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheEntry {
+    pub hash: u64,
+    pub interface: Interface,
+    /// See `CompiledClass::subroutine_lines`.
+    pub subroutine_lines: BTreeMap<String, usize>,
+    /// Warnings of the class, so that they are reported again without recompiling it.
+    pub warnings: Vec<Warning>,
+    /// See `type_check::declarations`.
+    pub declarations: String,
+    /// Key of the last type check the class passed, see `type_key`.
+    pub types: Option<u64>,
+}
+
+/// Remembers, per class, which source produced the .vm output that is on disk.
+pub struct BuildCache {
+    path: PathBuf,
+    entries: HashMap<String, CacheEntry>,
+}
+
+impl BuildCache {
+    /// Loads the manifest at `path`. A missing manifest, or one written by another build
+    /// of the compiler, yields an empty cache.
+    pub fn load(path: &Path) -> Self {
+        let entries = fs::read_to_string(path)
+            .ok()
+            .and_then(|manifest| Self::parse(&manifest))
+            .unwrap_or_default();
+
+        BuildCache {
+            path: path.to_path_buf(),
+            entries,
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let mut file = fs::File::create(&self.path)?;
+        file.write_all(self.serialize().as_bytes())
+    }
+
+    pub fn get(&self, class: &str) -> Option<&CacheEntry> {
+        self.entries.get(class)
+    }
+
+    pub fn insert(&mut self, class: &str, entry: CacheEntry) {
+        self.entries.insert(class.to_string(), entry);
+    }
+
+    pub fn remove(&mut self, class: &str) -> Option<CacheEntry> {
+        self.entries.remove(class)
+    }
+
+    pub fn classes(&self) -> impl Iterator<Item = &String> {
+        self.entries.keys()
+    }
+
+    // manifest format:
+    //   jack-build-cache <executable hash>
+    //   <class> <hash> <subroutine>/<argument count> ... <subroutine>@<line> ...
+    //   \tclass <declarations>
+    //   \ttypes <type check key>
+    //   \t<line> <lint> <message>
+    //   ...
+    // where the indented lines belong to the class above them. The line with the type
+    // check key is left out if the class passed none, and the last lines are its warnings.
+    fn parse(manifest: &str) -> Option<HashMap<String, CacheEntry>> {
+        let mut lines = manifest.lines();
+        if lines.next()? != format!("jack-build-cache {:016x}", executable_hash()) {
+            return None;
+        }
+
+        let mut entries = HashMap::new();
+        let mut last: Option<&mut CacheEntry> = None;
+        for line in lines {
+            if let Some(declarations) = line.strip_prefix("\tclass ") {
+                last.as_mut()?.declarations = format!("class {declarations}");
+                continue;
+            }
+            if let Some(key) = line.strip_prefix("\ttypes ") {
+                last.as_mut()?.types = Some(u64::from_str_radix(key, 16).ok()?);
+                continue;
+            }
+            if let Some(warning) = line.strip_prefix('\t') {
+                let mut parts = warning.splitn(3, ' ');
+                let line = parts.next()?.parse().ok()?;
+                let lint = Lint::from_name(parts.next()?)?;
+                let message = parts.next()?.to_string();
+                last.as_mut()?.warnings.push(Warning::new(lint, line, message));
+                continue;
+            }
+
+            let mut words = line.split_ascii_whitespace();
+            let class = words.next()?;
+            let hash = u64::from_str_radix(words.next()?, 16).ok()?;
+            let mut interface = Interface::new();
+            let mut subroutine_lines = BTreeMap::new();
+            for word in words {
+                if let Some((name, args)) = word.split_once('/') {
+                    interface.insert(name.to_string(), args.parse().ok()?);
+                } else {
+                    let (name, line) = word.split_once('@')?;
+                    subroutine_lines.insert(name.to_string(), line.parse().ok()?);
+                }
+            }
+
+            let entry = CacheEntry {
+                hash,
+                interface,
+                subroutine_lines,
+                warnings: Vec::new(),
+                declarations: String::new(),
+                types: None,
+            };
+            last = Some(entries.entry(class.to_string()).or_insert(entry));
+        }
+
+        Some(entries)
+    }
+
+    fn serialize(&self) -> String {
+        let mut classes = self.entries.keys().collect::<Vec<_>>();
+        classes.sort();
+
+        let mut manifest = format!("jack-build-cache {:016x}\n", executable_hash());
+        for class in classes {
+            let entry = &self.entries[class];
+            manifest.push_str(&format!("{class} {:016x}", entry.hash));
+            entry
+                .interface
+                .iter()
+                .for_each(|(name, args)| manifest.push_str(&format!(" {name}/{args}")));
+            entry
+                .subroutine_lines
+                .iter()
+                .for_each(|(name, line)| manifest.push_str(&format!(" {name}@{line}")));
+            manifest.push('\n');
+            manifest.push_str(&format!("\t{}\n", entry.declarations));
+            if let Some(key) = entry.types {
+                manifest.push_str(&format!("\ttypes {key:016x}\n"));
+            }
+            for warning in &entry.warnings {
+                manifest.push_str(&format!("\t{} {} {}\n", warning.line, warning.lint, warning.message));
+            }
+        }
+        manifest
+    }
+}
+
+/// Hash of a source file together with the compiler build and options, so that rebuilding
+/// the compiler or changing the options invalidates every cached output.
+pub fn source_hash(source: &str, options: &Options) -> u64 {
+    let build = executable_hash().to_le_bytes();
+    stable_hash([&build[..], options.to_string().as_bytes(), source.as_bytes()])
+}
+
+/// Key of a type check at the given strictness, given the declarations of every class of
+/// the program in order. A class whose source did not change and that passed a check with
+/// the same key passes it again.
+pub fn type_key<'a>(strictness: Strictness, declarations: impl IntoIterator<Item = &'a str>) -> u64 {
+    let build = executable_hash().to_le_bytes();
+    let strictness = format!("{strictness:?}");
+    let fields = [&build[..], strictness.as_bytes()].into_iter();
+    stable_hash(fields.chain(declarations.into_iter().map(|class| class.as_bytes())))
+}
+
+/// 64-bit FNV-1a hash of `fields`, each followed by a zero byte. Unlike `DefaultHasher`, it
+/// is stable across Rust releases, so it can key files on disk. The VM translator keys its
+/// module cache with it as well.
+pub fn stable_hash<'a>(fields: impl IntoIterator<Item = &'a [u8]>) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for field in fields {
+        for &byte in field.iter().chain(&[0]) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    hash
+}
+
+/// Hash of the running executable, which changes with every build of it, even one that
+/// keeps the package version. If the executable cannot be read, each run gets a hash of
+/// its own, so that nothing is taken from a cache.
+pub fn executable_hash() -> u64 {
+    static HASH: OnceLock<u64> = OnceLock::new();
+    *HASH.get_or_init(|| match std::env::current_exe().and_then(fs::read) {
+        Ok(executable) => stable_hash([executable.as_slice()]),
+        Err(_) => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{executable_hash, source_hash, BuildCache, CacheEntry, Interface};
+    use crate::options::Options;
+    use crate::warnings::{Lint, Warning};
+
+    #[test]
+    fn test_source_hash() {
+        let options = Options::default();
+        assert_eq!(source_hash("class Main {}", &options), source_hash("class Main {}", &options));
+        assert_ne!(source_hash("class Main {}", &options), source_hash("class Main { }", &options));
+
+        let pooled = Options { string_pool: true, ..Options::default() };
+        assert_ne!(source_hash("class Main {}", &options), source_hash("class Main {}", &pooled));
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let path = std::env::temp_dir().join(format!("jack-build-cache-test-{}", std::process::id()));
+        let interface = Interface::from([("new".to_string(), 2), ("dispose".to_string(), 1)]);
+
+        let mut cache = BuildCache::load(&path);
+        assert!(cache.get("List").is_none());
+        let list = CacheEntry {
+            hash: 42,
+            interface,
+            subroutine_lines: BTreeMap::from([("new".to_string(), 3), ("dispose".to_string(), 9)]),
+            warnings: vec![
+                Warning::new(Lint::UnusedVariable, 4, "Local variable 'x' is never read.".to_string()),
+                Warning::new(Lint::DeadLoop, 12, "A message, with words.".to_string()),
+            ],
+            declarations: "class List { constructor List new(int p0, List p1) {} }".to_string(),
+            types: Some(0x1234),
+        };
+        cache.insert("List", list.clone());
+        cache.insert(
+            "Main",
+            CacheEntry {
+                hash: 7,
+                interface: Interface::new(),
+                subroutine_lines: BTreeMap::new(),
+                warnings: Vec::new(),
+                declarations: "class Main { }".to_string(),
+                types: None,
+            },
+        );
+        cache.save().unwrap();
+
+        let cache = BuildCache::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(cache.get("List"), Some(&list));
+        assert_eq!(cache.get("Main").map(|e| e.hash), Some(7));
+        assert!(cache.get("Main").unwrap().warnings.is_empty());
+    }
+
+    #[test]
+    fn test_build_mismatch() {
+        let manifest = format!("jack-build-cache {:016x}\nMain 000000000000002a\n", executable_hash() ^ 1);
+        assert!(BuildCache::parse(&manifest).is_none());
+        let manifest = format!("jack-build-cache {:016x}\nMain 000000000000002a\n", executable_hash());
+        assert!(BuildCache::parse(&manifest).is_some());
+    }
+}
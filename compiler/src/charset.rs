@@ -0,0 +1,124 @@
+//! Mapping of Jack string literals to the Hack character set.
+//!
+//! The Hack character set is printable ASCII (32-126), newline (128), and the special keys
+//! of the keyboard: backspace (129), the arrow keys (130-133), home (134), end (135),
+//! page up (136), page down (137), insert (138), delete (139), escape (140) and F1-F12
+//! (141-152).
+
+pub const NEWLINE: u16 = 128;
+pub const BACKSPACE: u16 = 129;
+pub const LEFT_ARROW: u16 = 130;
+pub const UP_ARROW: u16 = 131;
+pub const RIGHT_ARROW: u16 = 132;
+pub const DOWN_ARROW: u16 = 133;
+pub const DELETE: u16 = 139;
+pub const ESCAPE: u16 = 140;
+pub const F12: u16 = 152;
+
+/// Returns `true` if `code` belongs to the Hack character set.
+pub fn is_hack_char(code: u16) -> bool {
+    matches!(code, 32..=126 | NEWLINE..=F12)
+}
+
+/// Maps a character to its Hack code. Hack has no tab, so a tab is written as a space.
+/// Home, end, page up/down, insert and the function keys have no Unicode counterpart and
+/// can only be written with `\xNN`.
+pub fn to_hack(c: char) -> Option<u16> {
+    match c {
+        ' '..='~' => Some(c as u16),
+        '\n' => Some(NEWLINE),
+        '\t' => Some(' ' as u16),
+        '\u{8}' => Some(BACKSPACE),
+        '←' => Some(LEFT_ARROW),
+        '↑' => Some(UP_ARROW),
+        '→' => Some(RIGHT_ARROW),
+        '↓' => Some(DOWN_ARROW),
+        '\u{7f}' => Some(DELETE),
+        '\u{1b}' => Some(ESCAPE),
+        _ => None,
+    }
+}
+
+/// Maps a string literal of standard Jack, which has no escape sequences, to Hack codes.
+pub fn encode(literal: &str) -> Result<Vec<u16>, String> {
+    literal.chars().map(encode_char).collect()
+}
+
+fn encode_char(c: char) -> Result<u16, String> {
+    to_hack(c).ok_or_else(|| format!("Character '{}' (U+{:04X}) is not in the Hack character set", c, c as u32))
+}
+
+/// Resolves the escape sequences of a string literal, as written between its quotes, and
+/// maps it to Hack codes.
+///
+/// Supported escapes are `\"`, `\\`, `\n`, `\t` and `\xNN`, which stands for the Hack code
+/// `NN` in hexadecimal.
+pub fn decode(literal: &str) -> Result<Vec<u16>, String> {
+    let mut codes = Vec::with_capacity(literal.len());
+    let mut chars = literal.chars();
+
+    while let Some(c) = chars.next() {
+        let code = if c == '\\' {
+            match chars.next() {
+                Some('"') => '"' as u16,
+                Some('\\') => '\\' as u16,
+                Some('n') => NEWLINE,
+                Some('t') => ' ' as u16,
+                Some('x') => {
+                    let digits = chars.by_ref().take(2).collect::<String>();
+                    let code = u16::from_str_radix(&digits, 16)
+                        .ok()
+                        .filter(|_| digits.len() == 2)
+                        .ok_or_else(|| format!("Escape sequence '\\x{digits}' expects two hex digits"))?;
+                    if !is_hack_char(code) {
+                        return Err(format!("Escape sequence '\\x{digits}' is not in the Hack character set"));
+                    }
+                    code
+                }
+                Some(c) => return Err(format!("Unknown escape sequence '\\{c}'")),
+                None => return Err("Unfinished escape sequence".to_string()),
+            }
+        } else {
+            encode_char(c)?
+        };
+        codes.push(code);
+    }
+
+    Ok(codes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain() {
+        assert_eq!(decode("Hi 5!").unwrap(), vec![72, 105, 32, 53, 33]);
+    }
+
+    #[test]
+    fn test_escapes() {
+        assert_eq!(decode(r#"\"\\\n\t"#).unwrap(), vec![34, 92, NEWLINE, 32]);
+        assert_eq!(decode(r"\x41\x80\x98").unwrap(), vec![65, NEWLINE, F12]);
+    }
+
+    #[test]
+    fn test_no_escapes() {
+        assert_eq!(encode(r#"[\]\n"#).unwrap(), vec![91, 92, 93, 92, 110]);
+        assert_eq!(encode("←").unwrap(), vec![130]);
+        assert_eq!(encode("é").unwrap_err(), "Character 'é' (U+00E9) is not in the Hack character set");
+    }
+
+    #[test]
+    fn test_special_keys() {
+        assert_eq!(decode("←↑→↓\u{8}\u{7f}\u{1b}").unwrap(), vec![130, 131, 132, 133, 129, 139, 140]);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(decode(r"\q").unwrap_err(), "Unknown escape sequence '\\q'");
+        assert_eq!(decode(r"\x4").unwrap_err(), "Escape sequence '\\x4' expects two hex digits");
+        assert_eq!(decode(r"\x7f").unwrap_err(), "Escape sequence '\\x7f' is not in the Hack character set");
+        assert_eq!(decode("é").unwrap_err(), "Character 'é' (U+00E9) is not in the Hack character set");
+    }
+}
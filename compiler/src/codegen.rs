@@ -1,6 +1,31 @@
-use std::{collections::HashMap, io::Write};
+use std::{cell::Cell, collections::HashMap, io::Write};
 
 use crate::ast::{class::Class, variable_type::VariableType, subroutine_kind::SubroutineKind};
+use crate::options::Options;
+use crate::warnings::{Lint, Warning};
+
+/// Name of the generated function returning the pooled string literals of a class, see
+/// `Options::string_pool`. The parser reserves subroutine names starting with `__`, so it
+/// cannot clash with a subroutine of the class.
+pub const STRING_POOL: &str = "__string";
+
+/// Names of the generated functions behind `Options::debug_checks`, with the number of
+/// arguments they take. As for `STRING_POOL`, the parser reserves their `__` prefix.
+pub const CHECK_NULL: (&str, u16) = ("__checkNull", 2);
+pub const CHECK_INDEX: (&str, u16) = ("__checkIndex", 3);
+pub const ARRAY_NEW: (&str, u16) = ("__arrayNew", 1);
+pub const ARRAY_TAGGED: (&str, u16) = ("__arrayTagged", 1);
+pub const ARRAY_DISPOSE: (&str, u16) = ("__arrayDispose", 1);
+
+/// First word of the header `Array.new` puts in front of an array with debug checks. The
+/// length of the array follows it. Since the array starts after the header, every block
+/// freed by the class goes through `ARRAY_DISPOSE`, which frees a tagged array from the
+/// start of its header.
+const ARRAY_TAG: u16 = 0x5aa5;
+
+/// `Sys.error` codes of failed debug checks. The line of the check is added to them.
+pub const NULL_ERROR: u16 = 10000;
+pub const INDEX_ERROR: u16 = 20000;
 
 #[derive(Debug)]
 pub struct ClassInfo {
@@ -8,31 +33,279 @@ pub struct ClassInfo {
     pub fields_count: u16,
 }
 
+/// Code generation state for a single class. Nothing is shared between
+/// compilers, so classes can be compiled independently of each other.
 #[derive(Debug)]
 pub struct Compiler {
     pub current_subroutine_kind: Option<SubroutineKind>,
     pub current_class: Option<ClassInfo>,
+    pub options: Options,
     branch_counter: u16,
+    // (continue, break) labels of the loops and switches around the statement being compiled.
+    // A switch has no continue label of its own, it keeps the one of the enclosing loop.
+    loops: Vec<(Option<String>, String)>,
+    // distinct string literals of the class, in order of appearance, when they are pooled
+    strings: Vec<Vec<u16>>,
+    warnings: Vec<Warning>,
+    /// Line of the code being compiled, for the errors of debug checks.
+    pub line: usize,
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Compiler {
     pub fn new() -> Self {
+        Self::with_options(Options::default())
+    }
+
+    pub fn with_options(options: Options) -> Self {
         let current_subroutine_kind = None;
         let current_class = None;
         let branch_counter = 0;
+        let loops = Vec::new();
+        let strings = Vec::new();
+        let warnings = Vec::new();
+        let line = 0;
 
         Compiler {
             current_subroutine_kind,
             current_class,
+            options,
             branch_counter,
+            loops,
+            strings,
+            warnings,
+            line,
         }
     }
 
     pub fn compile(&mut self, ast: &Class, out: &mut impl Write) {
+        // start over, so that labels only depend on the class being compiled
+        *self = Self::with_options(self.options.clone());
         let mut symbol_table = SymbolTable::new();
         ast.write_code(out, self, &mut symbol_table);
     }
 
+    /// Makes `break` and `continue` jump to the given labels, until the matching `exit_loop`.
+    pub fn enter_loop(&mut self, continue_label: String, break_label: String) {
+        self.loops.push((Some(continue_label), break_label));
+    }
+
+    /// Makes `break` jump to `break_label` until the matching `exit_loop`. `continue` still
+    /// refers to the enclosing loop.
+    pub fn enter_switch(&mut self, break_label: String) {
+        let continue_label = self.loops.last().and_then(|(next, _)| next.clone());
+        self.loops.push((continue_label, break_label));
+    }
+
+    pub fn exit_loop(&mut self) {
+        self.loops.pop();
+    }
+
+    /// The (continue, break) labels of the innermost loop or switch.
+    pub fn current_loop(&self) -> Option<(Option<String>, String)> {
+        self.loops.last().cloned()
+    }
+
+    /// Index of a string literal in the pool of the class, adding it if it is new.
+    pub fn pool_string(&mut self, codes: Vec<u16>) -> u16 {
+        let index = match self.strings.iter().position(|s| *s == codes) {
+            Some(index) => index,
+            None => {
+                self.strings.push(codes);
+                self.strings.len() - 1
+            }
+        };
+        index.try_into().expect("Too many string literals in one class.")
+    }
+
+    /// Writes the function that returns the pooled strings, if the class has any. The strings
+    /// are built on the first call, and kept in an array in the static variable `slot`.
+    pub fn write_string_pool(&self, out: &mut impl Write, slot: u16) {
+        if self.strings.is_empty() {
+            return;
+        }
+        let class = &self.current_class.as_ref().expect("The class is known.").name;
+
+        writeln!(out, "function {class}.{STRING_POOL} 0").unwrap();
+        push(out, AsmSection::Static, slot);
+        writeln!(out, "if-goto STRING_POOL_GET").unwrap();
+
+        push_constant(out, self.strings.len() as u16);
+        call_function(out, "Array.new", 1);
+        pop(out, AsmSection::Static, slot);
+        for (i, codes) in self.strings.iter().enumerate() {
+            new_string(out, codes);
+            push_constant(out, i as u16);
+            push(out, AsmSection::Static, slot);
+            writeln!(out, "add").unwrap();
+            pop(out, AsmSection::Pointer, 1);
+            pop(out, AsmSection::That, 0);
+        }
+
+        writeln!(out, "label STRING_POOL_GET").unwrap();
+        push(out, AsmSection::Argument, 0);
+        push(out, AsmSection::Static, slot);
+        writeln!(out, "add").unwrap();
+        pop(out, AsmSection::Pointer, 1);
+        push(out, AsmSection::That, 0);
+        writeln!(out, "return").unwrap();
+    }
+
+    /// Replaces the reference on top of the stack by itself, stopping the program if it is
+    /// null.
+    pub fn check_null(&self, out: &mut impl Write) {
+        let class = &self.current_class.as_ref().expect("The class is known.").name;
+        push_constant(out, NULL_ERROR + (self.line % 10000) as u16);
+        call_function(out, &format!("{class}.{}", CHECK_NULL.0), CHECK_NULL.1);
+    }
+
+    /// Replaces an array and an index on top of the stack by the address of the element,
+    /// stopping the program if the array is null or the index is out of its bounds.
+    pub fn check_index(&self, out: &mut impl Write) {
+        let class = &self.current_class.as_ref().expect("The class is known.").name;
+        push_constant(out, (self.line % 10000) as u16);
+        call_function(out, &format!("{class}.{}", CHECK_INDEX.0), CHECK_INDEX.1);
+    }
+
+    /// Writes the functions that carry out the debug checks of the class.
+    pub fn write_debug_checks(&self, out: &mut impl Write) {
+        let class = &self.current_class.as_ref().expect("The class is known.").name;
+
+        // (reference, error code) -> reference
+        writeln!(out, "function {class}.{} 0", CHECK_NULL.0).unwrap();
+        push(out, AsmSection::Argument, 0);
+        writeln!(out, "if-goto CHECK_NULL_OK").unwrap();
+        push(out, AsmSection::Argument, 1);
+        call_function(out, "Sys.error", 1);
+        pop(out, AsmSection::Temp, 0);
+        writeln!(out, "label CHECK_NULL_OK").unwrap();
+        push(out, AsmSection::Argument, 0);
+        writeln!(out, "return").unwrap();
+
+        // (array, index, line) -> address of the element. only arrays with the header of
+        // `ARRAY_NEW` know their length.
+        writeln!(out, "function {class}.{} 0", CHECK_INDEX.0).unwrap();
+        push(out, AsmSection::Argument, 0);
+        writeln!(out, "if-goto CHECK_INDEX_NOT_NULL").unwrap();
+        push_constant(out, NULL_ERROR);
+        push(out, AsmSection::Argument, 2);
+        writeln!(out, "add").unwrap();
+        call_function(out, "Sys.error", 1);
+        pop(out, AsmSection::Temp, 0);
+        writeln!(out, "label CHECK_INDEX_NOT_NULL").unwrap();
+        push(out, AsmSection::Argument, 0);
+        call_function(out, &format!("{class}.{}", ARRAY_TAGGED.0), ARRAY_TAGGED.1);
+        writeln!(out, "not").unwrap();
+        writeln!(out, "if-goto CHECK_INDEX_OK").unwrap();
+        write_array_header(out);
+        push(out, AsmSection::Argument, 1);
+        push_constant(out, 0);
+        writeln!(out, "lt").unwrap();
+        push(out, AsmSection::Argument, 1);
+        push(out, AsmSection::That, 1);
+        writeln!(out, "lt").unwrap();
+        writeln!(out, "not").unwrap();
+        writeln!(out, "or").unwrap();
+        writeln!(out, "not").unwrap();
+        writeln!(out, "if-goto CHECK_INDEX_OK").unwrap();
+        push_constant(out, INDEX_ERROR);
+        push(out, AsmSection::Argument, 2);
+        writeln!(out, "add").unwrap();
+        call_function(out, "Sys.error", 1);
+        pop(out, AsmSection::Temp, 0);
+        writeln!(out, "label CHECK_INDEX_OK").unwrap();
+        push(out, AsmSection::Argument, 0);
+        push(out, AsmSection::Argument, 1);
+        writeln!(out, "add").unwrap();
+        writeln!(out, "return").unwrap();
+
+        // (length) -> array, preceded by the tag and its length
+        writeln!(out, "function {class}.{} 1", ARRAY_NEW.0).unwrap();
+        push(out, AsmSection::Argument, 0);
+        push_constant(out, 2);
+        writeln!(out, "add").unwrap();
+        call_function(out, "Array.new", 1);
+        pop(out, AsmSection::Local, 0);
+        push(out, AsmSection::Local, 0);
+        pop(out, AsmSection::Pointer, 1);
+        push_constant(out, ARRAY_TAG);
+        pop(out, AsmSection::That, 0);
+        push(out, AsmSection::Argument, 0);
+        pop(out, AsmSection::That, 1);
+        push(out, AsmSection::Local, 0);
+        push_constant(out, 2);
+        writeln!(out, "add").unwrap();
+        writeln!(out, "return").unwrap();
+
+        // (reference) -> whether it has the header of `ARRAY_NEW`. the header of a reference
+        // below 2 would lie outside of the memory, and that of null cannot be read either
+        writeln!(out, "function {class}.{} 0", ARRAY_TAGGED.0).unwrap();
+        push(out, AsmSection::Argument, 0);
+        push_constant(out, 2);
+        writeln!(out, "lt").unwrap();
+        writeln!(out, "if-goto ARRAY_TAGGED_NO").unwrap();
+        write_array_header(out);
+        push(out, AsmSection::That, 0);
+        push_constant(out, ARRAY_TAG);
+        writeln!(out, "eq").unwrap();
+        writeln!(out, "return").unwrap();
+        writeln!(out, "label ARRAY_TAGGED_NO").unwrap();
+        push_constant(out, 0);
+        writeln!(out, "return").unwrap();
+
+        // (reference) -> 0, freeing a tagged array along with its header, and any other
+        // block as `Memory.deAlloc` does
+        writeln!(out, "function {class}.{} 0", ARRAY_DISPOSE.0).unwrap();
+        push(out, AsmSection::Argument, 0);
+        call_function(out, &format!("{class}.{}", ARRAY_TAGGED.0), ARRAY_TAGGED.1);
+        writeln!(out, "not").unwrap();
+        writeln!(out, "if-goto ARRAY_DISPOSE_UNTAGGED").unwrap();
+        write_array_header(out);
+        push_constant(out, 0);
+        pop(out, AsmSection::That, 0);
+        push(out, AsmSection::Argument, 0);
+        push_constant(out, 2);
+        writeln!(out, "sub").unwrap();
+        call_function(out, "Memory.deAlloc", 1);
+        writeln!(out, "return").unwrap();
+        writeln!(out, "label ARRAY_DISPOSE_UNTAGGED").unwrap();
+        push(out, AsmSection::Argument, 0);
+        call_function(out, "Memory.deAlloc", 1);
+        writeln!(out, "return").unwrap();
+    }
+
+    /// Returns `true` if the class needs the function written by `write_string_pool`.
+    pub fn has_string_pool(&self) -> bool {
+        !self.strings.is_empty()
+    }
+
+    /// Warnings about the class compiled last, in the order they were found.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// Warns about each variable in `entries` that is never read.
+    pub fn warn_unused(&mut self, entries: Vec<(&String, &SymbolEntry)>) {
+        for (name, entry) in entries {
+            let kind = match entry.scope {
+                SymbolScope::Static => "Static variable",
+                SymbolScope::Field => "Field",
+                SymbolScope::Local => "Local variable",
+                SymbolScope::Argument => "Parameter",
+            };
+            self.warnings.push(Warning::new(
+                Lint::UnusedVariable,
+                entry.line,
+                format!("{kind} '{name}' is never read."),
+            ));
+        }
+    }
+
     pub fn get_new_branch_counter(&mut self) -> u16 {
         self.branch_counter += 1;
         self.branch_counter
@@ -54,9 +327,9 @@ pub enum SymbolScope {
     Argument,
 }
 
-impl Into<AsmSection> for SymbolScope {
-    fn into(self) -> AsmSection {
-        match self {
+impl From<SymbolScope> for AsmSection {
+    fn from(scope: SymbolScope) -> Self {
+        match scope {
             SymbolScope::Static => AsmSection::Static,
             SymbolScope::Field => AsmSection::This,
             SymbolScope::Local => AsmSection::Local,
@@ -70,21 +343,34 @@ pub struct SymbolEntry {
     pub id: u16,
     pub typ: VariableType,
     pub scope: SymbolScope,
+    /// Line of the declaration.
+    pub line: usize,
+    // set once the variable is read, see `SymbolTable::resolve_variable`
+    used: Cell<bool>,
 }
 
 pub struct SymbolTable {
     pub class_symbols: HashMap<String, SymbolEntry>,
     pub local_symbols: HashMap<String, SymbolEntry>,
+    // values of the `const` declarations of the class, which take no memory
+    constants: HashMap<String, u16>,
     static_counter: i32,
     field_counter: i32,
     local_counter: i32,
     args_counter: i32,
 }
 
+impl Default for SymbolTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SymbolTable {
     pub fn new() -> Self {
         let class_symbols = HashMap::new();
         let local_symbols = HashMap::new();
+        let constants = HashMap::new();
         let static_counter = -1;
         let field_counter = -1;
         let local_counter = -1;
@@ -93,6 +379,7 @@ impl SymbolTable {
         SymbolTable {
             class_symbols,
             local_symbols,
+            constants,
             static_counter,
             field_counter,
             local_counter,
@@ -100,7 +387,20 @@ impl SymbolTable {
         }
     }
 
+    /// Number of static variables declared so far.
+    pub fn static_count(&self) -> u16 {
+        (self.static_counter + 1) as u16
+    }
+
+    /// Looks up a variable that is about to be read, and marks it as used.
     pub fn resolve_variable(&self, name: &str) -> Option<&SymbolEntry> {
+        let entry = self.resolve_target(name)?;
+        entry.used.set(true);
+        Some(entry)
+    }
+
+    /// Looks up a variable that is about to be assigned, without marking it as used.
+    pub fn resolve_target(&self, name: &str) -> Option<&SymbolEntry> {
         if let Some(entry) = self.local_symbols.get(name) {
             Some(entry)
         } else if let Some(entry) = self.class_symbols.get(name) {
@@ -110,7 +410,50 @@ impl SymbolTable {
         }
     }
 
-    pub fn add_variable(&mut self, name: &str, typ: &VariableType, scope: SymbolScope) {
+    /// The value of a constant of the class, unless a local variable hides it.
+    pub fn resolve_constant(&self, name: &str) -> Option<u16> {
+        if self.local_symbols.contains_key(name) {
+            None
+        } else {
+            self.constants.get(name).copied()
+        }
+    }
+
+    pub fn add_constant(&mut self, name: &str, value: u16) {
+        if self.class_symbols.contains_key(name) || self.constants.contains_key(name) {
+            panic!("Duplicate variable '{name}' in class scope.");
+        }
+        self.constants.insert(name.to_string(), value);
+    }
+
+    /// Parameters and local variables of the current subroutine that are never read, in
+    /// order of declaration.
+    pub fn unused_locals(&self) -> Vec<(&String, &SymbolEntry)> {
+        Self::unused(&self.local_symbols, |_| true)
+    }
+
+    /// Static variables of the class that are never read, in order of declaration.
+    pub fn unused_statics(&self) -> Vec<(&String, &SymbolEntry)> {
+        Self::unused(&self.class_symbols, |scope| scope == SymbolScope::Static)
+    }
+
+    fn unused(
+        symbols: &HashMap<String, SymbolEntry>,
+        scope: impl Fn(SymbolScope) -> bool,
+    ) -> Vec<(&String, &SymbolEntry)> {
+        let mut unused = symbols
+            .iter()
+            .filter(|(_, entry)| scope(entry.scope) && !entry.used.get())
+            .collect::<Vec<_>>();
+        // parameters come before local variables
+        unused.sort_by_key(|(_, entry)| (entry.line, entry.scope == SymbolScope::Local, entry.id));
+        unused
+    }
+
+    pub fn add_variable(&mut self, name: &str, typ: &VariableType, scope: SymbolScope, line: usize) {
+        if matches!(scope, SymbolScope::Static | SymbolScope::Field) && self.constants.contains_key(name) {
+            panic!("Duplicate variable '{name}' in class scope.");
+        }
         let typ = typ.clone();
         let id = match scope {
             SymbolScope::Static => {
@@ -138,7 +481,7 @@ impl SymbolTable {
                 }
 
                 self.class_symbols
-                    .insert(name.to_string(), SymbolEntry { id, typ, scope });
+                    .insert(name.to_string(), SymbolEntry { id, typ, scope, line, used: Cell::new(false) });
             }
             SymbolScope::Local | SymbolScope::Argument => {
                 if self.local_symbols.contains_key(name) {
@@ -146,7 +489,7 @@ impl SymbolTable {
                 }
 
                 self.local_symbols
-                    .insert(name.to_string(), SymbolEntry { id, typ, scope });
+                    .insert(name.to_string(), SymbolEntry { id, typ, scope, line, used: Cell::new(false) });
             }
         }
     }
@@ -188,10 +531,52 @@ pub trait CodeGen {
     );
 }
 
+// points `that` at the header in front of the array in argument 0, which must be at least 2
+fn write_array_header(out: &mut impl Write) {
+    push(out, AsmSection::Argument, 0);
+    push_constant(out, 2);
+    writeln!(out, "sub").unwrap();
+    pop(out, AsmSection::Pointer, 1);
+}
+
 pub fn push_constant(out: &mut impl Write, val: u16) {
     writeln!(out, "push constant {val}").unwrap();
 }
 
+/// Pushes any 16-bit value. `push constant` only takes 0-32767, so other values are
+/// negated or complemented.
+pub fn push_value(out: &mut impl Write, value: u16) {
+    match value {
+        0..=0x7fff => push_constant(out, value),
+        0x8000 => {
+            push_constant(out, 0x7fff);
+            writeln!(out, "not").unwrap();
+        }
+        _ => {
+            push_constant(out, value.wrapping_neg());
+            writeln!(out, "neg").unwrap();
+        }
+    }
+}
+
+/// Pushes a new string with the given Hack codes.
+pub fn new_string(out: &mut impl Write, codes: &[u16]) {
+    // Create string object first.
+    push_constant(
+        out,
+        codes
+            .len()
+            .try_into()
+            .expect("String length is limited to 16 bits."),
+    );
+    call_function(out, "String.new", 1);
+
+    codes.iter().for_each(|&code| {
+        push_constant(out, code);
+        call_function(out, "String.appendChar", 2);
+    });
+}
+
 // TODO: is this sufficient?
 pub fn push_this(out: &mut impl Write) {
     writeln!(out, "push pointer 0").unwrap();
@@ -230,3 +615,300 @@ pub fn pop(out: &mut impl Write, section: AsmSection, index: u16) {
         Temp => writeln!(out, "pop temp {index}").unwrap(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::batch::compile_class;
+    use crate::options::Options;
+
+    // the codes appended to the string of the only literal in `main`
+    fn string_codes(literal: &str, options: &Options) -> Vec<u16> {
+        let source = format!("class Main {{ function void main() {{ do Output.printString(\"{literal}\"); return; }} }}");
+        let vm = String::from_utf8(compile_class(&source, options).vm).unwrap();
+        let lines = vm.lines().collect::<Vec<&str>>();
+        lines
+            .windows(2)
+            .filter(|pair| pair[1] == "call String.appendChar 2")
+            .map(|pair| pair[0].strip_prefix("push constant ").unwrap().parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        let escapes = Options { escapes: true, ..Options::default() };
+        assert_eq!(string_codes(r"a\nb\x8C", &escapes), vec![97, 128, 98, 140]);
+        assert_eq!(string_codes(r#"\"\\"#, &escapes), vec![34, 92]);
+
+        // standard Jack keeps every backslash
+        assert_eq!(string_codes(r"a\nb", &Options::default()), vec![97, 92, 110, 98]);
+        assert_eq!(string_codes(r"[\]", &Options::default()), vec![91, 92, 93]);
+        assert_eq!(string_codes(r"\", &Options::default()), vec![92]);
+    }
+
+    #[test]
+    fn test_string_pool() {
+        let source = "class Main {
+            static int n;
+            function void main() {
+                do Output.printString(\"Hi\");
+                do Output.printString(\"Hi\");
+                do Output.printString(\"Bye\");
+                return;
+            }
+        }";
+        let options = Options { string_pool: true, ..Options::default() };
+        let compiled = compile_class(source, &options);
+        let vm = String::from_utf8(compiled.vm).unwrap();
+
+        // both uses of "Hi" share the first string of the pool
+        assert_eq!(vm.matches("push constant 0\ncall Main.__string 1\n").count(), 2);
+        assert_eq!(vm.matches("push constant 1\ncall Main.__string 1\n").count(), 1);
+        assert_eq!(vm.matches("call String.new 1").count(), 2);
+        // the pool is kept after the static variable `n`
+        assert!(vm.contains("function Main.__string 0\npush static 1\nif-goto STRING_POOL_GET\n"));
+        assert_eq!(compiled.interface.get("__string"), Some(&1));
+
+        let plain = compile_class(source, &Options::default());
+        assert_eq!(String::from_utf8(plain.vm).unwrap().matches("call String.new 1").count(), 3);
+    }
+
+    #[test]
+    fn test_const_and_switch() {
+        let source = "class Sys {
+            const int LEFT = 130, RIGHT = LEFT + 2;
+            const int MIN = -32767 - 1, ALL = ~0;
+            static int left, right, other, loops, min, all;
+            function void init() {
+                var int i;
+                for (i = 128; i < 136; let i += 1) {
+                    switch (i) {
+                        case LEFT: let left = i;
+                        case RIGHT, RIGHT + 1:
+                            let right += 1;
+                            if (i = 133) { break; }
+                            let right += 100;
+                        case 128: continue;
+                        default: let other += 1;
+                    }
+                    let loops += 1;
+                }
+                let min = MIN;
+                let all = ALL;
+                while (true) { }
+                return;
+            }
+        }";
+        let options = Options { extensions: true, ..Options::default() };
+        let vm = String::from_utf8(compile_class(source, &options).vm).unwrap();
+        // constants take no static variable
+        assert!(vm.contains("pop static 0\n"));
+        assert!(!vm.contains("static 6"));
+
+        let mut emulator = emulator::vm::Vm::new(&[("Sys".to_string(), vm)]).unwrap();
+        assert!(emulator.run_until_idle(100_000));
+        assert_eq!(&emulator.ram[16..22], &[130, 102, 4, 7, 0x8000, 0xffff]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot assign to constant 'LEFT'.")]
+    fn test_const_assignment() {
+        let source = "class Keys { const int LEFT = 130; function void f() { let LEFT = 1; return; } }";
+        compile_class(source, &Options { extensions: true, ..Options::default() });
+    }
+
+    #[test]
+    #[should_panic(expected = "Duplicate case value 130 in switch statement.")]
+    fn test_switch_duplicate_case() {
+        let source = "class Keys {
+            const int LEFT = 130;
+            function void f(int key) { switch (key) { case LEFT: return; case 130: return; } return; }
+        }";
+        compile_class(source, &Options { extensions: true, ..Options::default() });
+    }
+
+    // runs `Main` with the OS of `tools/OS`, and returns `RAM[first..first + len]` once idle
+    fn run_with_os(main: &str, options: &Options, first: usize, len: usize) -> Vec<u16> {
+        let vm = String::from_utf8(compile_class(main, options).vm).unwrap();
+        let mut modules = vec![("Main".to_string(), vm)];
+        let os = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../tools/OS");
+        for class in ["Array", "Keyboard", "Math", "Memory", "Output", "Screen", "String", "Sys"] {
+            let vm = std::fs::read_to_string(os.join(format!("{class}.vm"))).unwrap();
+            modules.push((class.to_string(), vm));
+        }
+
+        let mut emulator = emulator::vm::Vm::new(&modules).unwrap();
+        assert!(emulator.run_until_idle(50_000_000));
+        emulator.ram[first..first + len].to_vec()
+    }
+
+    #[test]
+    fn test_optimize() {
+        let source = "class Main {
+            function void main() {
+                var Array xs;
+                var int i, x, n;
+                let n = 12;
+                let xs = Array.new(n);
+                let xs[0] = 0; let xs[1] = 1; let xs[2] = -1; let xs[3] = 7; let xs[4] = -7;
+                let xs[5] = 255; let xs[6] = -256; let xs[7] = 32767; let xs[8] = -32767 - 1;
+                let xs[9] = 12345; let xs[10] = -12345; let xs[11] = 16384;
+                while (i < n) {
+                    let x = xs[i];
+                    do Main.store(i, 0, x * 2);
+                    do Main.store(i, 1, 8 * x);
+                    do Main.store(i, 2, x * -4);
+                    do Main.store(i, 3, x * (16 * 2048));
+                    do Main.store(i, 4, x / 2);
+                    do Main.store(i, 5, x / 64);
+                    do Main.store(i, 6, x / -16);
+                    do Main.store(i, 7, x / 16384);
+                    do Main.store(i, 8, 16 * 32 + x - -(3));
+                    do Main.store(i, 9, x * 3 / 1 * 1);
+                    let i = i + 1;
+                }
+                return;
+            }
+
+            function void store(int i, int j, int value) {
+                do Memory.poke(15000 + (i * 10) + j, value);
+                return;
+            }
+        }";
+        let options = Options { optimize: true, ..Options::default() };
+        let vm = String::from_utf8(compile_class(source, &options).vm).unwrap();
+        // only `x * 3` and the indices in `store` still multiply, and only `/ 1` does not divide
+        assert_eq!(vm.matches("call Math.multiply 2").count(), 2);
+        assert_eq!(vm.matches("call Math.divide 2").count(), 4);
+        assert!(vm.contains("push constant 512\n"));
+
+        let plain = run_with_os(source, &Options::default(), 15000, 120);
+        assert_eq!(run_with_os(source, &options, 15000, 120), plain);
+        let precedence = Options { precedence: true, ..Options::default() };
+        assert_eq!(
+            run_with_os(source, &Options { optimize: true, ..precedence.clone() }, 15000, 120),
+            run_with_os(source, &precedence, 15000, 120)
+        );
+        // spot checks, as the OS computes them
+        assert_eq!(plain[55], 3); // 255 / 64
+        assert_eq!(plain[84] as i16, -16384); // -32768 / 2
+        assert_eq!(plain[46] as i16, 0); // -7 / -16
+    }
+
+    #[test]
+    fn test_optimize_unfolded() {
+        let source = "class Main {
+            function void main() {
+                var boolean less, greater;
+                var int x;
+                let less = -20000 < 20000;
+                let greater = 20000 > -20000;
+                let x = 1 / 0;
+                let less = 1 < 2;
+                return;
+            }
+        }";
+        let options = Options { optimize: true, ..Options::default() };
+        let vm = String::from_utf8(compile_class(source, &options).vm).unwrap();
+        // `-20000 - 20000` overflows, so the Hack code of `lt` and `gt` does not give the
+        // true order, and a division by zero is left to `Math.divide` to report. `1 < 2` folds
+        assert_eq!(vm.matches("lt\n").count(), 1);
+        assert_eq!(vm.matches("gt\n").count(), 1);
+        assert!(vm.contains("push constant 1\npush constant 0\ncall Math.divide 2\n"));
+        assert!(vm.contains("push constant 1\nneg\npop local 0\n"));
+    }
+
+    #[test]
+    fn test_debug_checks() {
+        let main = "class Main {
+            field int value; static int result;
+            constructor Main new() { let value = 7; return this; }
+            method int get() { return value; } method void dispose() { do Memory.deAlloc(this); return; }
+            function void run(int test) {
+                var Array a, raw; var Main m;
+                let a = Array.new(3);
+                let a[0] = 1;
+                let a[2] = a[0] + 1;
+                if (test = 1) { let a[3] = 5; }
+                if (test = 2) { let test = a[test - 3]; }
+                if (test = 3) { do m.get(); }
+                if (test = 4) { let test = raw[0]; }
+                if (test = 5) {
+                    let raw = Memory.alloc(2);
+                    let raw[5] = a[2];
+                    do a.dispose();
+                    let m = Main.new();
+                    let test = m.get();
+                }
+                if (test = 6) {
+                    let raw = a;
+                    do Memory.deAlloc(a);
+                    let a = Array.new(3);
+                    let result = a - raw;
+                }
+                if (test = 7) {
+                    let raw = a;
+                    let m = a;
+                    do m.dispose();
+                    let a = Array.new(3);
+                    let result = a - raw;
+                }
+                if (test = 8) { let raw = 32767; let raw[0] = 23205; let raw = 1; let test = raw[1000]; }
+                return;
+            }
+        }";
+        let options = Options { debug_checks: true, ..Options::default() };
+        let main = String::from_utf8(compile_class(main, &options).vm).unwrap();
+        let os = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../tools/OS");
+
+        // the VM once `Main.run` has returned or failed
+        let emulate = |test: u16| {
+            let sys = format!(
+                "class Sys {{
+                    static int code, done;
+                    function void init() {{
+                        do Memory.init();
+                        do Main.run({test});
+                        let done = 1;
+                        while (true) {{ }}
+                    }}
+                    function void error(int c) {{
+                        let code = c;
+                        while (true) {{ }}
+                    }}
+                }}"
+            );
+            let sys = String::from_utf8(compile_class(&sys, &Options::default()).vm).unwrap();
+            let mut modules = vec![("Sys".to_string(), sys), ("Main".to_string(), main.clone())];
+            for class in ["Array", "Memory"] {
+                let vm = std::fs::read_to_string(os.join(format!("{class}.vm"))).unwrap();
+                modules.push((class.to_string(), vm));
+            }
+            let mut emulator = emulator::vm::Vm::new(&modules).unwrap();
+            assert!(emulator.run_until_idle(1_000_000));
+            emulator
+        };
+        // (Sys.error code, whether `Main.run` returned)
+        let run = |test: u16| {
+            let emulator = emulate(test);
+            (emulator.ram[16], emulator.ram[17])
+        };
+
+        assert_eq!(run(0), (0, 1));
+        // the code tells the kind of check and the line of the access
+        assert_eq!(run(1), (20010, 0));
+        assert_eq!(run(2), (20011, 0));
+        assert_eq!(run(3), (10012, 0));
+        assert_eq!(run(4), (10013, 0));
+        // arrays without a header, such as memory blocks, are only checked for null
+        assert_eq!(run(5), (0, 1));
+        // freeing an array through `Memory.deAlloc` or a variable of another class frees its
+        // header as well, so the next array of the same size takes its place
+        for test in [6, 7] {
+            let emulator = emulate(test);
+            assert_eq!((emulator.ram[16], emulator.ram[17], emulator.ram[18]), (0, 1, 0));
+        }
+        // a reference too small to have a header is not looked into, even if the address its
+        // header would wrap around to holds the tag
+        assert_eq!(run(8), (0, 1));
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+
+use crate::ast::{class::Class, subroutine_kind::SubroutineKind};
+use crate::build_cache::Interface;
+
+/// Subroutines a class declares, with the number of arguments a `call` to them passes.
+/// Methods receive `this` as an extra argument.
+pub fn interface(class: &Class) -> Interface {
+    class
+        .subroutines
+        .iter()
+        .map(|s| {
+            let this = match s.kind {
+                SubroutineKind::Method => 1,
+                SubroutineKind::Constructor | SubroutineKind::Function => 0,
+            };
+            (s.name.clone(), s.parameters.len() as u16 + this)
+        })
+        .collect()
+}
+
+/// Every `call Class.subroutine n` in compiled vm code, as (class, subroutine, n).
+pub fn calls(vm: &str) -> Vec<(String, String, u16)> {
+    vm.lines()
+        .filter_map(|line| {
+            let mut words = line.split_ascii_whitespace();
+            let "call" = words.next()? else { return None };
+            let (class, subroutine) = words.next()?.split_once('.')?;
+            let args = words.next()?.parse().ok()?;
+            Some((class.to_string(), subroutine.to_string(), args))
+        })
+        .collect()
+}
+
+/// Checks calls made by `caller` against the interfaces of the other classes in the project.
+/// Calls to classes outside of the project (such as the OS) are not checked.
+pub fn check_calls(
+    caller: &str,
+    calls: &[(String, String, u16)],
+    interfaces: &HashMap<String, Interface>,
+) -> Vec<String> {
+    calls
+        .iter()
+        .filter_map(|(class, subroutine, args)| {
+            let interface = interfaces.get(class)?;
+            match interface.get(subroutine) {
+                None => Some(format!(
+                    "{caller}: '{class}.{subroutine}' is called, but class {class} declares no subroutine '{subroutine}'."
+                )),
+                Some(expected) if expected != args => Some(format!(
+                    "{caller}: '{class}.{subroutine}' is called with {args} argument(s), but it takes {expected}."
+                )),
+                Some(_) => None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{calls, check_calls, interface};
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_interface() {
+        let input = "
+            class List {
+                constructor List new(int car, List cdr) { return this; }
+                method void dispose() { return; }
+                function int length(List l) { return 0; }
+            }
+        ";
+        let class = Parser::new(input).parse();
+        let iface = interface(&class);
+        assert_eq!(iface.get("new"), Some(&2));
+        assert_eq!(iface.get("dispose"), Some(&1));
+        assert_eq!(iface.get("length"), Some(&1));
+    }
+
+    #[test]
+    fn test_check_calls() {
+        let vm = "function Main.main 0\npush constant 1\ncall List.new 1\ncall List.size 1\ncall Output.printInt 1\n";
+        let found = calls(vm);
+        assert_eq!(found.len(), 3);
+
+        let class = Parser::new("class List { constructor List new(int car, List cdr) { return this; } }").parse();
+        let interfaces = HashMap::from([("List".to_string(), interface(&class))]);
+        let errors = check_calls("Main", &found, &interfaces);
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].contains("with 1 argument(s), but it takes 2"));
+        assert!(errors[1].contains("no subroutine 'size'"));
+    }
+}
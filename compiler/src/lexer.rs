@@ -1,4 +1,6 @@
 use crate::{
+    charset,
+    options::Options,
     span::Span,
     token::{KeywordType, Token, TokenType},
 };
@@ -8,6 +10,8 @@ pub struct Lexer {
     start: usize,
     position: usize,
     line: usize,
+    extensions: bool,
+    escapes: bool,
     pub current_token: Token,
     pub next_token: Token,
 }
@@ -33,11 +37,49 @@ impl Lexer {
             start,
             position,
             line,
+            extensions: false,
+            escapes: false,
             current_token,
             next_token,
         }
     }
 
+    /// Like `new`, but also reads the symbols of the language extensions: `:` and the
+    /// two-character symbols `+=`, `-=`, `!=`, `<=` and `>=`.
+    pub fn with_extensions(source: &str) -> Self {
+        Lexer {
+            extensions: true,
+            ..Lexer::new(source)
+        }
+    }
+
+    /// A lexer for the language the options select: the extensions, and escape sequences in
+    /// string literals.
+    pub fn with_options(source: &str, options: &Options) -> Self {
+        Lexer {
+            extensions: options.extensions,
+            escapes: options.escapes,
+            ..Lexer::new(source)
+        }
+    }
+
+    /// Line number, starting at 1, of a position in the source.
+    pub fn line_at(&self, position: usize) -> usize {
+        self.source[..position.min(self.source.len())]
+            .iter()
+            .filter(|c| **c == '\n')
+            .count()
+            + 1
+    }
+
+    /// Source text between two positions, with whitespace collapsed to single spaces.
+    pub fn text(&self, start: usize, end: usize) -> String {
+        let text = self.source[start..end.min(self.source.len())]
+            .iter()
+            .collect::<String>();
+        text.split_whitespace().collect::<Vec<&str>>().join(" ")
+    }
+
     pub fn has_more_tokens(&self) -> bool {
         self.position < self.source.len()
     }
@@ -59,7 +101,7 @@ impl Lexer {
     }
 
     pub fn all_tokens(&mut self) -> Vec<Token> {
-        if self.source.len() == 0 {
+        if self.source.is_empty() {
             return vec![Token {
                 token_type: TokenType::Eof,
                 span: Span::default(),
@@ -100,6 +142,26 @@ impl Lexer {
             self.start = self.position;
             let c = self.advance();
             match c {
+                '+' | '-' | '!' | '<' | '>' if self.extensions && self.peek() == '=' => {
+                    self.advance();
+                    let symbol = match c {
+                        '+' => "+=",
+                        '-' => "-=",
+                        '!' => "!=",
+                        '<' => "<=",
+                        _ => ">=",
+                    };
+                    break Token {
+                        token_type: TokenType::CompoundSymbol(symbol),
+                        span: Span(self.start, self.position),
+                    };
+                }
+                ':' if self.extensions => {
+                    break Token {
+                        token_type: TokenType::Symbol(c),
+                        span: Span(self.start, self.position),
+                    }
+                }
                 '{' | '}' | '(' | ')' | '[' | ']' | '.' | ',' | ';' | '+' | '-' | '*' | '&'
                 | '|' | '<' | '>' | '=' | '~' => {
                     break Token {
@@ -155,9 +217,8 @@ impl Lexer {
                             }
                         };
 
-                        match result {
-                            Some(tok) => break tok,
-                            None => (),
+                        if let Some(tok) = result {
+                            break tok;
                         }
                     } else {
                         break Token {
@@ -179,6 +240,10 @@ impl Lexer {
                         }
 
                         self.advance();
+                        // the escaped character cannot end the literal
+                        if self.escapes && peek == '\\' && !self.is_at_end() && !matches!(self.peek(), '\n' | '\r') {
+                            self.advance();
+                        }
                     }
 
                     if self.is_at_end() {
@@ -194,12 +259,20 @@ impl Lexer {
                     let literal = self.source[(self.start + 1)..(self.position - 1)]
                         .iter()
                         .collect::<String>();
+                    let codes = if self.escapes {
+                        charset::decode(&literal)
+                    } else {
+                        charset::encode(&literal)
+                    };
+                    if let Err(e) = codes {
+                        panic!("{e} in string literal at line {}, position {}", self.line, self.start);
+                    }
                     break Token {
                         token_type: TokenType::String(literal),
                         span: Span(self.start, self.position),
                     };
                 }
-                c if c.is_ascii_alphabetic() => {
+                c if c.is_ascii_alphabetic() || c == '_' => {
                     self.position -= 1;
                     break self.symbol();
                 }
@@ -223,8 +296,7 @@ impl Lexer {
             }
         };
         std::mem::swap(&mut self.current_token, &mut self.next_token);
-        let old_token = std::mem::replace(&mut self.next_token, token);
-        old_token
+        std::mem::replace(&mut self.next_token, token)
     }
 
     fn advance(&mut self) -> char {
@@ -240,7 +312,7 @@ impl Lexer {
         }
     }
 
-    // NOTE: this function expects that the current character is an alphabet, and not a digit
+    // NOTE: this function expects that the current character is an alphabet or '_', and not a digit
     //       the caller must guarantee this condition
     fn symbol(&mut self) -> Token {
         let position = self.position;
@@ -267,20 +339,53 @@ impl Lexer {
         }
     }
 
+    // decimal literals go up to 32768, which is only valid as `-32768` and is left to the
+    // parser. Hexadecimal and binary literals are bit patterns, and take all 16 bits.
     fn number(&mut self) -> Token {
         let position = self.position;
-        while self.peek().is_ascii_digit() {
+        let radix = match self.source.get(position..position + 2) {
+            Some(['0', 'x' | 'X']) => 16,
+            Some(['0', 'b' | 'B']) => 2,
+            _ => 10,
+        };
+        if radix != 10 {
+            if !self.extensions {
+                panic!(
+                    "Hexadecimal and binary literals are only supported with --extensions, at line {}, position {}",
+                    self.line_at(position),
+                    position
+                );
+            }
+            self.position += 2;
+        }
+
+        let digits_start = self.position;
+        while self.peek().is_digit(radix) {
             self.position += 1;
         }
 
-        let num = self.source[position..self.position]
-            .iter()
-            .collect::<String>()
-            .parse::<u16>()
-            .expect("Expected number string, but failed to parse into number.");
+        let literal = self.text(position, self.position);
+        let digits = self.text(digits_start, self.position);
+        let max = if radix == 10 { 32768 } else { 0xffff };
+        let num = u32::from_str_radix(&digits, radix)
+            .ok()
+            .filter(|num| *num <= max)
+            .unwrap_or_else(|| {
+                let range = if radix == 10 { "0-32767" } else { "0-0xFFFF" };
+                panic!(
+                    "Integer literal {literal} is out of range ({range}) at line {}, position {}",
+                    self.line_at(position),
+                    position
+                )
+            });
 
+        let token_type = if radix == 10 {
+            TokenType::Integer(num as u16)
+        } else {
+            TokenType::RadixInteger(num as u16, radix)
+        };
         Token {
-            token_type: TokenType::Integer(num),
+            token_type,
             span: Span(position, self.position),
         }
     }
@@ -320,8 +425,11 @@ impl Lexer {
 }
 
 #[cfg(test)]
+#[allow(clippy::needless_borrow)]
 mod tests {
     use super::Lexer;
+    use crate::batch::compile_class;
+    use crate::options::Options;
     use crate::token::KeywordType;
     use crate::token::Token;
     use crate::token::TokenType;
@@ -338,6 +446,18 @@ mod tests {
             .collect::<Vec<TokenType>>()
     }
 
+    fn lex_with_escapes(input: &str) -> Vec<TokenType> {
+        let options = Options {
+            escapes: true,
+            ..Options::default()
+        };
+        Lexer::with_options(input, &options)
+            .all_tokens()
+            .into_iter()
+            .map(|t| t.token_type)
+            .collect::<Vec<TokenType>>()
+    }
+
     #[test]
     fn test_empty1() {
         use TokenType::*;
@@ -416,6 +536,31 @@ mod tests {
         assert_eq!(tokens, expected);
     }
 
+    #[test]
+    fn test_extensions() {
+        use TokenType::*;
+        let input = "+= -= != <= >= <-1 =-";
+        let tokens = Lexer::with_extensions(input)
+            .all_tokens()
+            .into_iter()
+            .map(|t| t.token_type)
+            .collect::<Vec<TokenType>>();
+        let expected = vec![
+            CompoundSymbol("+="),
+            CompoundSymbol("-="),
+            CompoundSymbol("!="),
+            CompoundSymbol("<="),
+            CompoundSymbol(">="),
+            Symbol('<'),
+            Symbol('-'),
+            Integer(1),
+            Symbol('='),
+            Symbol('-'),
+            Eof,
+        ];
+        assert_eq!(tokens, expected);
+    }
+
     #[test]
     fn test_number() {
         let input = "42";
@@ -434,6 +579,54 @@ mod tests {
         assert_eq!(tokens, expected);
     }
 
+    #[test]
+    fn test_number_limits() {
+        use TokenType::*;
+
+        let tokens = lex_and_get_token_types("0 32767 32768");
+        assert_eq!(tokens, vec![Integer(0), Integer(32767), Integer(32768), Eof]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Integer literal 40000 is out of range (0-32767) at line 2, position 9")]
+    fn test_number_overflow() {
+        lex_and_get_token_types("let x =\n 40000;");
+    }
+
+    #[test]
+    fn test_hex_and_binary() {
+        use TokenType::*;
+
+        let tokens = Lexer::with_extensions("0x4000 0XfFfF 0b1010 0B0 0x8000")
+            .all_tokens()
+            .into_iter()
+            .map(|t| t.token_type)
+            .collect::<Vec<TokenType>>();
+        assert_eq!(
+            tokens,
+            vec![
+                RadixInteger(0x4000, 16),
+                RadixInteger(0xffff, 16),
+                RadixInteger(10, 2),
+                RadixInteger(0, 2),
+                RadixInteger(0x8000, 16),
+                Eof
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Integer literal 0x10000 is out of range (0-0xFFFF)")]
+    fn test_hex_overflow() {
+        Lexer::with_extensions("0x10000").all_tokens();
+    }
+
+    #[test]
+    #[should_panic(expected = "Hexadecimal and binary literals are only supported with --extensions")]
+    fn test_hex_strict() {
+        lex_and_get_token_types("0x4000");
+    }
+
     #[test]
     fn test_keyword() {
         use KeywordType::*;
@@ -661,6 +854,74 @@ mod tests {
         assert_eq!(tokens, expected);
     }
 
+    #[test]
+    fn test_string_escapes() {
+        use TokenType::*;
+
+        // the literal is kept as written, escapes are resolved by `charset::decode`
+        let input = r#""say \"hi\"\n" "\\" 1"#;
+        let tokens = lex_with_escapes(input);
+        let expected = vec![
+            String(std::string::String::from(r#"say \"hi\"\n"#)),
+            String(std::string::String::from(r"\\")),
+            Integer(1),
+            Eof,
+        ];
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown escape sequence '\\q' in string literal")]
+    fn test_string_unknown_escape() {
+        lex_with_escapes(r#""\q""#);
+    }
+
+    #[test]
+    fn test_string_backslash() {
+        use TokenType::*;
+
+        // without escapes a backslash is an ordinary character, as in standard Jack
+        let input = r#""[\]" "\" 1"#;
+        let tokens = lex_and_get_token_types(input);
+        let expected = vec![
+            String(std::string::String::from(r"[\]")),
+            String(std::string::String::from(r"\")),
+            Integer(1),
+            Eof,
+        ];
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "is not in the Hack character set")]
+    fn test_string_unmappable() {
+        lex_and_get_token_types("\"caf\u{e9}\"");
+    }
+
+    #[test]
+    fn test_spans() {
+        let spans = lex_and_get_tokens("let x = 10;")
+            .into_iter()
+            .map(|t| (t.span.0, t.span.1))
+            .collect::<Vec<(usize, usize)>>();
+        assert_eq!(spans, vec![(0, 3), (4, 5), (6, 7), (8, 10), (10, 11), (0, 0)]);
+    }
+
+    #[test]
+    fn test_underscore_identifiers() {
+        use TokenType::*;
+
+        // identifiers may start with an underscore, as the Jack specification allows
+        let tokens = lex_and_get_token_types("_bar __baz _1");
+        let expected = vec![
+            Identifier(std::string::String::from("_bar")),
+            Identifier(std::string::String::from("__baz")),
+            Identifier(std::string::String::from("_1")),
+            Eof,
+        ];
+        assert_eq!(tokens, expected);
+    }
+
     #[test]
     fn test_dot_identifier() {
         use TokenType::*;
@@ -676,4 +937,30 @@ mod tests {
         ];
         assert_eq!(tokens, expected);
     }
+
+    #[test]
+    fn test_integer_literals() {
+        let source = "class Sys {
+            static int min, mask, all, screen, bits;
+            function void init() {
+                let min = -32768;
+                let mask = 0x8000;
+                let all = 0xFFFF;
+                let screen = 0x4000 + 32;
+                let bits = 0b1010 - -32768;
+                while (true) { }
+                return;
+            }
+        }";
+        let options = Options { extensions: true, ..Options::default() };
+        let vm = String::from_utf8(compile_class(source, &options).vm).unwrap();
+        // every constant is within the range of `push constant`
+        for line in vm.lines().filter(|line| line.starts_with("push constant")) {
+            assert!(line[14..].parse::<u16>().unwrap() <= 32767, "{line}");
+        }
+
+        let mut emulator = emulator::vm::Vm::new(&[("Sys".to_string(), vm)]).unwrap();
+        assert!(emulator.run_until_idle(100_000));
+        assert_eq!(&emulator.ram[16..21], &[0x8000, 0x8000, 0xffff, 16416, 0x800a]);
+    }
 }
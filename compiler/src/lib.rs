@@ -1,7 +1,14 @@
 pub mod ast;
+pub mod batch;
+pub mod build_cache;
+pub mod charset;
 pub mod codegen;
+pub mod cross_check;
 pub mod lexer;
+pub mod options;
 pub mod parser;
 pub mod span;
 pub mod token;
+pub mod type_check;
+pub mod warnings;
 pub mod xml_printer;
@@ -1,10 +1,17 @@
+use std::collections::{HashMap, HashSet};
 use std::fs::{self, File};
+use std::io::Write;
 use std::path::Path;
 
-use compiler::codegen::Compiler;
+use compiler::batch::{compile_all, default_jobs};
+use compiler::build_cache::{source_hash, type_key, BuildCache, CacheEntry, CACHE_FILENAME};
+use compiler::cross_check::{calls, check_calls};
 use compiler::lexer::Lexer;
+use compiler::options::Options;
 use compiler::parser::Parser;
 use compiler::token::TokenType;
+use compiler::type_check::{check_types, Strictness};
+use compiler::warnings::{Lint, Warning};
 use compiler::xml_printer::XmlPrinter;
 
 pub fn emit_tokens(filename: &str, input: &str) {
@@ -19,7 +26,7 @@ pub fn emit_tokens(filename: &str, input: &str) {
 }
 
 pub fn emit_ast(filename: &str, input: &str) {
-    let mut parser = Parser::new(&input);
+    let mut parser = Parser::new(input);
     let parse_tree = parser.parse();
     let mut file = File::create(filename).unwrap();
     parse_tree.print_xml(&mut file);
@@ -52,19 +59,61 @@ pub fn emit_syntax_analysis() {
     emit_ast(&ast_output, &input);
 }
 
-pub fn compile() {
-    let args = std::env::args().collect::<Vec<String>>();
+pub fn compile(mut args: Vec<String>) {
+
+    // `--jobs <n>` sets how many classes are compiled in parallel
+    let jobs = args.iter().position(|arg| arg == "--jobs").map(|i| {
+        let jobs = args
+            .get(i + 1)
+            .and_then(|n| n.parse::<usize>().ok())
+            .unwrap_or_else(|| panic!("--jobs expects a number of threads."));
+        args.drain(i..i + 2);
+        jobs
+    });
+    let jobs = jobs.unwrap_or_else(default_jobs);
+
+    // `--deny <lint>` turns the warnings of a lint into errors, `--deny warnings` all of them
+    let mut denied = HashSet::new();
+    while let Some(i) = args.iter().position(|arg| arg == "--deny") {
+        match args.get(i + 1).map(String::as_str) {
+            Some("warnings") => denied.extend(Lint::ALL),
+            Some(name) => {
+                denied.insert(Lint::from_name(name).unwrap_or_else(|| panic!("Unknown lint '{name}'.")));
+            }
+            None => panic!("--deny expects a lint name or 'warnings'."),
+        }
+        args.drain(i..i + 2);
+    }
+
+    // `--type-check <lenient|strict>` checks the types of the whole program
+    let type_check = args.iter().position(|arg| arg == "--type-check").map(|i| {
+        let strictness = args
+            .get(i + 1)
+            .and_then(|name| Strictness::from_name(name))
+            .unwrap_or_else(|| panic!("--type-check expects 'lenient' or 'strict'."));
+        args.drain(i..i + 2);
+        strictness
+    });
+
+    let options = Options::from_args(&mut args);
+
+    // `--no-cache` recompiles every class regardless of the build cache
+    let use_cache = !args.iter().any(|arg| arg == "--no-cache");
+    let args = args
+        .into_iter()
+        .filter(|arg| !arg.starts_with("--"))
+        .collect::<Vec<String>>();
 
     if args.len() < 2 {
-        println!("help: compiler <path to jack code>");
+        println!("help: compiler [--no-cache] [--jobs <n>] [--deny <lint>] [--type-check <lenient|strict>] [--string-pool] [--extensions] [--precedence] [--optimize] [--debug-checks] [--escapes] <path to jack code>");
         panic!("Please supply file name");
     }
 
-    let filenames: Vec<String> = if args[1].ends_with(".jack") {
+    let mut filenames: Vec<String> = if args[1].ends_with(".jack") {
         vec![args[1].to_string()]
     } else {
         fs::read_dir(&args[1])
-            .expect(&format!("{} is not a directory", args[1]))
+            .unwrap_or_else(|_| panic!("{} is not a directory", args[1]))
             .map(|entry| {
                 entry
                     .unwrap()
@@ -76,6 +125,19 @@ pub fn compile() {
             .filter(|path| path.ends_with(".jack"))
             .collect::<Vec<String>>()
     };
+    // `read_dir` order depends on the file system
+    filenames.sort();
+
+    let mut cache = BuildCache::load(Path::new(CACHE_FILENAME));
+
+    // classes whose interface may differ from the one other classes were last checked against
+    let mut changed = HashSet::new();
+    let mut recompiled = HashSet::new();
+    let mut interfaces = HashMap::new();
+    let mut outputs = Vec::new();
+    let mut pending = Vec::new();
+    // every class of the project, for the type checker
+    let mut sources = HashMap::new();
 
     for filepath in &filenames {
         let filename_no_ext = Path::new(filepath)
@@ -85,18 +147,184 @@ pub fn compile() {
             .into_string()
             .unwrap();
         let compilation_output = format!("{}.vm", filename_no_ext);
-        let mut output_file = File::create(compilation_output).unwrap();
 
         let input = fs::read_to_string(filepath)
             .expect("Expected path to valid jack file. Make sure the file exists.");
+        let hash = source_hash(&input, &options);
+
+        let cached = cache.get(&filename_no_ext).filter(|entry| {
+            use_cache && entry.hash == hash && Path::new(&compilation_output).exists()
+        });
+
+        if let Some(entry) = cached {
+            interfaces.insert(filename_no_ext.clone(), entry.interface.clone());
+        } else {
+            pending.push((filename_no_ext.clone(), compilation_output.clone(), hash, input.clone()));
+        }
+        sources.insert(filename_no_ext.clone(), input);
+
+        outputs.push((filename_no_ext, compilation_output));
+    }
+
+    let pending_sources = pending
+        .iter()
+        .map(|(_, _, _, input)| input.clone())
+        .collect::<Vec<String>>();
+    let compiled = compile_all(&pending_sources, jobs, &options);
+
+    for ((class, compilation_output, hash, _), compiled) in pending.into_iter().zip(compiled) {
+        let mut output_file = File::create(&compilation_output).unwrap();
+        output_file.write_all(&compiled.vm).unwrap();
+
+        let interface = compiled.interface;
+        if cache.get(&class).map(|entry| &entry.interface) != Some(&interface) {
+            changed.insert(class.clone());
+        }
+        let entry = CacheEntry {
+            hash,
+            interface: interface.clone(),
+            subroutine_lines: compiled.subroutine_lines,
+            warnings: compiled.warnings,
+            declarations: compiled.declarations,
+            types: None,
+        };
+        cache.insert(&class, entry);
+        interfaces.insert(class.clone(), interface);
+        recompiled.insert(class);
+    }
+
+    // forget classes that are no longer part of the project
+    let removed = cache
+        .classes()
+        .filter(|class| !interfaces.contains_key(*class))
+        .cloned()
+        .collect::<Vec<String>>();
+    for class in removed {
+        cache.remove(&class);
+        changed.insert(class);
+    }
+
+    // a class is checked again only if it was recompiled, or if it calls a class whose interface changed
+    let mut errors = Vec::new();
+    let mut called = HashSet::new();
+    for (class, compilation_output) in &outputs {
+        let vm = fs::read_to_string(compilation_output).unwrap();
+        let calls = calls(&vm);
+        if recompiled.contains(class) || calls.iter().any(|(callee, _, _)| changed.contains(callee)) {
+            errors.extend(check_calls(class, &calls, &interfaces));
+        }
+        called.extend(calls.into_iter().map(|(callee, subroutine, _)| (callee, subroutine)));
+    }
+
+    // the types of a class depend on its source and on the declarations of every class. a
+    // class is checked again only if it did not pass a check with the same declarations, and
+    // the others are only known by their declarations.
+    if let Some(strictness) = type_check {
+        let key = type_key(
+            strictness,
+            outputs.iter().map(|(class, _)| cache.get(class).unwrap().declarations.as_str()),
+        );
+        let (passed, checked): (Vec<_>, Vec<_>) = outputs
+            .iter()
+            .map(|(class, _)| class)
+            .partition(|class| cache.get(class).unwrap().types == Some(key));
+        let classes = checked
+            .iter()
+            .map(|class| Parser::with_options(&sources[*class], &options).parse())
+            .collect::<Vec<_>>();
+        let declared = passed
+            .iter()
+            .map(|class| Parser::new(&cache.get(class).unwrap().declarations).parse())
+            .collect::<Vec<_>>();
+        errors.extend(check_types(&classes, &declared, strictness));
+
+        // the cache is only saved if the whole build succeeds
+        for class in checked {
+            let mut entry = cache.remove(class).unwrap();
+            entry.types = Some(key);
+            cache.insert(class, entry);
+        }
+    }
+
+    // warnings of cached classes are reported again, so that `--deny` does not depend on the cache
+    for (class, _) in &outputs {
+        let entry = cache.get(class).expect("Every class of the project is in the cache.");
+        let mut warnings = entry.warnings.clone();
+
+        // only a whole project shows whether a subroutine is called. the OS calls `Sys.init`,
+        // which calls `Main.main`.
+        if !args[1].ends_with(".jack") {
+            for (subroutine, line) in &entry.subroutine_lines {
+                let entry_point = matches!((class.as_str(), subroutine.as_str()), ("Main", "main") | ("Sys", "init"));
+                if !entry_point && !called.contains(&(class.clone(), subroutine.clone())) {
+                    warnings.push(Warning::new(
+                        Lint::UnusedSubroutine,
+                        *line,
+                        format!("Subroutine '{class}.{subroutine}' is never called."),
+                    ));
+                }
+            }
+            warnings.sort_by_key(|w| w.line);
+        }
+
+        for warning in warnings {
+            let Warning { lint, line, message } = warning;
+            if denied.contains(&lint) {
+                errors.push(format!("{class}.jack, line {line}: {message} (denied {lint})"));
+            } else {
+                eprintln!("warning[{lint}]: {class}.jack, line {line}: {message}");
+            }
+        }
+    }
 
-        let mut parser = Parser::new(&input);
-        let ast = parser.parse();
-        let mut compiler = Compiler::new();
-        compiler.compile(&ast, &mut output_file);
+    if !errors.is_empty() {
+        panic!("{}", errors.join("\n"));
+    }
+
+    if use_cache {
+        cache.save().expect("Failed to write the build cache.");
     }
 }
 
 fn main() {
-    compile();
+    compile(std::env::args().collect());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::panic;
+
+    use super::compile;
+    use compiler::build_cache::CACHE_FILENAME;
+
+    #[test]
+    fn test_type_check_cached() {
+        // the build writes the .vm files and the build cache into the current directory
+        let dir = std::env::temp_dir().join(format!("compiler-type-check-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        std::env::set_current_dir(&dir).unwrap();
+        let args = || ["compiler", ".", "--type-check", "strict"].map(str::to_string).to_vec();
+
+        let main = "class Main { function void main() { var int x; let x = Point.size(); return; } }";
+        fs::write("Main.jack", main).unwrap();
+        fs::write("Point.jack", "class Point { function int size() { return 2; } }").unwrap();
+        compile(args());
+
+        // a new body of Point leaves the declarations unchanged, and with them the key of the
+        // check Main passed, so only Point is checked again
+        let manifest = fs::read_to_string(CACHE_FILENAME).unwrap();
+        let main_key = manifest.lines().skip_while(|line| !line.starts_with("Main ")).nth(2).unwrap().to_string();
+        assert!(main_key.starts_with("\ttypes "));
+        fs::write("Point.jack", "class Point { function int size() { return 3; } }").unwrap();
+        compile(args());
+        assert!(fs::read_to_string(CACHE_FILENAME).unwrap().contains(&format!("\n{main_key}\n")));
+
+        // only Point is compiled again, but Main is checked against its new return type
+        fs::write("Point.jack", "class Point { function boolean size() { return true; } }").unwrap();
+        let error = panic::catch_unwind(|| compile(args())).unwrap_err();
+        let message = error.downcast_ref::<String>().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(message, "Main.jack, line 1: Cannot assign boolean to 'x' of type int.");
+    }
 }
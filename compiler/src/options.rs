@@ -0,0 +1,85 @@
+use std::fmt;
+
+/// Switches that change the generated code. The defaults follow the Jack specification.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Options {
+    /// Build every distinct string literal of a class once, and share it between its uses.
+    pub string_pool: bool,
+    /// Accept the extensions to the Jack language: `for` loops, `break`/`continue`,
+    /// `else if`, `+=`/`-=`, the comparisons `!=`, `<=` and `>=`, hexadecimal and binary
+    /// literals, `const` declarations and `switch` statements.
+    pub extensions: bool,
+    /// Parse expressions with conventional operator precedence instead of strictly left to
+    /// right, e.g. `1 + 2 * 3` is 7 rather than 9.
+    pub precedence: bool,
+    /// Fold constant subexpressions, and reduce multiplication and division by powers of two
+    /// to additions.
+    pub optimize: bool,
+    /// Check for null references before method calls and array accesses, and for indices
+    /// out of bounds on arrays created by `Array.new`, stopping with `Sys.error`.
+    pub debug_checks: bool,
+    /// Resolve the escape sequences `\"`, `\\`, `\n`, `\t` and `\xNN` in string literals.
+    /// Without it a backslash is an ordinary character, as in standard Jack.
+    pub escapes: bool,
+}
+
+impl Options {
+    /// Removes the flags this struct knows about from `args`, setting the matching options.
+    pub fn from_args(args: &mut Vec<String>) -> Self {
+        let mut options = Options::default();
+        args.retain(|arg| match arg.as_str() {
+            "--string-pool" => {
+                options.string_pool = true;
+                false
+            }
+            "--extensions" => {
+                options.extensions = true;
+                false
+            }
+            "--precedence" => {
+                options.precedence = true;
+                false
+            }
+            "--optimize" => {
+                options.optimize = true;
+                false
+            }
+            "--debug-checks" => {
+                options.debug_checks = true;
+                false
+            }
+            "--escapes" => {
+                options.escapes = true;
+                false
+            }
+            _ => true,
+        });
+        options
+    }
+}
+
+// the command line flags that select these options
+impl fmt::Display for Options {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut flags = Vec::new();
+        if self.string_pool {
+            flags.push("--string-pool");
+        }
+        if self.extensions {
+            flags.push("--extensions");
+        }
+        if self.precedence {
+            flags.push("--precedence");
+        }
+        if self.optimize {
+            flags.push("--optimize");
+        }
+        if self.debug_checks {
+            flags.push("--debug-checks");
+        }
+        if self.escapes {
+            flags.push("--escapes");
+        }
+        write!(f, "{}", flags.join(" "))
+    }
+}
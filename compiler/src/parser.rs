@@ -1,6 +1,7 @@
 use crate::ast::class::Class;
 use crate::ast::class_var_dec::ClassVarDec;
-use crate::ast::expr::Expr;
+use crate::ast::const_dec::ConstDec;
+use crate::ast::expr::{precedence, Expr, GREATER_EQUAL, LESS_EQUAL, NOT_EQUAL};
 use crate::ast::expr_term::ExprTerm;
 use crate::ast::statement::Statement;
 use crate::ast::subroutine_body::SubroutineBody;
@@ -11,18 +12,39 @@ use crate::ast::var_dec::VarDec;
 use crate::ast::variable_scope::VariableScope;
 use crate::ast::variable_type::VariableType;
 use crate::lexer::Lexer;
+use crate::options::Options;
 use crate::token::{KeywordType, TokenType};
+use crate::warnings::{Lint, Warning};
 
 pub struct Parser {
     lexer: Lexer,
+    // accept the extensions to the language, see `Options::extensions`
+    extensions: bool,
+    // parse expressions with conventional precedence, see `Options::precedence`
+    precedence: bool,
+    warnings: Vec<Warning>,
 }
 
 impl Parser {
     pub fn new(input: &str) -> Self {
-        let mut lexer = Lexer::new(input);
+        Self::with_options(input, &Options::default())
+    }
+
+    pub fn with_options(input: &str, options: &Options) -> Self {
+        let mut lexer = Lexer::with_options(input, options);
         lexer.advance_token();
         lexer.advance_token();
-        Parser { lexer }
+        Parser {
+            lexer,
+            extensions: options.extensions,
+            precedence: options.precedence,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Warnings about the code parsed so far.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
     }
 
     pub fn parse(&mut self) -> Class {
@@ -33,6 +55,17 @@ impl Parser {
         self.lexer.advance_token().token_type
     }
 
+    // line of the token about to be parsed
+    fn line(&self) -> usize {
+        self.lexer.line_at(self.lexer.current_token.span.0)
+    }
+
+    fn require_extensions(&self, feature: &str) {
+        if !self.extensions {
+            panic!("{feature} is only supported with --extensions.");
+        }
+    }
+
     fn parse_class(&mut self) -> Class {
         let tt = self.advance();
         let TokenType::Keyword(KeywordType::Class) = tt else {
@@ -43,19 +76,22 @@ impl Parser {
 
         self.consume_symbol('{');
 
-        let variables = self.parse_class_var_decs();
+        let (constants, variables) = self.parse_class_var_decs();
         let subroutines = self.parse_subroutine_decs();
 
         self.consume_symbol('}');
 
         Class {
             name,
+            constants,
             variables,
             subroutines,
         }
     }
 
-    fn parse_class_var_decs(&mut self) -> Vec<ClassVarDec> {
+    // constants may be declared among the class variables
+    fn parse_class_var_decs(&mut self) -> (Vec<ConstDec>, Vec<ClassVarDec>) {
+        let mut const_decs = Vec::new();
         let mut class_var_decs = Vec::new();
 
         loop {
@@ -63,8 +99,15 @@ impl Parser {
             let scope = match tt {
                 TokenType::Keyword(KeywordType::Static) => VariableScope::Static,
                 TokenType::Keyword(KeywordType::Field) => VariableScope::Field,
+                TokenType::Identifier(name) if name == "const" => {
+                    self.require_extensions("'const'");
+                    self.advance();
+                    const_decs.extend(self.parse_const_decs());
+                    continue;
+                }
                 _ => break,
             };
+            let line = self.line();
             self.advance();
             let typ = self.parse_type();
 
@@ -80,11 +123,41 @@ impl Parser {
 
             self.consume_symbol(';');
 
-            let dec = ClassVarDec { scope, typ, vars };
+            let dec = ClassVarDec {
+                scope,
+                typ,
+                vars,
+                line,
+            };
             class_var_decs.push(dec);
         }
 
-        class_var_decs
+        (const_decs, class_var_decs)
+    }
+
+    // `const type NAME = value, ...;` after `const`
+    fn parse_const_decs(&mut self) -> Vec<ConstDec> {
+        let typ = self.parse_type();
+        let mut const_decs = Vec::new();
+
+        loop {
+            let name = self.parse_identifier();
+            self.consume_symbol('=');
+            let value = self.parse_expression();
+            const_decs.push(ConstDec {
+                typ: typ.clone(),
+                name,
+                value,
+            });
+
+            match self.advance() {
+                TokenType::Symbol(',') => {}
+                TokenType::Symbol(';') => break,
+                tt => panic!("Expected ',' or ';' after constant. Encountered {:?}", tt),
+            }
+        }
+
+        const_decs
     }
 
     fn parse_subroutine_decs(&mut self) -> Vec<SubroutineDec> {
@@ -110,7 +183,11 @@ impl Parser {
                 _ => panic!("Expected return type. Encountered {:?}", tt),
             };
 
+            let line = self.line();
             let name = self.parse_identifier();
+            if name.starts_with("__") {
+                panic!("Subroutine names starting with '__' are reserved for the compiler, found '{name}' at line {line}.");
+            }
 
             self.consume_symbol('(');
             let parameters = self.parse_parameter_list();
@@ -123,6 +200,7 @@ impl Parser {
                 name,
                 parameters,
                 body,
+                line,
             };
             subroutines.push(subroutine);
         }
@@ -165,10 +243,8 @@ impl Parser {
 
     fn parse_var_decs(&mut self) -> Vec<VarDec> {
         let mut decs = vec![];
-        loop {
-            let TokenType::Keyword(KeywordType::Var) = self.lexer.get_current_token_type() else {
-                break
-            };
+        while let TokenType::Keyword(KeywordType::Var) = self.lexer.get_current_token_type() {
+            let line = self.line();
             self.advance();
 
             let typ = self.parse_type();
@@ -182,7 +258,7 @@ impl Parser {
             }
             self.consume_symbol(';');
 
-            let var_dec = VarDec { typ, vars };
+            let var_dec = VarDec { typ, vars, line };
             decs.push(var_dec);
         }
         decs
@@ -221,8 +297,11 @@ impl Parser {
 
     fn parse_statements(&mut self) -> Vec<Statement> {
         let mut statements = Vec::new();
+        // whether the statements parsed so far always jump away
+        let mut jumped = false;
 
         loop {
+            let line = self.line();
             let stmt = match self.lexer.get_current_token_type() {
                 TokenType::Keyword(KeywordType::Let) => {
                     self.advance();
@@ -244,8 +323,38 @@ impl Parser {
                     self.advance();
                     self.parse_return_statement()
                 }
+                // not keywords of Jack, so they stay usable as names in strict mode
+                TokenType::Identifier(name) if name == "for" => {
+                    self.require_extensions("'for'");
+                    self.advance();
+                    self.parse_for_statement()
+                }
+                TokenType::Identifier(name) if name == "switch" => {
+                    self.require_extensions("'switch'");
+                    self.advance();
+                    self.parse_switch_statement()
+                }
+                TokenType::Identifier(name) if name == "break" || name == "continue" => {
+                    let statement = if name == "break" { Statement::Break } else { Statement::Continue };
+                    self.require_extensions(&format!("'{name}'"));
+                    self.advance();
+                    self.consume_symbol(';');
+                    statement
+                }
                 _ => break,
             };
+
+            if jumped {
+                // one warning for the whole rest of the block
+                self.warnings.push(Warning::new(
+                    Lint::UnreachableCode,
+                    line,
+                    "Statements after 'return', 'break' or 'continue' are never run.".to_string(),
+                ));
+                jumped = false;
+            } else if matches!(stmt, Statement::Return { .. } | Statement::Break | Statement::Continue) {
+                jumped = true;
+            }
             statements.push(stmt);
         }
 
@@ -255,7 +364,15 @@ impl Parser {
     fn parse_while_statement(&mut self) -> Statement {
         self.consume_symbol('(');
 
+        let line = self.line();
         let condition = self.parse_expression();
+        if condition.rhs.is_empty() && matches!(condition.lhs, ExprTerm::False | ExprTerm::Integer(0)) {
+            self.warnings.push(Warning::new(
+                Lint::DeadLoop,
+                line,
+                "The condition of this while loop is always false, so its body is never run.".to_string(),
+            ));
+        }
 
         self.consume_symbol(')');
         self.consume_symbol('{');
@@ -270,6 +387,85 @@ impl Parser {
         }
     }
 
+    fn parse_for_statement(&mut self) -> Statement {
+        self.consume_symbol('(');
+
+        let init = self.parse_for_assignment(';');
+        let condition = match self.lexer.get_current_token_type() {
+            TokenType::Symbol(';') => None,
+            _ => Some(self.parse_expression()),
+        };
+        self.consume_symbol(';');
+        let step = self.parse_for_assignment(')');
+
+        self.consume_symbol('{');
+        let statements = self.parse_statements();
+        self.consume_symbol('}');
+
+        Statement::For {
+            init,
+            condition,
+            step,
+            statements,
+        }
+    }
+
+    fn parse_switch_statement(&mut self) -> Statement {
+        self.consume_symbol('(');
+        let selector = self.parse_expression();
+        self.consume_symbol(')');
+        self.consume_symbol('{');
+
+        let mut cases = Vec::new();
+        let mut default = None;
+        loop {
+            match self.advance() {
+                TokenType::Identifier(name) if name == "case" => {
+                    let mut values = vec![self.parse_expression()];
+                    while let TokenType::Symbol(',') = self.lexer.get_current_token_type() {
+                        self.advance();
+                        values.push(self.parse_expression());
+                    }
+                    self.consume_symbol(':');
+                    cases.push((values, self.parse_statements()));
+                }
+                TokenType::Identifier(name) if name == "default" => {
+                    self.consume_symbol(':');
+                    default = Some(self.parse_statements());
+                    // the default case comes last
+                    self.consume_symbol('}');
+                    break;
+                }
+                TokenType::Symbol('}') => break,
+                tt => panic!("Expected 'case', 'default' or '}}' in switch statement. Encountered {:?}", tt),
+            }
+        }
+
+        Statement::Switch {
+            selector,
+            cases,
+            default,
+        }
+    }
+
+    // the optional assignment of a for loop header, up to and including `end`.
+    // `let` may be left out.
+    fn parse_for_assignment(&mut self, end: char) -> Option<Box<Statement>> {
+        if let TokenType::Symbol(c) = self.lexer.get_current_token_type() {
+            if *c == end {
+                self.advance();
+                return None;
+            }
+        }
+
+        if let TokenType::Keyword(KeywordType::Let) = self.lexer.get_current_token_type() {
+            self.advance();
+        }
+        let assignment = self.parse_assignment();
+        self.consume_symbol(end);
+        Some(Box::new(assignment))
+    }
+
     fn parse_if_statement(&mut self) -> Statement {
         self.consume_symbol('(');
 
@@ -285,6 +481,18 @@ impl Parser {
         let if_false =
             if let TokenType::Keyword(KeywordType::Else) = self.lexer.get_current_token_type() {
                 self.advance();
+
+                // `else if` is short for an else branch holding only the inner if statement
+                if let TokenType::Keyword(KeywordType::If) = self.lexer.get_current_token_type() {
+                    self.require_extensions("'else if'");
+                    self.advance();
+                    return Statement::If {
+                        condition,
+                        if_true,
+                        if_false: Some(vec![self.parse_if_statement()]),
+                    };
+                }
+
                 self.consume_symbol('{');
 
                 let if_false = self.parse_statements();
@@ -304,17 +512,30 @@ impl Parser {
     }
 
     fn parse_return_statement(&mut self) -> Statement {
+        let line = self.line();
         if let TokenType::Symbol(';') = self.lexer.get_current_token_type() {
             self.advance();
-            return Statement::Return { value: None };
+            return Statement::Return { value: None, line };
         }
 
         let expr = self.parse_expression();
         self.consume_symbol(';');
-        Statement::Return { value: Some(expr) }
+        Statement::Return {
+            value: Some(expr),
+            line,
+        }
     }
 
     fn parse_let_statement(&mut self) -> Statement {
+        let statement = self.parse_assignment();
+        self.consume_symbol(';');
+        statement
+    }
+
+    // `name = expr` or `name[index] = expr`. in extended Jack the operator may also be
+    // `+=` or `-=`.
+    fn parse_assignment(&mut self) -> Statement {
+        let line = self.line();
         let name = self.parse_identifier();
 
         let access = if let TokenType::Symbol('[') = self.lexer.get_current_token_type() {
@@ -327,14 +548,40 @@ impl Parser {
             None
         };
 
-        self.consume_symbol('=');
+        let op = match self.advance() {
+            TokenType::Symbol('=') => None,
+            // only lexed with extensions
+            TokenType::CompoundSymbol(symbol @ ("+=" | "-=")) => symbol.chars().next(),
+            tt => panic!("Expected '='. Encountered {:?}", tt),
+        };
         let expr = self.parse_expression();
-        self.consume_symbol(';');
 
-        Statement::Let { name, access, expr }
+        // `x += e` is lowered to `let x = x + (e)`, while an element keeps its operator so
+        // that its index is evaluated once
+        let (compound, expr) = match (op, &access) {
+            (Some(op), None) => {
+                let line = expr.line;
+                let expr = Expr {
+                    lhs: ExprTerm::Variable(name.clone()),
+                    rhs: vec![(op, ExprTerm::Group(Box::new(expr)))],
+                    line,
+                };
+                (None, expr)
+            }
+            (op, _) => (op, expr),
+        };
+
+        Statement::Let {
+            name,
+            access,
+            compound,
+            expr,
+            line,
+        }
     }
 
     fn parse_do_statement(&mut self) -> Statement {
+        let line = self.line();
         let tt = self.advance();
         let TokenType::Identifier(ref n) = tt else {
             panic!("Expected subroutine name after 'do'. Encountered {:?}", tt);
@@ -363,33 +610,84 @@ impl Parser {
         };
         self.consume_symbol(';');
 
-        Statement::Do { call }
+        Statement::Do { call, line }
     }
 
     fn parse_expression(&mut self) -> Expr {
+        let line = self.line();
+        if self.precedence {
+            let lhs = self.parse_binary(0);
+            return Expr {
+                lhs,
+                rhs: Vec::new(),
+                line,
+            };
+        }
+
+        let start = self.lexer.current_token.span.0;
         let lhs = self.parse_term();
 
         let mut rhs = Vec::new();
-        loop {
-            use TokenType::*;
-            match self.lexer.get_current_token_type() {
-                Symbol('+') | Symbol('-') | Symbol('*') | Symbol('/') | Symbol('&')
-                | Symbol('|') | Symbol('<') | Symbol('>') | Symbol('=') => {
-                    let Symbol(c) = self.advance() else { unreachable!("Token type must be a symbol here.") };
-                    let t = self.parse_term();
-                    rhs.push((c, t));
-                }
-                _ => break,
-            }
+        while let Some(op) = self.peek_operator() {
+            self.advance();
+            let t = self.parse_term();
+            rhs.push((op, t));
         }
 
-        Expr { lhs, rhs }
+        let expr = Expr { lhs, rhs, line };
+        if expr.mixes_precedence() {
+            let end = self.lexer.current_token.span.0;
+            self.warnings.push(Warning::new(
+                Lint::Precedence,
+                line,
+                format!(
+                    "'{}' is evaluated left to right. Add parentheses to make the order explicit.",
+                    self.lexer.text(start, end)
+                ),
+            ));
+        }
+        expr
+    }
+
+    // precedence climbing: parses terms joined by operators that bind at least as tightly as `min`
+    fn parse_binary(&mut self, min: u8) -> ExprTerm {
+        let mut lhs = self.parse_term();
+        while let Some(op) = self.peek_operator().filter(|op| precedence(*op) >= min) {
+            self.advance();
+            // operators of the same precedence associate to the left
+            let rhs = self.parse_binary(precedence(op) + 1);
+            lhs = ExprTerm::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        lhs
+    }
+
+    // the binary operator at the current token, if any
+    fn peek_operator(&self) -> Option<char> {
+        use TokenType::*;
+        match self.lexer.get_current_token_type() {
+            Symbol(c @ ('+' | '-' | '*' | '/' | '&' | '|' | '<' | '>' | '=')) => Some(*c),
+            CompoundSymbol("!=") => Some(NOT_EQUAL),
+            CompoundSymbol("<=") => Some(LESS_EQUAL),
+            CompoundSymbol(">=") => Some(GREATER_EQUAL),
+            _ => None,
+        }
     }
 
     fn parse_term(&mut self) -> ExprTerm {
+        let span = self.lexer.current_token.span;
         let tt = self.advance();
         match tt {
-            TokenType::Integer(num) => ExprTerm::Integer(num),
+            TokenType::Integer(num) => {
+                if num > 32767 {
+                    panic!(
+                        "Integer literal {num} is out of range (0-32767) at line {}. Only -32768 can be written.",
+                        self.lexer.line_at(span.0)
+                    );
+                }
+                ExprTerm::Integer(num)
+            }
+            // hexadecimal and binary literals may take all 16 bits
+            TokenType::RadixInteger(num, _) => ExprTerm::Integer(num),
             TokenType::String(ref s) => ExprTerm::Str(s.clone()),
             TokenType::Keyword(typ) => match typ {
                 KeywordType::True => ExprTerm::True,
@@ -440,6 +738,11 @@ impl Parser {
                 ExprTerm::Group(Box::new(expr))
             }
             TokenType::Symbol('-') => {
+                // the only place where 32768 is a valid literal
+                if let TokenType::Integer(32768) = self.lexer.get_current_token_type() {
+                    self.advance();
+                    return ExprTerm::Unary('-', Box::new(ExprTerm::Integer(32768)));
+                }
                 let term = self.parse_term();
                 ExprTerm::Unary('-', Box::new(term))
             }
@@ -474,13 +777,17 @@ impl Parser {
 #[cfg(test)]
 mod tests {
     use crate::ast;
+    use crate::ast::expr::{GREATER_EQUAL, LESS_EQUAL, NOT_EQUAL};
     use crate::ast::expr_term::ExprTerm;
     use crate::ast::statement::Statement;
     use crate::ast::subroutine_call::SubroutineCall;
     use crate::ast::subroutine_kind::SubroutineKind;
     use crate::ast::variable_scope::VariableScope;
     use crate::ast::variable_type::VariableType;
+    use crate::batch::compile_class;
+    use crate::options::Options;
     use crate::parser::Parser;
+    use crate::warnings::{Lint, Warning};
 
     #[test]
     fn test_term_int() {
@@ -677,7 +984,7 @@ mod tests {
         let mut parser = Parser::new(input);
         let stmts = parser.parse_statements();
         assert_eq!(stmts.len(), 1);
-        let Statement::Let { name, access, expr } = &stmts[0] else {
+        let Statement::Let { name, access, expr, .. } = &stmts[0] else {
             panic!("Must be let.");
         };
 
@@ -697,7 +1004,7 @@ mod tests {
         let mut parser = Parser::new(input);
         let stmts = parser.parse_statements();
         assert_eq!(stmts.len(), 1);
-        let Statement::Let { name, access, expr } = &stmts[0] else {
+        let Statement::Let { name, access, expr, .. } = &stmts[0] else {
             panic!("Must be let.");
         };
 
@@ -723,7 +1030,7 @@ mod tests {
         let stmts = parser.parse_statements();
 
         assert_eq!(stmts.len(), 2);
-        let Statement::Do { call } = &stmts[0] else {
+        let Statement::Do { call, .. } = &stmts[0] else {
             panic!("Must be do statement.");
         };
         let SubroutineCall::Function(n, args) = call else {
@@ -735,7 +1042,7 @@ mod tests {
             panic!("First argument must be 3.");
         };
 
-        let Statement::Do { call } = &stmts[1] else {
+        let Statement::Do { call, .. } = &stmts[1] else {
             panic!("Must be do statement.");
         };
         let SubroutineCall::Method(first, second, args) = call else {
@@ -815,7 +1122,7 @@ mod tests {
         let stmts = parser.parse_statements();
 
         assert_eq!(stmts.len(), 2);
-        let Statement::Return { value } = &stmts[0] else {
+        let Statement::Return { value, .. } = &stmts[0] else {
             panic!("Must be return statement.");
         };
 
@@ -827,7 +1134,7 @@ mod tests {
             panic!("Must be `this`.");
         };
 
-        let Statement::Return { value } = &stmts[1] else {
+        let Statement::Return { value, .. } = &stmts[1] else {
             panic!("Must be return statement.");
         };
         let None = value else {
@@ -843,7 +1150,7 @@ mod tests {
             static char foobar;
         ";
         let mut parser = Parser::new(input);
-        let decs = parser.parse_class_var_decs();
+        let (_, decs) = parser.parse_class_var_decs();
 
         assert_eq!(decs.len(), 3);
 
@@ -966,4 +1273,347 @@ mod tests {
         assert_eq!(class.variables[0].vars[0], "data");
         assert_eq!(class.subroutines.len(), 5);
     }
+
+    fn extended(input: &str) -> Parser {
+        let options = crate::options::Options {
+            extensions: true,
+            ..Default::default()
+        };
+        Parser::with_options(input, &options)
+    }
+
+    #[test]
+    fn test_statement_for() {
+        let input = "for (i = 0; i <= 9; let i += 1) { if (i = 3) { continue; } break; }";
+        let stmts = extended(input).parse_statements();
+        assert_eq!(stmts.len(), 1);
+        let Statement::For { init, condition, step, statements } = &stmts[0] else {
+            panic!("Must be for.");
+        };
+
+        let Some(Statement::Let { name, .. }) = init.as_deref() else { panic!("Must be let.") };
+        assert_eq!(name, "i");
+        assert_eq!(condition.as_ref().unwrap().rhs[0].0, LESS_EQUAL);
+        let Some(Statement::Let { expr, .. }) = step.as_deref() else { panic!("Must be let.") };
+        let ExprTerm::Variable(ref v) = expr.lhs else { panic!("Must be variable.") };
+        assert_eq!(v, "i");
+        assert_eq!(expr.rhs[0].0, '+');
+        assert_eq!(statements[1], Statement::Break);
+    }
+
+    #[test]
+    fn test_statement_for_empty() {
+        let stmts = extended("for (;;) { }").parse_statements();
+        assert_eq!(
+            stmts[0],
+            Statement::For { init: None, condition: None, step: None, statements: vec![] }
+        );
+    }
+
+    #[test]
+    fn test_statement_else_if() {
+        let input = "if (x != 1) { } else if (x >= 2) { } else { return; }";
+        let stmts = extended(input).parse_statements();
+        let Statement::If { condition, if_false: Some(if_false), .. } = &stmts[0] else {
+            panic!("Must be if with else.");
+        };
+        assert_eq!(condition.rhs[0].0, NOT_EQUAL);
+        let [Statement::If { condition, if_false: Some(inner), .. }] = &if_false[..] else {
+            panic!("Must be a single if.");
+        };
+        assert_eq!(condition.rhs[0].0, GREATER_EQUAL);
+        assert_eq!(inner.len(), 1);
+    }
+
+    #[test]
+    fn test_statement_compound_assignment() {
+        let stmts = extended("let a[i] -= 2 * x; let x += 1;").parse_statements();
+        let Statement::Let { name, access: Some(_), compound, expr, .. } = &stmts[0] else {
+            panic!("Must be let with access.");
+        };
+        assert_eq!(name, "a");
+        assert_eq!(*compound, Some('-'));
+        assert_eq!(expr.lhs, ExprTerm::Integer(2));
+        assert_eq!(expr.rhs[0].0, '*');
+
+        let Statement::Let { access: None, compound: None, expr, .. } = &stmts[1] else {
+            panic!("Must be let without access.");
+        };
+        assert_eq!(expr.lhs, ExprTerm::Variable("x".to_string()));
+        assert_eq!(expr.rhs[0].0, '+');
+    }
+
+    #[test]
+    #[should_panic(expected = "'for' is only supported with --extensions.")]
+    fn test_strict_for() {
+        Parser::new("for (;;) { }").parse_statements();
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected '='. Encountered Symbol('+')")]
+    fn test_strict_compound_assignment() {
+        Parser::new("let x += 1;").parse_statements();
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid token type while parsing term: Symbol('=')")]
+    fn test_strict_comparison() {
+        Parser::new("let x = y <= 1;").parse_statements();
+    }
+
+    #[test]
+    fn test_strict_names() {
+        // `for`, `break` and `continue` are ordinary identifiers in Jack
+        let stmts = Parser::new("let break = for + continue;").parse_statements();
+        let Statement::Let { name, .. } = &stmts[0] else { panic!("Must be let.") };
+        assert_eq!(name, "break");
+    }
+
+    fn with_precedence(input: &str) -> Parser {
+        let options = crate::options::Options {
+            precedence: true,
+            ..Default::default()
+        };
+        Parser::with_options(input, &options)
+    }
+
+    #[test]
+    fn test_expression_precedence() {
+        use ExprTerm::*;
+        let expr = with_precedence("1 + 2 * 3 - 4 | x = 5").parse_expression();
+        assert!(expr.rhs.is_empty());
+
+        let product = Binary('*', Box::new(Integer(2)), Box::new(Integer(3)));
+        let sum = Binary('+', Box::new(Integer(1)), Box::new(product));
+        let difference = Binary('-', Box::new(sum), Box::new(Integer(4)));
+        let comparison = Binary('=', Box::new(Variable("x".to_string())), Box::new(Integer(5)));
+        assert_eq!(expr.lhs, Binary('|', Box::new(difference), Box::new(comparison)));
+    }
+
+    #[test]
+    fn test_expression_precedence_single_term() {
+        let expr = with_precedence("(a - b)").parse_expression();
+        let ExprTerm::Group(inner) = expr.lhs else { panic!("Must be group.") };
+        assert!(matches!(inner.lhs, ExprTerm::Binary('-', _, _)));
+    }
+
+    #[test]
+    fn test_precedence_lint() {
+        let mut parser = Parser::new("let x = a * b + c;\nlet y = a +\n  b * c;\nlet z = a + (b * c) < d;");
+        parser.parse_statements();
+        assert_eq!(
+            parser.warnings(),
+            &[Warning::new(
+                Lint::Precedence,
+                2,
+                "'a + b * c' is evaluated left to right. Add parentheses to make the order explicit.".to_string()
+            )]
+        );
+
+        let mut parser = with_precedence("let y = a + b * c;");
+        parser.parse_statements();
+        assert!(parser.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_dead_code_lints() {
+        let input = "while (false) { let x = 1; }
+            while (x) {
+                if (x) { return; let y = 2; let z = 3; }
+                let x = x - 1;
+            }
+            return;
+            do Output.println();";
+        let mut parser = Parser::new(input);
+        parser.parse_statements();
+        let found = parser
+            .warnings()
+            .iter()
+            .map(|w| (w.lint, w.line))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![(Lint::DeadLoop, 1), (Lint::UnreachableCode, 3), (Lint::UnreachableCode, 7)]
+        );
+    }
+
+    #[test]
+    fn test_const_decs() {
+        let input = "class Keys { static int a; const int LEFT = 130; field boolean b; const int RIGHT = LEFT + 2; }";
+        let class = extended(input).parse_class();
+        assert_eq!(class.variables.len(), 2);
+        assert_eq!(class.constants.len(), 2);
+        assert_eq!(class.constants[0].name, "LEFT");
+        assert_eq!(class.constants[0].value.lhs, ExprTerm::Integer(130));
+        assert_eq!(class.constants[1].value.rhs, vec![('+', ExprTerm::Integer(2))]);
+    }
+
+    #[test]
+    fn test_statement_switch() {
+        let input = "switch (key) { case LEFT, 130: let x = x - 1; case 132: break; default: return; }";
+        let stmts = extended(input).parse_statements();
+        let Statement::Switch { selector, cases, default } = &stmts[0] else {
+            panic!("Must be switch.");
+        };
+        assert_eq!(selector.lhs, ExprTerm::Variable("key".to_string()));
+        assert_eq!(cases.len(), 2);
+        assert_eq!(cases[0].0.len(), 2);
+        assert_eq!(cases[0].1.len(), 1);
+        assert_eq!(cases[1].1, vec![Statement::Break]);
+        assert_eq!(default.as_ref().map(Vec::len), Some(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected '}'")]
+    fn test_statement_switch_default_last() {
+        extended("switch (key) { default: return; case 1: return; }").parse_statements();
+    }
+
+    #[test]
+    #[should_panic(expected = "'const' is only supported with --extensions.")]
+    fn test_strict_const() {
+        Parser::new("class Keys { const int LEFT = 130; }").parse_class();
+    }
+
+    #[test]
+    fn test_term_min_int() {
+        let term = Parser::new("-32768").parse_term();
+        assert_eq!(term, ExprTerm::Unary('-', Box::new(ExprTerm::Integer(32768))));
+        let expr = Parser::new("x - -32768").parse_expression();
+        assert_eq!(expr.rhs[0].1, term);
+    }
+
+    #[test]
+    #[should_panic(expected = "Integer literal 32768 is out of range (0-32767) at line 1. Only -32768 can be written.")]
+    fn test_term_out_of_range() {
+        Parser::new("x - 32768").parse_expression();
+    }
+
+    #[test]
+    fn test_term_hex() {
+        let expr = extended("0x8000 | 0b1").parse_expression();
+        assert_eq!(expr.lhs, ExprTerm::Integer(0x8000));
+        assert_eq!(expr.rhs, vec![('|', ExprTerm::Integer(1))]);
+    }
+
+    #[test]
+    fn test_term_hex_negated() {
+        // the radix, not the digits of the literal, tells 0x8000 from 32768
+        let term = extended("-0x8000").parse_term();
+        assert_eq!(term, ExprTerm::Unary('-', Box::new(ExprTerm::Integer(0x8000))));
+        assert_eq!(extended("0x8000").parse_term(), ExprTerm::Integer(32768));
+    }
+
+    #[test]
+    #[should_panic(expected = "Subroutine names starting with '__' are reserved for the compiler, found '__string' at line 1.")]
+    fn test_reserved_subroutine_name() {
+        Parser::new("class Main { function void __string() { return; } }").parse();
+    }
+
+    #[test]
+    #[should_panic(expected = "Subroutine names starting with '__' are reserved for the compiler, found '__checkNull' at line 2.")]
+    fn test_reserved_check_name() {
+        Parser::new("class Main {\n method void __checkNull(int x, int line) { return; } }").parse();
+    }
+
+    #[test]
+    fn test_extensions() {
+        let source = "class Sys {
+            static int sum, evens, last, countdown;
+            function void init() {
+                var int i;
+                for (i = 0; i < 100; let i += 1) {
+                    if (i > 9) { break; }
+                    let sum += i;
+                    if ((i & 1) != 0) { continue; } else if (i >= 8) { let last = i; }
+                    let evens += 1;
+                }
+                let countdown = 5;
+                while (true) {
+                    let countdown -= 1;
+                    if (countdown <= 0) { break; }
+                }
+                while (true) { }
+                return;
+            }
+        }";
+        let options = Options { extensions: true, ..Options::default() };
+        let vm = String::from_utf8(compile_class(source, &options).vm).unwrap();
+
+        let mut emulator = emulator::vm::Vm::new(&[("Sys".to_string(), vm)]).unwrap();
+        assert!(emulator.run_until_idle(100_000));
+        assert_eq!(&emulator.ram[16..20], &[45, 5, 8, 0]);
+    }
+
+    #[test]
+    fn test_compound_element() {
+        // the index of `a[Sys.next()] += ...` is evaluated once, and the value may access
+        // arrays itself
+        let source = "class Sys {
+            static int calls;
+            static Array a;
+            function int next() {
+                let calls += 1;
+                return calls;
+            }
+            function void init() {
+                let a = 100;
+                let a[0] = 0;
+                let a[1] = 10;
+                let a[2] = 20;
+                let a[Sys.next()] += a[0] + 5;
+                let a[Sys.next()] -= a[1];
+                while (true) { }
+                return;
+            }
+        }";
+        let options = Options { extensions: true, ..Options::default() };
+        let vm = String::from_utf8(compile_class(source, &options).vm).unwrap();
+
+        let mut emulator = emulator::vm::Vm::new(&[("Sys".to_string(), vm)]).unwrap();
+        assert!(emulator.run_until_idle(100_000));
+        assert_eq!(emulator.ram[16], 2);
+        assert_eq!(&emulator.ram[100..103], &[0, 15, 5]);
+    }
+
+    #[test]
+    fn test_precedence() {
+        let source = "class Sys {
+            static int a, b, c;
+            function void init() {
+                let a = 1 + 2 * 3;
+                let b = 20 - 8 / 4 - 3;
+                let c = 5 & 1 + 2;
+                while (true) { }
+                return;
+            }
+        }";
+        // just enough of the OS for `*` and `/` on small positive numbers
+        let math = "class Math {
+            function int multiply(int x, int y) {
+                var int product;
+                while (y > 0) { let product = product + x; let y = y - 1; }
+                return product;
+            }
+            function int divide(int x, int y) {
+                var int quotient;
+                while (~(x < y)) { let x = x - y; let quotient = quotient + 1; }
+                return quotient;
+            }
+        }";
+        let math = String::from_utf8(compile_class(math, &Options::default()).vm).unwrap();
+        let run = |options: &Options| {
+            let vm = String::from_utf8(compile_class(source, options).vm).unwrap();
+            let modules = [("Sys".to_string(), vm), ("Math".to_string(), math.clone())];
+            let mut emulator = emulator::vm::Vm::new(&modules).unwrap();
+            assert!(emulator.run_until_idle(100_000));
+            emulator.ram[16..19].to_vec()
+        };
+
+        let options = Options { precedence: true, ..Options::default() };
+        assert_eq!(run(&options), vec![7, 15, 1]);
+        assert_eq!(run(&Options::default()), vec![9, 0, 3]);
+        let warnings = compile_class(source, &Options::default()).warnings;
+        assert_eq!(warnings.iter().filter(|w| w.lint == Lint::Precedence).count(), 3);
+    }
 }
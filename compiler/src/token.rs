@@ -2,7 +2,7 @@ use std::fs::File;
 use std::io::Write;
 
 use crate::span::Span;
-use crate::xml_printer::{print_closing, print_opening, print_tag, XmlPrinter};
+use crate::xml_printer::{escape, print_closing, print_opening, print_symbol, print_tag, XmlPrinter};
 
 #[derive(Debug, Clone)]
 pub struct Token {
@@ -22,9 +22,11 @@ impl Default for Token {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TokenType {
     Symbol(char),       // a single character representing the symbol
+    CompoundSymbol(&'static str), // one of `+=`, `-=`, `!=`, `<=`, `>=` of extended Jack
     Identifier(String), // a sequence of letters, digits, underscore not starting with digit
     Keyword(KeywordType),
     Integer(u16),
+    RadixInteger(u16, u32), // a hexadecimal or binary literal of extended Jack, and its radix
     String(String),
     Eof,
     Error,
@@ -61,9 +63,10 @@ impl XmlPrinter for TokenType {
 
         match self {
             Symbol(c) => c.print_xml(file),
+            CompoundSymbol(s) => print_symbol(file, &escape(s)),
             Identifier(name) => print_tag(file, "identifier", name),
             Keyword(k) => k.print_xml(file),
-            Integer(num) => writeln!(file, "<integerConstant> {num} </integerConstant>").unwrap(),
+            Integer(num) | RadixInteger(num, _) => writeln!(file, "<integerConstant> {num} </integerConstant>").unwrap(),
             String(name) => print_tag(file, "stringConstant", name),
             Eof => (),
             Error => panic!("Not supposed to be printed in xml format"),
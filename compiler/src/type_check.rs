@@ -0,0 +1,749 @@
+//! Type checking of whole Jack programs.
+//!
+//! Jack treats every value as a 16-bit word, and the official compiler checks no types at
+//! all. The checker has two levels of strictness:
+//! - lenient: int, char, boolean, `null` and object references convert into each other, and
+//!   `Array` converts to and from any class. Objects of two different classes do not mix,
+//!   arithmetic is not applied to objects other than arrays, and subroutines return what
+//!   they declare.
+//! - strict: in addition, int and char are the only numbers, conditions are booleans, only
+//!   `null` converts to an object, an `Array` converts to no other class, and arithmetic is
+//!   not applied to any object.
+//!
+//! In both modes, any object converts to `Array`.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::ast::class::Class;
+use crate::ast::expr::{Expr, GREATER_EQUAL, LESS_EQUAL, NOT_EQUAL};
+use crate::ast::expr_term::ExprTerm;
+use crate::ast::statement::Statement;
+use crate::ast::subroutine_call::SubroutineCall;
+use crate::ast::subroutine_dec::SubroutineDec;
+use crate::ast::subroutine_kind::SubroutineKind;
+use crate::ast::variable_type::VariableType;
+use crate::parser::Parser;
+
+/// The subroutines of the Jack OS, used for classes the program does not declare itself.
+const OS_API: [&str; 8] = [
+    "class Math {
+        function void init() {}
+        function int abs(int x) {}
+        function int multiply(int x, int y) {}
+        function int divide(int x, int y) {}
+        function int min(int x, int y) {}
+        function int max(int x, int y) {}
+        function int sqrt(int x) {}
+    }",
+    "class String {
+        constructor String new(int maxLength) {}
+        method void dispose() {}
+        method int length() {}
+        method char charAt(int j) {}
+        method void setCharAt(int j, char c) {}
+        method String appendChar(char c) {}
+        method void eraseLastChar() {}
+        method int intValue() {}
+        method void setInt(int j) {}
+        function char backSpace() {}
+        function char doubleQuote() {}
+        function char newLine() {}
+    }",
+    "class Array {
+        function Array new(int size) {}
+        method void dispose() {}
+    }",
+    "class Output {
+        function void init() {}
+        function void moveCursor(int i, int j) {}
+        function void printChar(char c) {}
+        function void printString(String s) {}
+        function void printInt(int i) {}
+        function void println() {}
+        function void backSpace() {}
+    }",
+    "class Screen {
+        function void init() {}
+        function void clearScreen() {}
+        function void setColor(boolean b) {}
+        function void drawPixel(int x, int y) {}
+        function void drawLine(int x1, int y1, int x2, int y2) {}
+        function void drawRectangle(int x1, int y1, int x2, int y2) {}
+        function void drawCircle(int x, int y, int r) {}
+    }",
+    "class Keyboard {
+        function void init() {}
+        function char keyPressed() {}
+        function char readChar() {}
+        function String readLine(String message) {}
+        function int readInt(String message) {}
+    }",
+    "class Memory {
+        function void init() {}
+        function int peek(int address) {}
+        function void poke(int address, int value) {}
+        function Array alloc(int size) {}
+        function void deAlloc(Array o) {}
+    }",
+    "class Sys {
+        function void init() {}
+        function void halt() {}
+        function void error(int errorCode) {}
+        function void wait(int duration) {}
+    }",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strictness {
+    Lenient,
+    Strict,
+}
+
+impl Strictness {
+    pub fn from_name(name: &str) -> Option<Strictness> {
+        match name {
+            "lenient" => Some(Strictness::Lenient),
+            "strict" => Some(Strictness::Strict),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Type {
+    Int,
+    Char,
+    Boolean,
+    Void,
+    Null,
+    Object(String),
+    // array elements, and whatever the checker knows nothing about. converts to any type.
+    Unknown,
+}
+
+impl From<&VariableType> for Type {
+    fn from(typ: &VariableType) -> Self {
+        match typ {
+            VariableType::Int => Type::Int,
+            VariableType::Char => Type::Char,
+            VariableType::Boolean => Type::Boolean,
+            VariableType::Void => Type::Void,
+            VariableType::Other(class) => Type::Object(class.clone()),
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Char => write!(f, "char"),
+            Type::Boolean => write!(f, "boolean"),
+            Type::Void => write!(f, "void"),
+            Type::Null => write!(f, "null"),
+            Type::Object(class) => write!(f, "{class}"),
+            Type::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Signature {
+    kind: SubroutineKind,
+    return_type: Type,
+    parameters: Vec<Type>,
+}
+
+// subroutine signatures by class and subroutine name
+type Signatures = HashMap<String, HashMap<String, Signature>>;
+
+fn signatures(class: &Class) -> HashMap<String, Signature> {
+    class
+        .subroutines
+        .iter()
+        .map(|s| {
+            let signature = Signature {
+                kind: s.kind,
+                return_type: (&s.return_type).into(),
+                parameters: s.parameters.iter().map(|(typ, _)| typ.into()).collect(),
+            };
+            (s.name.clone(), signature)
+        })
+        .collect()
+}
+
+/// The subroutine declarations of a class, as the source of a class with empty bodies, like
+/// those of `OS_API`. Checking the other classes of a program only takes these.
+pub fn declarations(class: &Class) -> String {
+    let subroutines = class.subroutines.iter().map(|s| {
+        let kind = match s.kind {
+            SubroutineKind::Constructor => "constructor",
+            SubroutineKind::Function => "function",
+            SubroutineKind::Method => "method",
+        };
+        let parameters = s
+            .parameters
+            .iter()
+            .enumerate()
+            .map(|(i, (typ, _))| format!("{} p{i}", Type::from(typ)))
+            .collect::<Vec<_>>();
+        let return_type = Type::from(&s.return_type);
+        format!(" {kind} {return_type} {}({}) {{}}", s.name, parameters.join(", "))
+    });
+    format!("class {} {{{} }}", class.name, subroutines.collect::<String>())
+}
+
+/// Checks the types of `classes`, returning an error message for each mismatch. `declared`
+/// are the other classes of the program, which are not checked themselves, e.g. as parsed
+/// from `declarations`. Calls to OS classes the program does not declare are checked
+/// against the standard OS API.
+pub fn check_types(classes: &[Class], declared: &[Class], strictness: Strictness) -> Vec<String> {
+    let mut all = OS_API
+        .iter()
+        .map(|source| {
+            let class = Parser::new(source).parse();
+            (class.name.clone(), signatures(&class))
+        })
+        .collect::<Signatures>();
+    // a class of the program replaces the OS class of the same name
+    for class in classes.iter().chain(declared) {
+        all.insert(class.name.clone(), signatures(class));
+    }
+    let project = classes
+        .iter()
+        .chain(declared)
+        .map(|class| class.name.as_str())
+        .collect::<HashSet<_>>();
+
+    let mut checker = Checker {
+        strictness,
+        signatures: &all,
+        project: &project,
+        class: "",
+        subroutine: "",
+        return_type: Type::Void,
+        class_vars: HashMap::new(),
+        locals: HashMap::new(),
+        errors: Vec::new(),
+    };
+    classes.iter().for_each(|class| checker.class(class));
+    checker.errors
+}
+
+struct Checker<'a> {
+    strictness: Strictness,
+    signatures: &'a Signatures,
+    // calls to these classes are already checked by `cross_check`, apart from the types
+    project: &'a HashSet<&'a str>,
+    class: &'a str,
+    subroutine: &'a str,
+    return_type: Type,
+    // fields, static variables and constants of the class
+    class_vars: HashMap<&'a str, Type>,
+    // parameters and local variables of the subroutine
+    locals: HashMap<&'a str, Type>,
+    errors: Vec<String>,
+}
+
+impl<'a> Checker<'a> {
+    fn error(&mut self, line: usize, message: String) {
+        self.errors.push(format!("{}.jack, line {line}: {message}", self.class));
+    }
+
+    fn strict(&self) -> bool {
+        self.strictness == Strictness::Strict
+    }
+
+    fn class(&mut self, class: &'a Class) {
+        self.class = &class.name;
+        self.class_vars = class
+            .variables
+            .iter()
+            .flat_map(|dec| dec.vars.iter().map(|name| (name.as_str(), (&dec.typ).into())))
+            .chain(class.constants.iter().map(|c| (c.name.as_str(), (&c.typ).into())))
+            .collect();
+
+        for constant in &class.constants {
+            let value = self.expr(&constant.value);
+            self.assign(value, &(&constant.typ).into(), constant.value.line, &constant.name);
+        }
+        class.subroutines.iter().for_each(|s| self.subroutine(s));
+    }
+
+    fn subroutine(&mut self, subroutine: &'a SubroutineDec) {
+        self.subroutine = &subroutine.name;
+        self.return_type = (&subroutine.return_type).into();
+        self.locals = subroutine
+            .parameters
+            .iter()
+            .map(|(typ, name)| (name.as_str(), typ.into()))
+            .chain(
+                subroutine
+                    .body
+                    .locals
+                    .iter()
+                    .flat_map(|dec| dec.vars.iter().map(|name| (name.as_str(), (&dec.typ).into()))),
+            )
+            .collect();
+
+        self.statements(&subroutine.body.statements);
+    }
+
+    fn variable(&self, name: &str) -> Type {
+        self.locals
+            .get(name)
+            .or_else(|| self.class_vars.get(name))
+            .cloned()
+            // undeclared variables are reported by the code generator
+            .unwrap_or(Type::Unknown)
+    }
+
+    fn statements(&mut self, statements: &'a [Statement]) {
+        statements.iter().for_each(|s| self.statement(s));
+    }
+
+    fn statement(&mut self, statement: &'a Statement) {
+        match statement {
+            Statement::Let {
+                name,
+                access: None,
+                expr,
+                line,
+                ..
+            } => {
+                let value = self.expr(expr);
+                self.assign(value, &self.variable(name), *line, name);
+            }
+            Statement::Let {
+                name,
+                access: Some(index),
+                compound,
+                expr,
+                line,
+            } => {
+                self.array(name, *line);
+                let index_type = self.expr(index);
+                self.operand(&index_type, "[]", index.line);
+                let value = self.expr(expr);
+                if let Some(op) = compound {
+                    // the elements of an array have no declared type
+                    self.binary(*op, Type::Unknown, value, expr.line);
+                }
+            }
+            Statement::If {
+                condition,
+                if_true,
+                if_false,
+            } => {
+                self.condition(condition);
+                self.statements(if_true);
+                if let Some(if_false) = if_false {
+                    self.statements(if_false);
+                }
+            }
+            Statement::While {
+                condition,
+                statements,
+            } => {
+                self.condition(condition);
+                self.statements(statements);
+            }
+            Statement::For {
+                init,
+                condition,
+                step,
+                statements,
+            } => {
+                init.iter().for_each(|init| self.statement(init));
+                condition.iter().for_each(|condition| self.condition(condition));
+                step.iter().for_each(|step| self.statement(step));
+                self.statements(statements);
+            }
+            Statement::Switch {
+                selector,
+                cases,
+                default,
+            } => {
+                let selector_type = self.expr(selector);
+                self.operand(&selector_type, "switch", selector.line);
+                for (_, statements) in cases {
+                    self.statements(statements);
+                }
+                if let Some(default) = default {
+                    self.statements(default);
+                }
+            }
+            Statement::Break | Statement::Continue => {}
+            Statement::Do { call, line } => {
+                self.call(call, *line);
+            }
+            Statement::Return { value, line } => match (value, self.return_type.clone()) {
+                (None, Type::Void) => {}
+                (None, typ) => self.error(
+                    *line,
+                    format!("'{}' must return a value of type {typ}.", self.subroutine),
+                ),
+                (Some(value), Type::Void) => {
+                    self.expr(value);
+                    self.error(
+                        value.line,
+                        format!("'{}' is void, so it cannot return a value.", self.subroutine),
+                    );
+                }
+                (Some(value), typ) => {
+                    let value_type = self.expr(value);
+                    if !self.converts(&value_type, &typ) {
+                        self.error(
+                            value.line,
+                            format!("'{}' returns {typ}, not {value_type}.", self.subroutine),
+                        );
+                    }
+                }
+            },
+        }
+    }
+
+    fn assign(&mut self, value: Type, target: &Type, line: usize, name: &str) {
+        if !self.converts(&value, target) {
+            self.error(line, format!("Cannot assign {value} to '{name}' of type {target}."));
+        }
+    }
+
+    fn condition(&mut self, condition: &'a Expr) {
+        let typ = self.expr(condition);
+        if self.strict() && !matches!(typ, Type::Boolean | Type::Unknown) {
+            self.error(condition.line, format!("The condition must be a boolean, not {typ}."));
+        }
+    }
+
+    // indexing requires an array
+    fn array(&mut self, name: &str, line: usize) {
+        let typ = self.variable(name);
+        let is_array = match &typ {
+            Type::Object(class) => class == "Array",
+            Type::Unknown => true,
+            _ => false,
+        };
+        if self.strict() && !is_array {
+            self.error(line, format!("'{name}' of type {typ} is not an Array."));
+        }
+    }
+
+    // checks the operand of an arithmetic operator, or of an index
+    fn operand(&mut self, typ: &Type, op: &str, line: usize) {
+        let allowed = match typ {
+            Type::Int | Type::Char | Type::Unknown => true,
+            // already reported where the call is
+            Type::Void => true,
+            Type::Boolean | Type::Null => !self.strict(),
+            Type::Object(class) => class == "Array" && !self.strict(),
+        };
+        if !allowed {
+            self.error(line, format!("Cannot apply '{op}' to {typ}."));
+        }
+    }
+
+    /// Whether a value of type `from` may be used where `to` is expected.
+    fn converts(&self, from: &Type, to: &Type) -> bool {
+        use Type::*;
+
+        match (from, to) {
+            (Unknown, _) | (_, Unknown) => true,
+            (from, to) if from == to => true,
+            (Int | Char, Int | Char) => true,
+            (Null, Object(_)) => true,
+            (Void, _) | (_, Void) => false,
+            // any object is a block of memory, e.g. for `Memory.deAlloc(this)`
+            (Object(_), Object(b)) if b == "Array" => true,
+            (Object(a), Object(_)) => a == "Array" && !self.strict(),
+            _ => self.strictness == Strictness::Lenient,
+        }
+    }
+
+    fn expr(&mut self, expr: &'a Expr) -> Type {
+        let mut typ = self.term(&expr.lhs, expr.line);
+        for (op, term) in &expr.rhs {
+            let rhs = self.term(term, expr.line);
+            typ = self.binary(*op, typ, rhs, expr.line);
+        }
+        typ
+    }
+
+    fn term(&mut self, term: &'a ExprTerm, line: usize) -> Type {
+        match term {
+            ExprTerm::Integer(_) => Type::Int,
+            ExprTerm::Str(_) => Type::Object("String".to_string()),
+            ExprTerm::True | ExprTerm::False => Type::Boolean,
+            ExprTerm::Null => Type::Null,
+            ExprTerm::This => Type::Object(self.class.to_string()),
+            ExprTerm::Variable(name) => self.variable(name),
+            ExprTerm::Access(name, index) => {
+                self.array(name, line);
+                let index_type = self.expr(index);
+                self.operand(&index_type, "[]", index.line);
+                Type::Unknown
+            }
+            ExprTerm::Call(call) => match self.call(call, line) {
+                Type::Void => {
+                    let name = match call {
+                        SubroutineCall::Function(name, _) => name.clone(),
+                        SubroutineCall::Method(namespace, name, _) => format!("{namespace}.{name}"),
+                    };
+                    self.error(line, format!("'{name}' returns nothing, so it cannot be used as a value."));
+                    Type::Unknown
+                }
+                typ => typ,
+            },
+            ExprTerm::Group(expr) => self.expr(expr),
+            ExprTerm::Unary(op, term) => {
+                let typ = self.term(term, line);
+                if *op == '~' && typ == Type::Boolean {
+                    return Type::Boolean;
+                }
+                self.operand(&typ, &op.to_string(), line);
+                Type::Int
+            }
+            ExprTerm::Binary(op, lhs, rhs) => {
+                let lhs = self.term(lhs, line);
+                let rhs = self.term(rhs, line);
+                self.binary(*op, lhs, rhs, line)
+            }
+        }
+    }
+
+    fn binary(&mut self, op: char, lhs: Type, rhs: Type, line: usize) -> Type {
+        let name = match op {
+            NOT_EQUAL => "!=".to_string(),
+            LESS_EQUAL => "<=".to_string(),
+            GREATER_EQUAL => ">=".to_string(),
+            op => op.to_string(),
+        };
+
+        match op {
+            '=' | NOT_EQUAL => {
+                if !self.converts(&lhs, &rhs) && !self.converts(&rhs, &lhs) {
+                    self.error(line, format!("Cannot compare {lhs} with {rhs}."));
+                }
+                Type::Boolean
+            }
+            '&' | '|' if lhs == Type::Boolean && rhs == Type::Boolean => Type::Boolean,
+            '&' | '|' if self.strict() && (lhs == Type::Boolean || rhs == Type::Boolean) => {
+                self.error(line, format!("Cannot apply '{name}' to {lhs} and {rhs}."));
+                Type::Unknown
+            }
+            _ => {
+                self.operand(&lhs, &name, line);
+                self.operand(&rhs, &name, line);
+                match op {
+                    '<' | '>' | LESS_EQUAL | GREATER_EQUAL => Type::Boolean,
+                    _ => Type::Int,
+                }
+            }
+        }
+    }
+
+    // the type a call returns
+    fn call(&mut self, call: &'a SubroutineCall, line: usize) -> Type {
+        let (class, name, args) = match call {
+            SubroutineCall::Function(name, args) => (self.class.to_string(), name, args),
+            SubroutineCall::Method(namespace, name, args) => match self.receiver(namespace) {
+                Some(Type::Object(class)) => (class, name, args),
+                None => (namespace.clone(), name, args),
+                Some(typ) => {
+                    self.error(line, format!("Cannot call method '{name}' on '{namespace}' of type {typ}."));
+                    args.iter().for_each(|arg| {
+                        self.expr(arg);
+                    });
+                    return Type::Unknown;
+                }
+            },
+        };
+        let callee = format!("{class}.{name}");
+        let arg_types = args.iter().map(|arg| self.expr(arg)).collect::<Vec<_>>();
+
+        let Some(subroutines) = self.signatures.get(&class) else {
+            return Type::Unknown;
+        };
+        let Some(signature) = subroutines.get(name) else {
+            if !self.project.contains(class.as_str()) {
+                self.error(line, format!("Class {class} has no subroutine '{name}'."));
+            }
+            return Type::Unknown;
+        };
+
+        let on_object = match call {
+            SubroutineCall::Function(..) => true,
+            SubroutineCall::Method(namespace, ..) => self.receiver(namespace).is_some(),
+        };
+        if signature.kind == SubroutineKind::Method && !on_object {
+            self.error(line, format!("'{callee}' is a method, so it must be called on an object."));
+        }
+        if signature.kind != SubroutineKind::Method && on_object {
+            self.error(line, format!("'{callee}' is not a method, so it must be called on its class."));
+        }
+        if !self.project.contains(class.as_str()) && args.len() != signature.parameters.len() {
+            self.error(
+                line,
+                format!("'{callee}' takes {} argument(s), but {} were given.", signature.parameters.len(), args.len()),
+            );
+        }
+        for (i, ((arg, typ), parameter)) in args.iter().zip(&arg_types).zip(&signature.parameters).enumerate() {
+            if !self.converts(typ, parameter) {
+                self.error(arg.line, format!("Argument {} of '{callee}' must be {parameter}, not {typ}.", i + 1));
+            }
+        }
+
+        signature.return_type.clone()
+    }
+
+    // the type of the receiver of a method call, or `None` if `namespace` is a class
+    fn receiver(&self, namespace: &str) -> Option<Type> {
+        self.locals
+            .get(namespace)
+            .or_else(|| self.class_vars.get(namespace))
+            .cloned()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{check_types, declarations, Strictness};
+    use crate::parser::Parser;
+
+    const BALL: &str = "class Ball {
+        constructor Ball new() { return this; }
+        method void move() { return; }
+    }";
+
+    fn check(source: &str, strictness: Strictness) -> Vec<String> {
+        let classes = [Parser::new(source).parse(), Parser::new(BALL).parse()];
+        check_types(&classes, &[], strictness)
+    }
+
+    #[test]
+    fn test_declarations() {
+        let ball = Parser::new(BALL).parse();
+        let source = "class Point {
+            field int x;
+            constructor Point new(int ax, Ball b) { let x = ax; return this; }
+            method boolean near(Point other) { return true; }
+        }";
+        let stub = declarations(&Parser::new(source).parse());
+        assert_eq!(
+            stub,
+            "class Point { constructor Point new(int p0, Ball p1) {} method boolean near(Point p0) {} }"
+        );
+
+        // a class checked against the declarations of the others alone
+        let main = "class Main {
+            function void main() { var int x; let x = Point.new(1, Ball.new()); return; }
+        }";
+        let classes = [Parser::new(main).parse()];
+        let declared = [Parser::new(&stub).parse(), Parser::new(&declarations(&ball)).parse()];
+        assert_eq!(
+            check_types(&classes, &declared, Strictness::Strict),
+            ["Main.jack, line 2: Cannot assign Point to 'x' of type int."]
+        );
+    }
+
+    #[test]
+    fn test_lenient() {
+        let source = "class Main {
+            function void main() {
+                var int x; var boolean b; var Array a; var Ball ball; var String s;
+                let x = true;
+                let a = 8000;
+                let x = a + 1;
+                if (x) { let b = x; }
+                let ball = Ball.new();
+                let s = ball;
+                let x = ball + 1;
+                do Output.printString(ball);
+                let x = Main.nothing();
+                do Output.printInt(1, 2);
+                do Memory.deAlloc(ball);
+                do Ball.move();
+                do ball.new();
+                return 1;
+            }
+            function void nothing() { return; }
+            function int f() { return; }
+        }";
+        assert_eq!(
+            check(source, Strictness::Lenient),
+            [
+                "Main.jack, line 9: Cannot assign Ball to 's' of type String.",
+                "Main.jack, line 10: Cannot apply '+' to Ball.",
+                "Main.jack, line 11: Argument 1 of 'Output.printString' must be String, not Ball.",
+                "Main.jack, line 12: 'Main.nothing' returns nothing, so it cannot be used as a value.",
+                "Main.jack, line 13: 'Output.printInt' takes 1 argument(s), but 2 were given.",
+                "Main.jack, line 15: 'Ball.move' is a method, so it must be called on an object.",
+                "Main.jack, line 16: 'Ball.new' is not a method, so it must be called on its class.",
+                "Main.jack, line 17: 'main' is void, so it cannot return a value.",
+                "Main.jack, line 20: 'f' must return a value of type int.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_strict() {
+        let source = "class Main {
+            function void main() {
+                var int x; var char c; var boolean b; var Array a; var Ball ball;
+                let c = x + 1;
+                let x = true;
+                if (x) {}
+                while (b & (x < 3)) {}
+                let x = x & b;
+                let a = 8000;
+                let x = a + 1;
+                let ball = a;
+                let ball = null;
+                let x[0] = 1;
+                let a[b] = c;
+                if (ball = null) {}
+                if (x = b) {}
+                do x.foo();
+                do Memory.deAlloc(ball);
+                return;
+            }
+        }";
+        assert_eq!(
+            check(source, Strictness::Strict),
+            [
+                "Main.jack, line 5: Cannot assign boolean to 'x' of type int.",
+                "Main.jack, line 6: The condition must be a boolean, not int.",
+                "Main.jack, line 8: Cannot apply '&' to int and boolean.",
+                "Main.jack, line 9: Cannot assign int to 'a' of type Array.",
+                "Main.jack, line 10: Cannot apply '+' to Array.",
+                "Main.jack, line 11: Cannot assign Array to 'ball' of type Ball.",
+                "Main.jack, line 13: 'x' of type int is not an Array.",
+                "Main.jack, line 14: Cannot apply '[]' to boolean.",
+                "Main.jack, line 16: Cannot compare int with boolean.",
+                "Main.jack, line 17: Cannot call method 'foo' on 'x' of type int.",
+            ]
+        );
+        // only calling a method on an int breaks the official rules
+        assert_eq!(
+            check(source, Strictness::Lenient),
+            ["Main.jack, line 17: Cannot call method 'foo' on 'x' of type int."]
+        );
+    }
+
+    #[test]
+    fn test_sample_programs() {
+        // the programs of project 11 follow the official rules
+        for program in ["Average", "ComplexArrays", "ConvertToBin", "Pong", "Seven", "Square"] {
+            let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../projects/11").join(program);
+            let classes = std::fs::read_dir(dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "jack"))
+                .map(|path| Parser::new(&std::fs::read_to_string(path).unwrap()).parse())
+                .collect::<Vec<_>>();
+            assert_eq!(check_types(&classes, &[], Strictness::Lenient), Vec::<String>::new(), "{program}");
+        }
+    }
+}
@@ -0,0 +1,586 @@
+//! Warnings about code that compiles, but is likely a mistake.
+//!
+//! Most warnings are found while parsing or generating code. Assignments whose value is never
+//! read need a liveness analysis of their own, see `unused_assignments`.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+
+use crate::ast::expr::Expr;
+use crate::ast::expr_term::ExprTerm;
+use crate::ast::statement::Statement;
+use crate::ast::subroutine_call::SubroutineCall;
+use crate::ast::subroutine_dec::SubroutineDec;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Lint {
+    /// A local variable, parameter or static variable that is never read.
+    UnusedVariable,
+    /// An assignment to a local variable or parameter whose value is never read.
+    UnusedAssignment,
+    /// Statements after `return`, `break` or `continue`.
+    UnreachableCode,
+    /// `while (false)`, whose body never runs.
+    DeadLoop,
+    /// A subroutine that no class of the project calls.
+    UnusedSubroutine,
+    /// An expression that conventional operator precedence would group differently.
+    Precedence,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 6] = [
+        Lint::UnusedVariable,
+        Lint::UnusedAssignment,
+        Lint::UnreachableCode,
+        Lint::DeadLoop,
+        Lint::UnusedSubroutine,
+        Lint::Precedence,
+    ];
+
+    /// The name used in `allow(...)` pragmas and on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::UnusedVariable => "unused-variable",
+            Lint::UnusedAssignment => "unused-assignment",
+            Lint::UnreachableCode => "unreachable-code",
+            Lint::DeadLoop => "dead-loop",
+            Lint::UnusedSubroutine => "unused-subroutine",
+            Lint::Precedence => "precedence",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Lint> {
+        Lint::ALL.into_iter().find(|lint| lint.name() == name)
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub lint: Lint,
+    /// Line of the source file, starting at 1.
+    pub line: usize,
+    pub message: String,
+}
+
+impl Warning {
+    pub fn new(lint: Lint, line: usize, message: String) -> Self {
+        Warning { lint, line, message }
+    }
+}
+
+/// Lints allowed by `// allow(lint, ...)` comments, which hold nothing but the pragma. A
+/// comment allows the lints on its own line and on the next one, so it can either follow the
+/// code or precede it.
+#[derive(Debug, Default)]
+pub struct Pragmas {
+    allowed: HashMap<usize, Vec<Lint>>,
+}
+
+impl Pragmas {
+    /// Finds the pragmas of a source file, along with an error message for each unknown
+    /// lint name.
+    pub fn parse(source: &str) -> (Self, Vec<String>) {
+        let mut allowed: HashMap<usize, Vec<Lint>> = HashMap::new();
+        let mut unknown = Vec::new();
+
+        for (i, text) in source.lines().enumerate() {
+            let Some(comment) = text.find("//").or_else(|| text.find("/*")) else {
+                continue;
+            };
+            // only a comment made of the pragma alone is one, not a mention of it in prose
+            let comment = text[comment + 2..].trim_end();
+            let comment = comment.strip_suffix("*/").unwrap_or(comment).trim();
+            let Some(names) = comment.strip_prefix("allow(").and_then(|c| c.strip_suffix(')')) else {
+                continue;
+            };
+
+            let line = i + 1;
+            for name in names.split(',') {
+                let name = name.trim();
+                match Lint::from_name(name) {
+                    Some(lint) => {
+                        allowed.entry(line).or_default().push(lint);
+                        allowed.entry(line + 1).or_default().push(lint);
+                    }
+                    None => unknown.push(format!("line {line}: unknown lint '{name}' in allow(...)")),
+                }
+            }
+        }
+
+        (Pragmas { allowed }, unknown)
+    }
+
+    pub fn allows(&self, lint: Lint, line: usize) -> bool {
+        self.allowed
+            .get(&line)
+            .is_some_and(|lints| lints.contains(&lint))
+    }
+}
+
+/// Finds the assignments of a subroutine to its local variables and parameters whose value
+/// is never read, whatever path execution takes afterwards. Variables that are never read
+/// at all are left out, since `Lint::UnusedVariable` already reports them.
+pub fn unused_assignments(subroutine: &SubroutineDec) -> Vec<Warning> {
+    let mut tracked = subroutine
+        .parameters
+        .iter()
+        .map(|(_, name)| name.as_str())
+        .collect::<HashSet<&str>>();
+    tracked.extend(
+        subroutine
+            .body
+            .locals
+            .iter()
+            .flat_map(|dec| dec.vars.iter().map(String::as_str)),
+    );
+
+    let mut read = BTreeSet::new();
+    subroutine
+        .body
+        .statements
+        .iter()
+        .for_each(|s| statement_reads(s, &mut read));
+    tracked.retain(|name| read.contains(name));
+
+    let mut liveness = Liveness {
+        tracked,
+        report: true,
+        warnings: Vec::new(),
+    };
+    let exit = Jumps {
+        on_break: Live::new(),
+        on_continue: Live::new(),
+    };
+    liveness.block(&subroutine.body.statements, Live::new(), &exit);
+
+    let mut warnings = liveness.warnings;
+    warnings.sort_by_key(|w| w.line);
+    warnings.dedup();
+    warnings
+}
+
+// variables whose current value may still be read
+type Live<'a> = BTreeSet<&'a str>;
+
+// what is live where `break` and `continue` jump to
+struct Jumps<'a> {
+    on_break: Live<'a>,
+    on_continue: Live<'a>,
+}
+
+struct Liveness<'a> {
+    tracked: HashSet<&'a str>,
+    // loops are analysed repeatedly until nothing changes, and only report on the last pass
+    report: bool,
+    warnings: Vec<Warning>,
+}
+
+impl<'a> Liveness<'a> {
+    // statements are analysed backwards, from what is live after them
+    fn block(&mut self, statements: &'a [Statement], live: Live<'a>, jumps: &Jumps<'a>) -> Live<'a> {
+        statements
+            .iter()
+            .rev()
+            .fold(live, |live, statement| self.statement(statement, live, jumps))
+    }
+
+    fn statement(&mut self, statement: &'a Statement, mut live: Live<'a>, jumps: &Jumps<'a>) -> Live<'a> {
+        match statement {
+            Statement::Let {
+                name,
+                access: None,
+                expr,
+                line,
+                ..
+            } => {
+                if self.tracked.contains(name.as_str()) {
+                    if self.report && !live.contains(name.as_str()) {
+                        self.warnings.push(Warning::new(
+                            Lint::UnusedAssignment,
+                            *line,
+                            format!("The value assigned to '{name}' is never read."),
+                        ));
+                    }
+                    live.remove(name.as_str());
+                }
+                expr_reads(expr, &mut live);
+                live
+            }
+            Statement::Let {
+                name,
+                access: Some(index),
+                expr,
+                ..
+            } => {
+                live.insert(name);
+                expr_reads(index, &mut live);
+                expr_reads(expr, &mut live);
+                live
+            }
+            Statement::If {
+                condition,
+                if_true,
+                if_false,
+            } => {
+                let mut merged = self.block(if_true, live.clone(), jumps);
+                match if_false {
+                    Some(if_false) => merged.extend(self.block(if_false, live, jumps)),
+                    None => merged.extend(live),
+                }
+                expr_reads(condition, &mut merged);
+                merged
+            }
+            Statement::While {
+                condition,
+                statements,
+            } => self.fixpoint(|this, head| {
+                let jumps = Jumps {
+                    on_break: live.clone(),
+                    on_continue: head.clone(),
+                };
+                let mut entry = this.block(statements, head, &jumps);
+                entry.extend(live.iter());
+                expr_reads(condition, &mut entry);
+                entry
+            }),
+            Statement::For {
+                init,
+                condition,
+                step,
+                statements,
+            } => {
+                let head = self.fixpoint(|this, head| {
+                    let after_body = match step {
+                        Some(step) => this.statement(step, head, jumps),
+                        None => head,
+                    };
+                    let jumps = Jumps {
+                        on_break: live.clone(),
+                        on_continue: after_body.clone(),
+                    };
+                    let mut entry = this.block(statements, after_body, &jumps);
+                    // without a condition, the loop is only left by `break`
+                    if let Some(condition) = condition {
+                        entry.extend(live.iter());
+                        expr_reads(condition, &mut entry);
+                    }
+                    entry
+                });
+                match init {
+                    Some(init) => self.statement(init, head, jumps),
+                    None => head,
+                }
+            }
+            Statement::Switch {
+                selector,
+                cases,
+                default,
+            } => {
+                let inner = Jumps {
+                    on_break: live.clone(),
+                    on_continue: jumps.on_continue.clone(),
+                };
+                let mut merged = match default {
+                    Some(default) => self.block(default, live.clone(), &inner),
+                    None => live.clone(),
+                };
+                for (_, statements) in cases {
+                    merged.extend(self.block(statements, live.clone(), &inner));
+                }
+                expr_reads(selector, &mut merged);
+                merged
+            }
+            Statement::Break => jumps.on_break.clone(),
+            Statement::Continue => jumps.on_continue.clone(),
+            Statement::Do { call, .. } => {
+                call_reads(call, &mut live);
+                live
+            }
+            Statement::Return { value, .. } => {
+                let mut live = Live::new();
+                if let Some(value) = value {
+                    expr_reads(value, &mut live);
+                }
+                live
+            }
+        }
+    }
+
+    // the set live at the head of a loop, where `entry` computes it from a guess
+    fn fixpoint(&mut self, mut entry: impl FnMut(&mut Self, Live<'a>) -> Live<'a>) -> Live<'a> {
+        let report = std::mem::replace(&mut self.report, false);
+        let mut head = Live::new();
+        loop {
+            let next = entry(self, head.clone());
+            if next == head {
+                break;
+            }
+            head = next;
+        }
+        self.report = report;
+        entry(self, head)
+    }
+}
+
+fn statement_reads<'a>(statement: &'a Statement, read: &mut Live<'a>) {
+    let block = |statements: &'a [Statement], read: &mut Live<'a>| {
+        statements.iter().for_each(|s| statement_reads(s, read))
+    };
+    match statement {
+        Statement::Let {
+            name, access, expr, ..
+        } => {
+            if let Some(index) = access {
+                read.insert(name);
+                expr_reads(index, read);
+            }
+            expr_reads(expr, read);
+        }
+        Statement::If {
+            condition,
+            if_true,
+            if_false,
+        } => {
+            expr_reads(condition, read);
+            block(if_true, read);
+            if let Some(if_false) = if_false {
+                block(if_false, read);
+            }
+        }
+        Statement::While {
+            condition,
+            statements,
+        } => {
+            expr_reads(condition, read);
+            block(statements, read);
+        }
+        Statement::For {
+            init,
+            condition,
+            step,
+            statements,
+        } => {
+            init.iter().chain(step).for_each(|s| statement_reads(s, read));
+            condition.iter().for_each(|c| expr_reads(c, read));
+            block(statements, read);
+        }
+        Statement::Switch {
+            selector,
+            cases,
+            default,
+        } => {
+            expr_reads(selector, read);
+            cases.iter().for_each(|(_, statements)| block(statements, read));
+            default.iter().for_each(|statements| block(statements, read));
+        }
+        Statement::Do { call, .. } => call_reads(call, read),
+        Statement::Return { value: Some(value), .. } => expr_reads(value, read),
+        Statement::Return { value: None, .. } | Statement::Break | Statement::Continue => {}
+    }
+}
+
+fn expr_reads<'a>(expr: &'a Expr, read: &mut Live<'a>) {
+    term_reads(&expr.lhs, read);
+    expr.rhs.iter().for_each(|(_, term)| term_reads(term, read));
+}
+
+fn term_reads<'a>(term: &'a ExprTerm, read: &mut Live<'a>) {
+    match term {
+        ExprTerm::Variable(name) => {
+            read.insert(name);
+        }
+        ExprTerm::Access(name, index) => {
+            read.insert(name);
+            expr_reads(index, read);
+        }
+        ExprTerm::Call(call) => call_reads(call, read),
+        ExprTerm::Group(expr) => expr_reads(expr, read),
+        ExprTerm::Unary(_, term) => term_reads(term, read),
+        ExprTerm::Binary(_, lhs, rhs) => {
+            term_reads(lhs, read);
+            term_reads(rhs, read);
+        }
+        ExprTerm::Integer(_)
+        | ExprTerm::Str(_)
+        | ExprTerm::True
+        | ExprTerm::False
+        | ExprTerm::Null
+        | ExprTerm::This => {}
+    }
+}
+
+fn call_reads<'a>(call: &'a SubroutineCall, read: &mut Live<'a>) {
+    let args = match call {
+        SubroutineCall::Function(_, args) => args,
+        // the receiver of a method call is read, when it is a variable and not a class
+        SubroutineCall::Method(receiver, _, args) => {
+            read.insert(receiver);
+            args
+        }
+    };
+    args.iter().for_each(|arg| expr_reads(arg, read));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{unused_assignments, Lint, Pragmas};
+    use crate::batch::compile_class;
+    use crate::options::Options;
+    use crate::parser::Parser;
+
+    fn unused(subroutine: &str) -> Vec<usize> {
+        let options = Options {
+            extensions: true,
+            ..Default::default()
+        };
+        let class = Parser::with_options(&format!("class C {{\n{subroutine}\n}}"), &options).parse();
+        unused_assignments(&class.subroutines[0])
+            .into_iter()
+            .map(|w| {
+                assert_eq!(w.lint, Lint::UnusedAssignment);
+                // line of the subroutine source
+                w.line - 1
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_straight_line() {
+        let source = "function int f(int a) {
+            var int x;
+            let x = 1;
+            let x = a;
+            let a = 2;
+            return x;
+        }";
+        assert_eq!(unused(source), vec![3, 5]);
+    }
+
+    #[test]
+    fn test_branches() {
+        let source = "function int f(boolean c) {
+            var int x;
+            let x = 1;
+            if (c) { let x = 2; }
+            return x;
+        }";
+        assert_eq!(unused(source), Vec::<usize>::new());
+
+        let source = "function int f(boolean c) {
+            var int x;
+            let x = 1;
+            if (c) { let x = 2; } else { let x = 3; }
+            return x;
+        }";
+        assert_eq!(unused(source), vec![3]);
+    }
+
+    #[test]
+    fn test_loops() {
+        let source = "function int f(int n) {
+            var int i, sum, x;
+            let i = 0;
+            while (i < n) {
+                let x = i;
+                let sum = sum + x;
+                let x = 0;
+                let i = i + 1;
+            }
+            return sum;
+        }";
+        assert_eq!(unused(source), vec![7]);
+
+        let source = "function int f(int n) {
+            var int i, found;
+            let found = -1;
+            for (i = 0; i < n; i += 1) {
+                if (i = 5) { let found = i; break; }
+                let found = 0;
+                continue;
+            }
+            return found;
+        }";
+        assert_eq!(unused(source), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_array_and_method() {
+        let source = "function void f(Array a, List l) {
+            let a = Array.new(2);
+            let a[0] = 1;
+            let l = List.new(0, null);
+            do l.dispose();
+            let l = null;
+            return;
+        }";
+        assert_eq!(unused(source), vec![6]);
+    }
+
+    #[test]
+    fn test_pragmas() {
+        let source = "let x = 1; // allow(unused-assignment, dead-loop)\n\n// allow(precedence)\nlet y = a + b * c;\n// allow(bogus)\n// see allow(foo) in the docs\n/* allow(dead-loop) */";
+        let (pragmas, unknown) = Pragmas::parse(source);
+        assert!(pragmas.allows(Lint::UnusedAssignment, 1));
+        assert!(pragmas.allows(Lint::DeadLoop, 2));
+        assert!(!pragmas.allows(Lint::UnusedVariable, 1));
+        assert!(!pragmas.allows(Lint::Precedence, 2));
+        assert!(pragmas.allows(Lint::Precedence, 4));
+        assert!(pragmas.allows(Lint::DeadLoop, 7));
+        // a comment that only mentions a pragma is not one
+        assert_eq!(unknown, vec!["line 5: unknown lint 'bogus' in allow(...)"]);
+    }
+
+    #[test]
+    fn test_warnings() {
+        let source = "class Main {
+            static int count, unused;
+            function void main() {
+                var int x, y;
+                let x = 1;
+                let x = 2;
+                do Main.helper(x);
+                while (false) { let count = 3; }
+                return;
+            }
+            method void helper(int a) {
+                var int b; // allow(unused-variable)
+                return;
+            }
+            // allow(unused-subroutine, unused-variable)
+            function void spare(int a) { return; }
+        }";
+        let compiled = compile_class(source, &Options::default());
+        let found = compiled
+            .warnings
+            .iter()
+            .map(|w| (w.line, w.lint, w.message.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![
+                (2, Lint::UnusedVariable, "Static variable 'count' is never read."),
+                (2, Lint::UnusedVariable, "Static variable 'unused' is never read."),
+                (4, Lint::UnusedVariable, "Local variable 'y' is never read."),
+                (5, Lint::UnusedAssignment, "The value assigned to 'x' is never read."),
+                (8, Lint::DeadLoop, "The condition of this while loop is always false, so its body is never run."),
+                (11, Lint::UnusedVariable, "Parameter 'a' is never read."),
+            ]
+        );
+        assert_eq!(compiled.subroutine_lines.keys().collect::<Vec<_>>(), ["helper", "main"]);
+        assert_eq!(compiled.subroutine_lines["helper"], 11);
+    }
+
+    #[test]
+    #[should_panic(expected = "line 1: unknown lint 'unused' in allow(...)")]
+    fn test_unknown_lint() {
+        compile_class("class Main { // allow(unused)
+}", &Options::default());
+    }
+}
@@ -20,6 +20,10 @@ pub fn print_symbol(file: &mut File, content: &str) {
     writeln!(file, "<symbol> {content} </symbol>").unwrap();
 }
 
+pub fn escape(content: &str) -> String {
+    content.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
 impl XmlPrinter for char {
     fn print_xml(&self, file: &mut File) {
         match self {
@@ -30,6 +34,10 @@ impl XmlPrinter for char {
             '<' => writeln!(file, "<symbol> &lt; </symbol>").unwrap(),
             '>' => writeln!(file, "<symbol> &gt; </symbol>").unwrap(),
             '&' => writeln!(file, "<symbol> &amp; </symbol>").unwrap(),
+            // the comparisons of extended Jack
+            '≠' => writeln!(file, "<symbol> != </symbol>").unwrap(),
+            '≤' => writeln!(file, "<symbol> &lt;= </symbol>").unwrap(),
+            '≥' => writeln!(file, "<symbol> &gt;= </symbol>").unwrap(),
             _ => panic!("{} is not a valid operator!", self),
         }
     }
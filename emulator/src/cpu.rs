@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+
+use crate::vm::{memory_hash, word_hash};
+
+pub const ROM_SIZE: usize = 0x8000;
+pub const RAM_SIZE: usize = 0x8000;
+pub const SCREEN: u16 = 0x4000;
+pub const KBD: u16 = 0x6000;
+
+/// The Hack CPU together with its instruction and data memory.
+pub struct Cpu {
+    pub rom: Vec<u16>,
+    pub ram: Vec<u16>,
+    pub a: u16,
+    pub d: u16,
+    pub pc: u16,
+    /// Number of instructions executed since the program was loaded.
+    pub cycles: u64,
+}
+
+impl Default for Cpu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Cpu {
+    pub fn new() -> Self {
+        Cpu {
+            rom: vec![0; ROM_SIZE],
+            ram: vec![0; RAM_SIZE],
+            a: 0,
+            d: 0,
+            pc: 0,
+            cycles: 0,
+        }
+    }
+
+    /// Loads a program into ROM and resets the CPU. RAM is cleared as well.
+    pub fn load(&mut self, program: &[u16]) {
+        assert!(program.len() <= ROM_SIZE, "Program does not fit into ROM.");
+
+        self.rom.iter_mut().for_each(|word| *word = 0);
+        self.rom[..program.len()].copy_from_slice(program);
+        self.ram.iter_mut().for_each(|word| *word = 0);
+        self.a = 0;
+        self.d = 0;
+        self.pc = 0;
+        self.cycles = 0;
+    }
+
+    /// Parses the text of a `.hack` file into machine words.
+    pub fn parse_hack(source: &str) -> Result<Vec<u16>, String> {
+        source
+            .lines()
+            .map(str::trim)
+            .enumerate()
+            .filter(|(_, line)| !line.is_empty())
+            .map(|(i, line)| {
+                if line.len() != 16 {
+                    return Err(format!("line {}: '{}' is not a 16-bit word", i + 1, line));
+                }
+                u16::from_str_radix(line, 2)
+                    .map_err(|_| format!("line {}: '{}' is not a 16-bit word", i + 1, line))
+            })
+            .collect()
+    }
+
+    /// Executes a single instruction.
+    pub fn step(&mut self) {
+        let instruction = self.rom[self.pc as usize];
+        self.cycles += 1;
+
+        if instruction & 0x8000 == 0 {
+            self.a = instruction;
+            self.pc = self.pc.wrapping_add(1) & 0x7fff;
+            return;
+        }
+
+        let y = if instruction & 0x1000 != 0 {
+            self.ram[(self.a & 0x7fff) as usize]
+        } else {
+            self.a
+        };
+        let out = alu(self.d, y, (instruction >> 6) as u8 & 0x3f);
+
+        if instruction & 0x08 != 0 {
+            self.ram[(self.a & 0x7fff) as usize] = out;
+        }
+        let jump_target = self.a;
+        if instruction & 0x20 != 0 {
+            self.a = out;
+        }
+        if instruction & 0x10 != 0 {
+            self.d = out;
+        }
+
+        let out = out as i16;
+        let jump = (instruction & 0x04 != 0 && out < 0)
+            || (instruction & 0x02 != 0 && out == 0)
+            || (instruction & 0x01 != 0 && out > 0);
+        self.pc = if jump {
+            jump_target & 0x7fff
+        } else {
+            self.pc.wrapping_add(1) & 0x7fff
+        };
+    }
+
+    /// Runs at most `limit` instructions, stopping early once the program is idle, i.e. it
+    /// jumped back to a loop in the exact same state as before.
+    ///
+    /// Returns `true` if the program went idle before the limit was reached.
+    pub fn run_until_idle(&mut self, limit: u64) -> bool {
+        // jump target -> (A, D, memory hash) seen the last time the jump was taken
+        let mut visits: HashMap<u16, (u16, u16, u64)> = HashMap::new();
+        let mut hash = memory_hash(&self.ram);
+
+        for _ in 0..limit {
+            let pc = self.pc;
+            let address = (self.a & 0x7fff) as usize;
+            let before = self.ram[address];
+
+            self.step();
+
+            hash = hash
+                .wrapping_sub(word_hash(address, before))
+                .wrapping_add(word_hash(address, self.ram[address]));
+            if self.pc <= pc
+                && visits.insert(self.pc, (self.a, self.d, hash)) == Some((self.a, self.d, hash))
+            {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+/// Computes the Hack ALU function selected by the `zx nx zy ny f no` bits.
+pub fn alu(x: u16, y: u16, bits: u8) -> u16 {
+    let x = if bits & 0x20 != 0 { 0 } else { x };
+    let x = if bits & 0x10 != 0 { !x } else { x };
+    let y = if bits & 0x08 != 0 { 0 } else { y };
+    let y = if bits & 0x04 != 0 { !y } else { y };
+    let out = if bits & 0x02 != 0 {
+        x.wrapping_add(y)
+    } else {
+        x & y
+    };
+
+    if bits & 0x01 != 0 {
+        !out
+    } else {
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add() {
+        // RAM[0] = 2 + 3
+        let program = Cpu::parse_hack(
+            "0000000000000010\n\
+             1110110000010000\n\
+             0000000000000011\n\
+             1110000010010000\n\
+             0000000000000000\n\
+             1110001100001000\n",
+        )
+        .unwrap();
+
+        let mut cpu = Cpu::new();
+        cpu.load(&program);
+        for _ in 0..program.len() {
+            cpu.step();
+        }
+
+        assert_eq!(cpu.ram[0], 5);
+        assert_eq!(cpu.pc, 6);
+    }
+
+    #[test]
+    fn test_run_until_idle() {
+        // (LOOP) @LOOP; 0;JMP
+        let program = Cpu::parse_hack("0000000000000000\n1110101010000111\n").unwrap();
+
+        let mut cpu = Cpu::new();
+        cpu.load(&program);
+
+        assert!(cpu.run_until_idle(100));
+        assert!(cpu.cycles < 10);
+    }
+
+    #[test]
+    fn test_alu() {
+        assert_eq!(alu(5, 3, 0b000010), 8); // D+A
+        assert_eq!(alu(5, 3, 0b010011), 2); // D-A
+        assert_eq!(alu(5, 3, 0b000111), 0xfffe); // A-D
+        assert_eq!(alu(5, 3, 0b111111), 1); // 1
+        assert_eq!(alu(5, 3, 0b111010), 0xffff); // -1
+    }
+}
@@ -0,0 +1,468 @@
+use super::parser::PinDecl;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Nand,
+    Not,
+    And,
+    Or,
+    Xor,
+    Mux,
+    DMux,
+    Not16,
+    And16,
+    Or16,
+    Mux16,
+    Or8Way,
+    Mux4Way16,
+    Mux8Way16,
+    DMux4Way,
+    DMux8Way,
+    HalfAdder,
+    FullAdder,
+    Add16,
+    Inc16,
+    Alu,
+    Dff,
+    Bit,
+    Register,
+    Pc,
+    Ram,
+    Rom32K,
+    Keyboard,
+}
+
+type Pins = &'static [(&'static str, u8)];
+
+const SEL_8: Pins = &[
+    ("a", 16),
+    ("b", 16),
+    ("c", 16),
+    ("d", 16),
+    ("e", 16),
+    ("f", 16),
+    ("g", 16),
+    ("h", 16),
+    ("sel", 3),
+];
+const ALU_IN: Pins = &[
+    ("x", 16),
+    ("y", 16),
+    ("zx", 1),
+    ("nx", 1),
+    ("zy", 1),
+    ("ny", 1),
+    ("f", 1),
+    ("no", 1),
+];
+const WAY_8: Pins = &[
+    ("a", 1),
+    ("b", 1),
+    ("c", 1),
+    ("d", 1),
+    ("e", 1),
+    ("f", 1),
+    ("g", 1),
+    ("h", 1),
+];
+
+// name, implementation, inputs, outputs, and the number of address bits of memory chips
+const BUILTINS: &[(&str, Kind, Pins, Pins, u8)] = &[
+    ("Nand", Kind::Nand, &[("a", 1), ("b", 1)], &[("out", 1)], 0),
+    ("Not", Kind::Not, &[("in", 1)], &[("out", 1)], 0),
+    ("And", Kind::And, &[("a", 1), ("b", 1)], &[("out", 1)], 0),
+    ("Or", Kind::Or, &[("a", 1), ("b", 1)], &[("out", 1)], 0),
+    ("Xor", Kind::Xor, &[("a", 1), ("b", 1)], &[("out", 1)], 0),
+    (
+        "Mux",
+        Kind::Mux,
+        &[("a", 1), ("b", 1), ("sel", 1)],
+        &[("out", 1)],
+        0,
+    ),
+    (
+        "DMux",
+        Kind::DMux,
+        &[("in", 1), ("sel", 1)],
+        &[("a", 1), ("b", 1)],
+        0,
+    ),
+    ("Not16", Kind::Not16, &[("in", 16)], &[("out", 16)], 0),
+    (
+        "And16",
+        Kind::And16,
+        &[("a", 16), ("b", 16)],
+        &[("out", 16)],
+        0,
+    ),
+    (
+        "Or16",
+        Kind::Or16,
+        &[("a", 16), ("b", 16)],
+        &[("out", 16)],
+        0,
+    ),
+    (
+        "Mux16",
+        Kind::Mux16,
+        &[("a", 16), ("b", 16), ("sel", 1)],
+        &[("out", 16)],
+        0,
+    ),
+    ("Or8Way", Kind::Or8Way, &[("in", 8)], &[("out", 1)], 0),
+    (
+        "Mux4Way16",
+        Kind::Mux4Way16,
+        &[("a", 16), ("b", 16), ("c", 16), ("d", 16), ("sel", 2)],
+        &[("out", 16)],
+        0,
+    ),
+    ("Mux8Way16", Kind::Mux8Way16, SEL_8, &[("out", 16)], 0),
+    (
+        "DMux4Way",
+        Kind::DMux4Way,
+        &[("in", 1), ("sel", 2)],
+        &[("a", 1), ("b", 1), ("c", 1), ("d", 1)],
+        0,
+    ),
+    (
+        "DMux8Way",
+        Kind::DMux8Way,
+        &[("in", 1), ("sel", 3)],
+        WAY_8,
+        0,
+    ),
+    (
+        "HalfAdder",
+        Kind::HalfAdder,
+        &[("a", 1), ("b", 1)],
+        &[("sum", 1), ("carry", 1)],
+        0,
+    ),
+    (
+        "FullAdder",
+        Kind::FullAdder,
+        &[("a", 1), ("b", 1), ("c", 1)],
+        &[("sum", 1), ("carry", 1)],
+        0,
+    ),
+    (
+        "Add16",
+        Kind::Add16,
+        &[("a", 16), ("b", 16)],
+        &[("out", 16)],
+        0,
+    ),
+    ("Inc16", Kind::Inc16, &[("in", 16)], &[("out", 16)], 0),
+    (
+        "ALU",
+        Kind::Alu,
+        ALU_IN,
+        &[("out", 16), ("zr", 1), ("ng", 1)],
+        0,
+    ),
+    ("DFF", Kind::Dff, &[("in", 1)], &[("out", 1)], 0),
+    (
+        "Bit",
+        Kind::Bit,
+        &[("in", 1), ("load", 1)],
+        &[("out", 1)],
+        0,
+    ),
+    (
+        "Register",
+        Kind::Register,
+        &[("in", 16), ("load", 1)],
+        &[("out", 16)],
+        0,
+    ),
+    (
+        "ARegister",
+        Kind::Register,
+        &[("in", 16), ("load", 1)],
+        &[("out", 16)],
+        0,
+    ),
+    (
+        "DRegister",
+        Kind::Register,
+        &[("in", 16), ("load", 1)],
+        &[("out", 16)],
+        0,
+    ),
+    (
+        "PC",
+        Kind::Pc,
+        &[("in", 16), ("load", 1), ("inc", 1), ("reset", 1)],
+        &[("out", 16)],
+        0,
+    ),
+    (
+        "RAM8",
+        Kind::Ram,
+        &[("in", 16), ("load", 1), ("address", 3)],
+        &[("out", 16)],
+        3,
+    ),
+    (
+        "RAM64",
+        Kind::Ram,
+        &[("in", 16), ("load", 1), ("address", 6)],
+        &[("out", 16)],
+        6,
+    ),
+    (
+        "RAM512",
+        Kind::Ram,
+        &[("in", 16), ("load", 1), ("address", 9)],
+        &[("out", 16)],
+        9,
+    ),
+    (
+        "RAM4K",
+        Kind::Ram,
+        &[("in", 16), ("load", 1), ("address", 12)],
+        &[("out", 16)],
+        12,
+    ),
+    (
+        "RAM16K",
+        Kind::Ram,
+        &[("in", 16), ("load", 1), ("address", 14)],
+        &[("out", 16)],
+        14,
+    ),
+    (
+        "Screen",
+        Kind::Ram,
+        &[("in", 16), ("load", 1), ("address", 13)],
+        &[("out", 16)],
+        13,
+    ),
+    (
+        "ROM32K",
+        Kind::Rom32K,
+        &[("address", 15)],
+        &[("out", 16)],
+        15,
+    ),
+    ("Keyboard", Kind::Keyboard, &[], &[("out", 16)], 0),
+];
+
+/// A chip implemented natively rather than from HDL.
+pub struct Builtin {
+    name: &'static str,
+    kind: Kind,
+    inputs: Vec<PinDecl>,
+    outputs: Vec<PinDecl>,
+    /// Registers or memory words of sequential chips.
+    state: Vec<u16>,
+    // output of a register. the register itself already holds the new value after a tick,
+    // its output only follows on the tock.
+    latch: u16,
+    // memory write committed by the next tock: (address, value)
+    pending: Option<(usize, u16)>,
+}
+
+impl Builtin {
+    /// Creates the built-in implementation of the named chip, if there is one.
+    pub fn new(name: &str) -> Option<Self> {
+        let &(name, kind, inputs, outputs, address_bits) =
+            BUILTINS.iter().find(|builtin| builtin.0 == name)?;
+        let decls = |pins: Pins| {
+            pins.iter()
+                .map(|&(name, width)| PinDecl {
+                    name: name.to_string(),
+                    width,
+                })
+                .collect()
+        };
+        let state_size = match kind {
+            Kind::Dff | Kind::Bit | Kind::Register | Kind::Pc | Kind::Keyboard => 1,
+            Kind::Ram | Kind::Rom32K => 1 << address_bits,
+            _ => 0,
+        };
+
+        Some(Builtin {
+            name,
+            kind,
+            inputs: decls(inputs),
+            outputs: decls(outputs),
+            state: vec![0; state_size],
+            latch: 0,
+            pending: None,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        self.name
+    }
+
+    pub fn inputs(&self) -> &[PinDecl] {
+        &self.inputs
+    }
+
+    pub fn outputs(&self) -> &[PinDecl] {
+        &self.outputs
+    }
+
+    /// Returns `true` if the chip reacts to the clock.
+    pub fn is_clocked(&self) -> bool {
+        matches!(
+            self.kind,
+            Kind::Dff | Kind::Bit | Kind::Register | Kind::Pc | Kind::Ram
+        )
+    }
+
+    /// The register or memory word at `index`, as accessed by `Chip[index]` in test scripts.
+    pub fn state(&self, index: usize) -> Option<u16> {
+        self.state.get(index).copied()
+    }
+
+    pub fn set_state(&mut self, index: usize, value: u16) -> bool {
+        match self.state.get_mut(index) {
+            Some(word) => {
+                *word = value;
+                if self.kind != Kind::Ram {
+                    self.latch = value;
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replaces the memory contents, starting from address 0.
+    pub fn load(&mut self, words: &[u16]) -> bool {
+        if words.len() > self.state.len() {
+            return false;
+        }
+        self.state.iter_mut().for_each(|word| *word = 0);
+        self.state[..words.len()].copy_from_slice(words);
+        true
+    }
+
+    pub fn eval(&mut self, inputs: &[u16], outputs: &mut [u16]) {
+        let bit = |i: usize| inputs[i] & 1;
+        let select = |sel: u16| inputs[sel as usize];
+
+        match self.kind {
+            Kind::Nand => outputs[0] = !(inputs[0] & inputs[1]) & 1,
+            Kind::Not => outputs[0] = !inputs[0] & 1,
+            Kind::And => outputs[0] = inputs[0] & inputs[1],
+            Kind::Or => outputs[0] = inputs[0] | inputs[1],
+            Kind::Xor => outputs[0] = inputs[0] ^ inputs[1],
+            Kind::Mux | Kind::Mux16 => outputs[0] = select(bit(2)),
+            Kind::DMux => {
+                outputs[0] = inputs[0] * (1 - bit(1));
+                outputs[1] = inputs[0] * bit(1);
+            }
+            Kind::Not16 => outputs[0] = !inputs[0],
+            Kind::And16 => outputs[0] = inputs[0] & inputs[1],
+            Kind::Or16 => outputs[0] = inputs[0] | inputs[1],
+            Kind::Or8Way => outputs[0] = (inputs[0] != 0) as u16,
+            Kind::Mux4Way16 => outputs[0] = select(inputs[4] & 3),
+            Kind::Mux8Way16 => outputs[0] = select(inputs[8] & 7),
+            Kind::DMux4Way | Kind::DMux8Way => {
+                outputs.iter_mut().for_each(|out| *out = 0);
+                outputs[inputs[1] as usize] = inputs[0];
+            }
+            Kind::HalfAdder => {
+                outputs[0] = inputs[0] ^ inputs[1];
+                outputs[1] = inputs[0] & inputs[1];
+            }
+            Kind::FullAdder => {
+                let sum = inputs[0] + inputs[1] + inputs[2];
+                outputs[0] = sum & 1;
+                outputs[1] = sum >> 1;
+            }
+            Kind::Add16 => outputs[0] = inputs[0].wrapping_add(inputs[1]),
+            Kind::Inc16 => outputs[0] = inputs[0].wrapping_add(1),
+            Kind::Alu => {
+                let bits = inputs[2..]
+                    .iter()
+                    .fold(0, |bits, input| bits << 1 | (input & 1) as u8);
+                let out = crate::cpu::alu(inputs[0], inputs[1], bits);
+                outputs[0] = out;
+                outputs[1] = (out == 0) as u16;
+                outputs[2] = out >> 15;
+            }
+            Kind::Dff | Kind::Bit | Kind::Register | Kind::Pc => outputs[0] = self.latch,
+            Kind::Keyboard => outputs[0] = self.state[0],
+            Kind::Ram | Kind::Rom32K => {
+                let address = *inputs.last().unwrap() as usize;
+                outputs[0] = self.state[address];
+            }
+        }
+    }
+
+    /// Samples the inputs on the rising edge of the clock.
+    pub fn tick(&mut self, inputs: &[u16]) {
+        match self.kind {
+            Kind::Dff => self.state[0] = inputs[0],
+            Kind::Bit | Kind::Register if inputs[1] != 0 => self.state[0] = inputs[0],
+            Kind::Pc => {
+                self.state[0] = if inputs[3] != 0 {
+                    0
+                } else if inputs[1] != 0 {
+                    inputs[0]
+                } else if inputs[2] != 0 {
+                    self.latch.wrapping_add(1)
+                } else {
+                    self.latch
+                };
+            }
+            Kind::Ram if inputs[1] != 0 => self.pending = Some((inputs[2] as usize, inputs[0])),
+            _ => {}
+        }
+    }
+
+    /// Updates the outputs on the falling edge of the clock.
+    pub fn tock(&mut self) {
+        if let Some((address, value)) = self.pending.take() {
+            self.state[address] = value;
+        }
+        if self.kind != Kind::Ram && !self.state.is_empty() {
+            self.latch = self.state[0];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_combinational() {
+        let mut mux = Builtin::new("Mux4Way16").unwrap();
+        let mut out = [0];
+        mux.eval(&[1, 2, 3, 4, 2], &mut out);
+        assert_eq!(out, [3]);
+
+        let mut dmux = Builtin::new("DMux8Way").unwrap();
+        let mut out = [0; 8];
+        dmux.eval(&[1, 5], &mut out);
+        assert_eq!(out, [0, 0, 0, 0, 0, 1, 0, 0]);
+
+        let mut alu = Builtin::new("ALU").unwrap();
+        let mut out = [0; 3];
+        // x - y
+        alu.eval(&[3, 5, 0, 1, 0, 0, 1, 1], &mut out);
+        assert_eq!(out, [0xfffe, 0, 1]);
+    }
+
+    #[test]
+    fn test_sequential() {
+        let mut ram = Builtin::new("RAM8").unwrap();
+        let mut out = [0];
+
+        ram.tick(&[42, 1, 3]);
+        ram.eval(&[42, 1, 3], &mut out);
+        assert_eq!(out, [0]);
+
+        ram.tock();
+        ram.eval(&[42, 1, 3], &mut out);
+        assert_eq!(out, [42]);
+        assert_eq!(ram.state(3), Some(42));
+    }
+}
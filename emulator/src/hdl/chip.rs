@@ -0,0 +1,527 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use super::builtin::Builtin;
+use super::parser::{self, ChipDef, PinDecl, PinRef};
+
+/// A chip instance, either built-in or assembled from the parts of an HDL definition.
+pub enum Chip {
+    Builtin(Builtin),
+    Composite(Box<Composite>),
+}
+
+impl Chip {
+    pub fn name(&self) -> &str {
+        match self {
+            Chip::Builtin(chip) => chip.name(),
+            Chip::Composite(chip) => &chip.name,
+        }
+    }
+
+    pub fn inputs(&self) -> &[PinDecl] {
+        match self {
+            Chip::Builtin(chip) => chip.inputs(),
+            Chip::Composite(chip) => &chip.inputs,
+        }
+    }
+
+    pub fn outputs(&self) -> &[PinDecl] {
+        match self {
+            Chip::Builtin(chip) => chip.outputs(),
+            Chip::Composite(chip) => &chip.outputs,
+        }
+    }
+
+    /// Returns `true` if the chip, or any of its parts, reacts to the clock.
+    pub fn is_clocked(&self) -> bool {
+        match self {
+            Chip::Builtin(chip) => chip.is_clocked(),
+            Chip::Composite(chip) => chip.clocked,
+        }
+    }
+
+    /// Computes the outputs of the chip from its inputs and current state.
+    pub fn eval(&mut self, inputs: &[u16], outputs: &mut [u16]) {
+        match self {
+            Chip::Builtin(chip) => chip.eval(inputs, outputs),
+            Chip::Composite(chip) => chip.eval(inputs, outputs),
+        }
+    }
+
+    pub fn tick(&mut self, inputs: &[u16]) {
+        match self {
+            Chip::Builtin(chip) => chip.tick(inputs),
+            Chip::Composite(chip) => chip.tick(inputs),
+        }
+    }
+
+    pub fn tock(&mut self) {
+        match self {
+            Chip::Builtin(chip) => chip.tock(),
+            Chip::Composite(chip) => chip.tock(),
+        }
+    }
+
+    /// Finds the first built-in part named `name`, searching the parts depth-first.
+    pub fn find_builtin(&mut self, name: &str) -> Option<&mut Builtin> {
+        match self {
+            Chip::Builtin(chip) => Some(chip).filter(|chip| chip.name() == name),
+            Chip::Composite(chip) => chip
+                .parts
+                .iter_mut()
+                .find_map(|part| part.chip.find_builtin(name)),
+        }
+    }
+
+    /// Forgets every cached evaluation, e.g. after the state of a part was changed from outside.
+    pub fn invalidate(&mut self) {
+        if let Chip::Composite(chip) = self {
+            chip.stale = true;
+            for part in &mut chip.parts {
+                part.stale = true;
+                part.chip.invalidate();
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Source {
+    Constant(bool),
+    // wire index and its lowest connected bit
+    Wire(usize, u8),
+}
+
+// connects a range of bits of a part input to a source
+#[derive(Debug, Clone, Copy)]
+struct InputBinding {
+    pin: usize,
+    lo: u8,
+    hi: u8,
+    source: Source,
+}
+
+// connects a range of bits of a part output to a range of bits of a wire
+#[derive(Debug, Clone, Copy)]
+struct OutputBinding {
+    pin: usize,
+    lo: u8,
+    hi: u8,
+    wire: usize,
+    wire_lo: u8,
+}
+
+struct Part {
+    chip: Chip,
+    inputs: Vec<InputBinding>,
+    outputs: Vec<OutputBinding>,
+    // inputs of the last evaluation and the outputs it produced
+    last_inputs: Vec<u16>,
+    last_outputs: Vec<u16>,
+    stale: bool,
+}
+
+/// A chip made of parts. Its wires hold the inputs, then the outputs, then the internal pins.
+pub struct Composite {
+    name: String,
+    inputs: Vec<PinDecl>,
+    outputs: Vec<PinDecl>,
+    wires: Vec<u16>,
+    parts: Vec<Part>,
+    clocked: bool,
+    last_inputs: Vec<u16>,
+    stale: bool,
+}
+
+fn mask(lo: u8, hi: u8) -> u16 {
+    (0xffff >> (15 - (hi - lo))) << lo
+}
+
+impl Composite {
+    fn gather(wires: &[u16], part: &Part, values: &mut [u16]) {
+        values.iter_mut().for_each(|value| *value = 0);
+        for binding in &part.inputs {
+            let bits = match binding.source {
+                Source::Constant(false) => 0,
+                Source::Constant(true) => 0xffff,
+                Source::Wire(wire, lo) => wires[wire] >> lo,
+            };
+            let mask = mask(binding.lo, binding.hi);
+            values[binding.pin] = values[binding.pin] & !mask | (bits << binding.lo) & mask;
+        }
+    }
+
+    // writes the outputs of a part to its wires, returning `true` if any wire changed
+    fn scatter(wires: &mut [u16], part: &Part) -> bool {
+        let mut changed = false;
+        for binding in &part.outputs {
+            let bits = part.last_outputs[binding.pin] >> binding.lo;
+            let mask = mask(binding.wire_lo, binding.wire_lo + (binding.hi - binding.lo));
+            let value = wires[binding.wire] & !mask | (bits << binding.wire_lo) & mask;
+            changed |= value != wires[binding.wire];
+            wires[binding.wire] = value;
+        }
+        changed
+    }
+
+    fn eval(&mut self, inputs: &[u16], outputs: &mut [u16]) {
+        let outputs_start = self.inputs.len();
+        if !self.stale && self.last_inputs == inputs {
+            outputs.copy_from_slice(&self.wires[outputs_start..outputs_start + outputs.len()]);
+            return;
+        }
+        self.wires[..inputs.len()].copy_from_slice(inputs);
+
+        // evaluate the parts in order until the wires settle. parts whose inputs did not
+        // change since their last evaluation are skipped.
+        let mut values = Vec::new();
+        for _ in 0..=self.parts.len() {
+            let mut changed = false;
+            for part in &mut self.parts {
+                values.resize(part.last_inputs.len(), 0);
+                Self::gather(&self.wires, part, &mut values);
+                if part.stale || part.last_inputs != values {
+                    part.chip.eval(&values, &mut part.last_outputs);
+                    part.last_inputs.copy_from_slice(&values);
+                    part.stale = false;
+                    changed |= Self::scatter(&mut self.wires, part);
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        self.last_inputs.copy_from_slice(inputs);
+        self.stale = false;
+        outputs.copy_from_slice(&self.wires[outputs_start..outputs_start + outputs.len()]);
+    }
+
+    fn tick(&mut self, inputs: &[u16]) {
+        let mut outputs = vec![0; self.outputs.len()];
+        self.eval(inputs, &mut outputs);
+
+        let mut values = Vec::new();
+        for part in self.parts.iter_mut().filter(|part| part.chip.is_clocked()) {
+            values.resize(part.last_inputs.len(), 0);
+            Self::gather(&self.wires, part, &mut values);
+            part.chip.tick(&values);
+        }
+    }
+
+    fn tock(&mut self) {
+        for part in self.parts.iter_mut().filter(|part| part.chip.is_clocked()) {
+            part.chip.tock();
+            part.stale = true;
+        }
+        self.stale = true;
+    }
+}
+
+/// Loads chips from HDL files, falling back to the built-in implementation of standard
+/// chips that have no HDL file in any of the search directories.
+pub struct Loader {
+    dirs: Vec<PathBuf>,
+    definitions: HashMap<String, ChipDef>,
+}
+
+impl Loader {
+    pub fn new(dirs: Vec<PathBuf>) -> Self {
+        Loader {
+            dirs,
+            definitions: HashMap::new(),
+        }
+    }
+
+    fn definition(&mut self, name: &str) -> Result<Option<ChipDef>, String> {
+        if let Some(definition) = self.definitions.get(name) {
+            return Ok(Some(definition.clone()));
+        }
+
+        let Some(path) = self
+            .dirs
+            .iter()
+            .map(|dir| dir.join(format!("{}.hdl", name)))
+            .find(|path| path.is_file())
+        else {
+            return Ok(None);
+        };
+
+        let source = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let definition = parser::parse(&source).map_err(|e| format!("{}.hdl, {}", name, e))?;
+        if definition.name != name {
+            return Err(format!("{}.hdl defines chip {}", name, definition.name));
+        }
+        self.definitions
+            .insert(name.to_string(), definition.clone());
+
+        Ok(Some(definition))
+    }
+
+    /// Loads the named chip. The chip itself must be defined by an HDL file.
+    pub fn load_hdl(&mut self, name: &str) -> Result<Chip, String> {
+        match self.definition(name)? {
+            Some(definition) => self.build(&definition, &mut vec![name.to_string()]),
+            None => Err(format!("{}.hdl not found", name)),
+        }
+    }
+
+    fn load_part(&mut self, name: &str, loading: &mut Vec<String>) -> Result<Chip, String> {
+        if loading.iter().any(|chip| chip == name) {
+            return Err(format!("chip {} is used inside itself", name));
+        }
+
+        match self.definition(name)? {
+            Some(definition) => {
+                loading.push(name.to_string());
+                let chip = self.build(&definition, loading);
+                loading.pop();
+                chip
+            }
+            None => Builtin::new(name)
+                .map(Chip::Builtin)
+                .ok_or(format!("chip {} not found", name)),
+        }
+    }
+
+    fn build(&mut self, definition: &ChipDef, loading: &mut Vec<String>) -> Result<Chip, String> {
+        if let Some(builtin) = &definition.builtin {
+            return Builtin::new(builtin).map(Chip::Builtin).ok_or(format!(
+                "{}: unknown built-in chip {}",
+                definition.name, builtin
+            ));
+        }
+
+        let error = |line: usize, message: String| {
+            Err(format!(
+                "{}.hdl, line {}: {}",
+                definition.name, line, message
+            ))
+        };
+
+        // wire name -> (index, width)
+        let mut wires: HashMap<&str, (usize, u8)> = HashMap::new();
+        for (i, pin) in definition
+            .inputs
+            .iter()
+            .chain(&definition.outputs)
+            .enumerate()
+        {
+            wires.insert(&pin.name, (i, pin.width));
+        }
+        let is_input = |name: &str| definition.inputs.iter().any(|pin| pin.name == name);
+        let is_output = |name: &str| definition.outputs.iter().any(|pin| pin.name == name);
+
+        let mut chips = Vec::new();
+        for part in &definition.parts {
+            chips.push(self.load_part(&part.chip, loading)?);
+        }
+
+        // a part pin and the range of its bits a connection refers to
+        let part_range = |chip: &Chip, pin_ref: &PinRef, line: usize| {
+            let (pin, decl) = chip
+                .inputs()
+                .iter()
+                .chain(chip.outputs())
+                .enumerate()
+                .find(|(_, decl)| decl.name == pin_ref.name)
+                .ok_or(format!(
+                    "{}.hdl, line {}: {} has no pin {}",
+                    definition.name,
+                    line,
+                    chip.name(),
+                    pin_ref.name
+                ))?;
+            let (lo, hi) = pin_ref.range.unwrap_or((0, decl.width - 1));
+            if hi >= decl.width {
+                return Err(format!(
+                    "{}.hdl, line {}: sub-bus {}[{}] is out of range",
+                    definition.name, line, pin_ref.name, hi
+                ));
+            }
+            Ok((pin, lo, hi))
+        };
+
+        // first, every part output creates the internal pin it connects to
+        for (part, chip) in definition.parts.iter().zip(&chips) {
+            for connection in &part.connections {
+                let (pin, lo, hi) = part_range(chip, &connection.part, part.line)?;
+                if pin < chip.inputs().len() {
+                    continue;
+                }
+                let name = connection.chip.name.as_str();
+                if is_input(name) {
+                    return error(part.line, format!("cannot write to input pin {}", name));
+                }
+                if name == "true" || name == "false" {
+                    return error(part.line, format!("cannot write to constant {}", name));
+                }
+                if !is_output(name) {
+                    if connection.chip.range.is_some() {
+                        return error(
+                            part.line,
+                            format!("internal pin {} cannot be subscripted", name),
+                        );
+                    }
+                    if let Some((_, width)) = wires.get(name) {
+                        if *width != hi - lo + 1 {
+                            return error(
+                                part.line,
+                                format!("internal pin {} is used with different widths", name),
+                            );
+                        }
+                    }
+                    let index = wires.len();
+                    wires.entry(name).or_insert((index, hi - lo + 1));
+                }
+            }
+        }
+
+        let mut parts = Vec::new();
+        for (part, chip) in definition.parts.iter().zip(chips) {
+            let mut inputs = Vec::new();
+            let mut outputs = Vec::new();
+
+            for connection in &part.connections {
+                let (pin, lo, hi) = part_range(&chip, &connection.part, part.line)?;
+                let width = hi - lo + 1;
+                let name = connection.chip.name.as_str();
+
+                if pin < chip.inputs().len() {
+                    let source = match (name, wires.get(name)) {
+                        ("true", None) => Source::Constant(true),
+                        ("false", None) => Source::Constant(false),
+                        (_, Some(&(wire, wire_width))) => {
+                            let (wire_lo, wire_hi) =
+                                connection.chip.range.unwrap_or((0, wire_width - 1));
+                            if wire_hi >= wire_width {
+                                return error(
+                                    part.line,
+                                    format!("sub-bus {}[{}] is out of range", name, wire_hi),
+                                );
+                            }
+                            if wire_hi - wire_lo + 1 != width {
+                                return error(
+                                    part.line,
+                                    format!(
+                                        "width of {} does not match pin {}",
+                                        name, connection.part.name
+                                    ),
+                                );
+                            }
+                            Source::Wire(wire, wire_lo)
+                        }
+                        (_, None) => {
+                            return error(
+                                part.line,
+                                format!("pin {} is not connected to any output", name),
+                            )
+                        }
+                    };
+                    inputs.push(InputBinding {
+                        pin,
+                        lo,
+                        hi,
+                        source,
+                    });
+                } else {
+                    let (wire, wire_width) = wires[name];
+                    let (wire_lo, wire_hi) = connection.chip.range.unwrap_or((0, wire_width - 1));
+                    if wire_hi >= wire_width {
+                        return error(
+                            part.line,
+                            format!("sub-bus {}[{}] is out of range", name, wire_hi),
+                        );
+                    }
+                    if wire_hi - wire_lo + 1 != width {
+                        return error(
+                            part.line,
+                            format!(
+                                "width of {} does not match pin {}",
+                                name, connection.part.name
+                            ),
+                        );
+                    }
+                    outputs.push(OutputBinding {
+                        pin: pin - chip.inputs().len(),
+                        lo,
+                        hi,
+                        wire,
+                        wire_lo,
+                    });
+                }
+            }
+
+            parts.push(Part {
+                last_inputs: vec![0; chip.inputs().len()],
+                last_outputs: vec![0; chip.outputs().len()],
+                chip,
+                inputs,
+                outputs,
+                stale: true,
+            });
+        }
+
+        let clocked = parts.iter().any(|part| part.chip.is_clocked());
+        Ok(Chip::Composite(Box::new(Composite {
+            name: definition.name.clone(),
+            inputs: definition.inputs.clone(),
+            outputs: definition.outputs.clone(),
+            wires: vec![0; wires.len()],
+            parts,
+            clocked,
+            last_inputs: vec![0; definition.inputs.len()],
+            stale: true,
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn projects(dir: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../projects")
+            .join(dir)
+    }
+
+    #[test]
+    fn test_composite() {
+        let mut loader = Loader::new(vec![projects("01")]);
+        let mut chip = loader.load_hdl("Mux16").unwrap();
+
+        let mut out = [0];
+        chip.eval(&[0x1234, 0x4321, 0], &mut out);
+        assert_eq!(out, [0x1234]);
+        chip.eval(&[0x1234, 0x4321, 1], &mut out);
+        assert_eq!(out, [0x4321]);
+    }
+
+    #[test]
+    fn test_clocked() {
+        let mut loader = Loader::new(vec![projects("03/a")]);
+        let mut chip = loader.load_hdl("Register").unwrap();
+        assert!(chip.is_clocked());
+
+        let mut out = [0];
+        chip.tick(&[1234, 1]);
+        chip.eval(&[1234, 1], &mut out);
+        assert_eq!(out, [0]);
+
+        chip.tock();
+        chip.eval(&[1234, 1], &mut out);
+        assert_eq!(out, [1234]);
+    }
+
+    #[test]
+    fn test_missing_chip() {
+        let mut loader = Loader::new(vec![projects("01")]);
+        assert_eq!(
+            loader.load_hdl("Nothing").err().unwrap(),
+            "Nothing.hdl not found"
+        );
+    }
+}
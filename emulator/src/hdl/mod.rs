@@ -0,0 +1,3 @@
+pub mod builtin;
+pub mod chip;
+pub mod parser;
@@ -0,0 +1,374 @@
+/// A pin declared in the IN or OUT section of a chip.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PinDecl {
+    pub name: String,
+    pub width: u8,
+}
+
+/// One side of a `pin=pin` connection, with an optional `[i]` or `[i..j]` sub-bus.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PinRef {
+    pub name: String,
+    pub range: Option<(u8, u8)>,
+}
+
+/// A `part pin = chip pin` connection inside a part.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Connection {
+    pub part: PinRef,
+    pub chip: PinRef,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartDef {
+    pub chip: String,
+    pub connections: Vec<Connection>,
+    pub line: usize,
+}
+
+/// The contents of a `.hdl` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChipDef {
+    pub name: String,
+    pub inputs: Vec<PinDecl>,
+    pub outputs: Vec<PinDecl>,
+    pub parts: Vec<PartDef>,
+    /// Set for `BUILTIN` chips, naming the built-in implementation.
+    pub builtin: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    Number(u8),
+    Symbol(char),
+    Range,
+}
+
+/// Parses the source of a `.hdl` file.
+pub fn parse(source: &str) -> Result<ChipDef, String> {
+    let tokens = tokenize(source)?;
+    Parser { tokens, pos: 0 }.chip()
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    let mut line = 1;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            c if c.is_whitespace() => {}
+            '/' if chars.peek() == Some(&'/') => {
+                while chars.peek().is_some_and(|c| *c != '\n') {
+                    chars.next();
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut last = ' ';
+                loop {
+                    match chars.next() {
+                        Some('/') if last == '*' => break,
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            last = c;
+                        }
+                        None => return Err(format!("line {}: unterminated comment", line)),
+                    }
+                }
+            }
+            '.' if chars.peek() == Some(&'.') => {
+                chars.next();
+                tokens.push((Token::Range, line));
+            }
+            c if c.is_ascii_digit() => {
+                let mut number = c.to_string();
+                while let Some(c) = chars.peek().filter(|c| c.is_ascii_digit()) {
+                    number.push(*c);
+                    chars.next();
+                }
+                let number = number
+                    .parse()
+                    .map_err(|_| format!("line {}: number {} is out of range", line, number))?;
+                tokens.push((Token::Number(number), line));
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut ident = c.to_string();
+                while let Some(c) = chars
+                    .peek()
+                    .filter(|c| c.is_ascii_alphanumeric() || **c == '_')
+                {
+                    ident.push(*c);
+                    chars.next();
+                }
+                tokens.push((Token::Ident(ident), line));
+            }
+            '{' | '}' | '(' | ')' | '[' | ']' | ',' | ';' | '=' | ':' => {
+                tokens.push((Token::Symbol(c), line))
+            }
+            c => return Err(format!("line {}: unexpected character '{}'", line, c)),
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or(self.tokens.last())
+            .map_or(1, |(_, line)| *line)
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T, String> {
+        Err(format!("line {}: {}", self.line(), message))
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn is_symbol(&self, symbol: char) -> bool {
+        self.peek() == Some(&Token::Symbol(symbol))
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(ident)) if ident == keyword)
+    }
+
+    fn expect_symbol(&mut self, symbol: char) -> Result<(), String> {
+        if self.is_symbol(symbol) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            self.error(&format!("expected '{}'", symbol))
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), String> {
+        if self.is_keyword(keyword) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            self.error(&format!("expected '{}'", keyword))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some(Token::Ident(ident)) => {
+                let ident = ident.clone();
+                self.pos += 1;
+                Ok(ident)
+            }
+            _ => self.error("expected a name"),
+        }
+    }
+
+    fn number(&mut self) -> Result<u8, String> {
+        match self.peek() {
+            Some(Token::Number(number)) => {
+                let number = *number;
+                self.pos += 1;
+                Ok(number)
+            }
+            _ => self.error("expected a number"),
+        }
+    }
+
+    fn chip(&mut self) -> Result<ChipDef, String> {
+        self.expect_keyword("CHIP")?;
+        let name = self.ident()?;
+        self.expect_symbol('{')?;
+
+        let mut chip = ChipDef {
+            name,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            parts: Vec::new(),
+            builtin: None,
+        };
+
+        if self.is_keyword("IN") {
+            self.pos += 1;
+            chip.inputs = self.pin_decls()?;
+        }
+        if self.is_keyword("OUT") {
+            self.pos += 1;
+            chip.outputs = self.pin_decls()?;
+        }
+
+        if self.is_keyword("BUILTIN") {
+            self.pos += 1;
+            chip.builtin = Some(self.ident()?);
+            self.expect_symbol(';')?;
+            // the clocked pins of a built-in chip are known to its implementation
+            if self.is_keyword("CLOCKED") {
+                while !self.is_symbol(';') && self.next().is_some() {}
+                self.expect_symbol(';')?;
+            }
+        } else {
+            self.expect_keyword("PARTS")?;
+            self.expect_symbol(':')?;
+            while !self.is_symbol('}') && self.peek().is_some() {
+                chip.parts.push(self.part()?);
+            }
+        }
+
+        self.expect_symbol('}')?;
+        if self.peek().is_some() {
+            return self.error("unexpected input after the end of the chip");
+        }
+
+        Ok(chip)
+    }
+
+    fn pin_decls(&mut self) -> Result<Vec<PinDecl>, String> {
+        let mut pins = Vec::new();
+        loop {
+            let name = self.ident()?;
+            let width = if self.is_symbol('[') {
+                self.pos += 1;
+                let width = self.number()?;
+                self.expect_symbol(']')?;
+                width
+            } else {
+                1
+            };
+            if width == 0 || width > 16 {
+                return self.error(&format!("pin {} must be 1 to 16 bits wide", name));
+            }
+            pins.push(PinDecl { name, width });
+
+            match self.next() {
+                Some(Token::Symbol(',')) => {}
+                Some(Token::Symbol(';')) => break,
+                _ => {
+                    self.pos -= 1;
+                    return self.error("expected ',' or ';'");
+                }
+            }
+        }
+        Ok(pins)
+    }
+
+    fn part(&mut self) -> Result<PartDef, String> {
+        let line = self.line();
+        let chip = self.ident()?;
+        self.expect_symbol('(')?;
+
+        let mut connections = Vec::new();
+        loop {
+            let part = self.pin_ref()?;
+            self.expect_symbol('=')?;
+            let chip = self.pin_ref()?;
+            connections.push(Connection { part, chip });
+
+            match self.next() {
+                Some(Token::Symbol(',')) => {}
+                Some(Token::Symbol(')')) => break,
+                _ => {
+                    self.pos -= 1;
+                    return self.error("expected ',' or ')'");
+                }
+            }
+        }
+        self.expect_symbol(';')?;
+
+        Ok(PartDef {
+            chip,
+            connections,
+            line,
+        })
+    }
+
+    fn pin_ref(&mut self) -> Result<PinRef, String> {
+        let name = self.ident()?;
+        let range = if self.is_symbol('[') {
+            self.pos += 1;
+            let from = self.number()?;
+            let to = if self.peek() == Some(&Token::Range) {
+                self.pos += 1;
+                self.number()?
+            } else {
+                from
+            };
+            self.expect_symbol(']')?;
+            if from > to || to > 15 {
+                return self.error(&format!("invalid sub-bus {}[{}..{}]", name, from, to));
+            }
+            Some((from, to))
+        } else {
+            None
+        };
+        Ok(PinRef { name, range })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_chip() {
+        let chip = parse(
+            "/** And gate */\n\
+             CHIP And {\n\
+                 IN a, b[16];\n\
+                 OUT out;\n\
+                 PARTS:\n\
+                 // the usual way\n\
+                 Nand(a=a, b=b[3..4], out=x);\n\
+                 Not(in=x, out=out);\n\
+             }",
+        )
+        .unwrap();
+
+        assert_eq!(chip.name, "And");
+        assert_eq!(
+            chip.inputs[1],
+            PinDecl {
+                name: "b".to_string(),
+                width: 16
+            }
+        );
+        assert_eq!(chip.parts.len(), 2);
+        assert_eq!(chip.parts[0].line, 7);
+        assert_eq!(
+            chip.parts[0].connections[1].chip,
+            PinRef {
+                name: "b".to_string(),
+                range: Some((3, 4))
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_builtin() {
+        let chip = parse("CHIP DFF { IN in; OUT out; BUILTIN DFF; CLOCKED in; }").unwrap();
+        assert_eq!(chip.builtin.as_deref(), Some("DFF"));
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let error =
+            parse("CHIP Not {\n IN in;\n OUT out;\n PARTS:\n Nand(a=in b=in);\n}").unwrap_err();
+        assert_eq!(error, "line 5: expected ',' or ')'");
+    }
+}
@@ -0,0 +1,3 @@
+pub mod cpu;
+pub mod hdl;
+pub mod vm;
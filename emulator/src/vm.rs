@@ -0,0 +1,497 @@
+use std::collections::HashMap;
+
+const SP: usize = 0;
+const LCL: usize = 1;
+const ARG: usize = 2;
+const THIS: usize = 3;
+const THAT: usize = 4;
+const TEMP: u16 = 5;
+const STATIC: u16 = 16;
+const STACK: u16 = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Segment {
+    Argument,
+    Local,
+    Static(u16),
+    Constant,
+    This,
+    That,
+    Pointer,
+    Temp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Add,
+    Sub,
+    Neg,
+    Eq,
+    Gt,
+    Lt,
+    And,
+    Or,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Arithmetic(Op),
+    Push(Segment, u16),
+    Pop(Segment, u16),
+    Goto(usize),
+    IfGoto(usize),
+    Function(u16),
+    Call(usize, u16),
+    Return,
+}
+
+/// Executes VM code directly, one command at a time, like the VM emulator of the course.
+///
+/// Every module gets its own static segment, allocated from address 16 in the order the
+/// modules were given. If a `Sys.init` function exists, execution starts by calling it
+/// with the stack at 256; otherwise it starts at the first command of the first module.
+pub struct Vm {
+    pub ram: Vec<u16>,
+    program: Vec<Command>,
+    functions: HashMap<String, usize>,
+    // name of each module and the position of its first command
+    modules: Vec<(String, usize)>,
+    // position of the next command, or None once the program ran off its end
+    pc: Option<usize>,
+    /// Number of commands executed so far.
+    pub steps: u64,
+    // memory_hash of the RAM, kept up to date by every write of a command. Direct writes to
+    // `ram` offset it, which is fine as long as hashes are only compared within a run.
+    hash: u64,
+}
+
+impl Vm {
+    /// Loads a program made of `(module name, source)` pairs.
+    pub fn new(modules: &[(String, String)]) -> Result<Self, String> {
+        let mut parsed = Vec::new();
+        let mut functions = HashMap::new();
+        let mut static_base = STATIC;
+        let mut starts = Vec::new();
+
+        // first pass: tokenize, and record the position of each function
+        for (module, source) in modules {
+            starts.push((module.clone(), parsed.len()));
+            let mut statics = 0;
+            for (i, line) in source.lines().enumerate() {
+                let code = line.split("//").next().unwrap();
+                let words: Vec<&str> = code.split_ascii_whitespace().collect();
+                if words.is_empty() {
+                    continue;
+                }
+
+                let location = format!("{}:{}", module, i + 1);
+                if words[0] == "function" {
+                    let name = words
+                        .get(1)
+                        .ok_or(format!("{}: missing function name", location))?;
+                    if functions.insert(name.to_string(), parsed.len()).is_some() {
+                        return Err(format!("{}: function {} is defined twice", location, name));
+                    }
+                }
+                if words[0] == "push" || words[0] == "pop" {
+                    if let (Some(&"static"), Some(index)) = (words.get(1), words.get(2)) {
+                        statics = statics.max(index.parse::<u16>().unwrap_or(0) + 1);
+                    }
+                }
+                parsed.push((
+                    location,
+                    static_base,
+                    words.iter().map(|s| s.to_string()).collect::<Vec<_>>(),
+                ));
+            }
+            static_base += statics;
+        }
+
+        // labels are scoped to the function they appear in
+        let mut labels = HashMap::new();
+        let mut function = String::new();
+        for (i, (_, _, words)) in parsed.iter().enumerate() {
+            match words[0].as_str() {
+                "function" => function = words[1].clone(),
+                "label" => {
+                    let label = words.get(1).cloned().unwrap_or_default();
+                    labels.insert((function.clone(), label), i);
+                }
+                _ => {}
+            }
+        }
+
+        let mut program = Vec::with_capacity(parsed.len());
+        let mut function = String::new();
+        for (location, static_base, words) in &parsed {
+            let arg = |n: usize| {
+                words.get(n).map(String::as_str).ok_or(format!(
+                    "{}: '{}' is missing an argument",
+                    location, words[0]
+                ))
+            };
+            let number = |n: usize| {
+                arg(n)?
+                    .parse::<u16>()
+                    .map_err(|_| format!("{}: '{}' is not a valid number", location, words[n]))
+            };
+            let label = |n: usize| {
+                let label = arg(n)?;
+                labels
+                    .get(&(function.clone(), label.to_string()))
+                    .copied()
+                    .ok_or(format!("{}: undefined label {}", location, label))
+            };
+            let segment = |n: usize| {
+                Ok(match arg(n)? {
+                    "argument" => Segment::Argument,
+                    "local" => Segment::Local,
+                    "static" => Segment::Static(*static_base),
+                    "constant" => Segment::Constant,
+                    "this" => Segment::This,
+                    "that" => Segment::That,
+                    "pointer" => Segment::Pointer,
+                    "temp" => Segment::Temp,
+                    s => return Err(format!("{}: unknown segment {}", location, s)),
+                })
+            };
+
+            let command = match words[0].as_str() {
+                "add" => Command::Arithmetic(Op::Add),
+                "sub" => Command::Arithmetic(Op::Sub),
+                "neg" => Command::Arithmetic(Op::Neg),
+                "eq" => Command::Arithmetic(Op::Eq),
+                "gt" => Command::Arithmetic(Op::Gt),
+                "lt" => Command::Arithmetic(Op::Lt),
+                "and" => Command::Arithmetic(Op::And),
+                "or" => Command::Arithmetic(Op::Or),
+                "not" => Command::Arithmetic(Op::Not),
+                "push" => Command::Push(segment(1)?, number(2)?),
+                "pop" => match segment(1)? {
+                    Segment::Constant => {
+                        return Err(format!("{}: cannot pop into constant", location))
+                    }
+                    segment => Command::Pop(segment, number(2)?),
+                },
+                // a label is a no-op, so it is executed as a jump to the next command
+                "label" => Command::Goto(program.len() + 1),
+                "goto" => Command::Goto(label(1)?),
+                "if-goto" => Command::IfGoto(label(1)?),
+                "function" => {
+                    function = words[1].clone();
+                    Command::Function(number(2)?)
+                }
+                "call" => {
+                    let name = arg(1)?;
+                    let target = functions
+                        .get(name)
+                        .copied()
+                        .ok_or(format!("{}: undefined function {}", location, name))?;
+                    Command::Call(target, number(2)?)
+                }
+                "return" => Command::Return,
+                c => return Err(format!("{}: unknown command {}", location, c)),
+            };
+            program.push(command);
+        }
+
+        let mut vm = Vm {
+            ram: vec![0; 0x8000],
+            program,
+            pc: Some(0),
+            functions,
+            modules: starts,
+            steps: 0,
+            hash: 0,
+        };
+        vm.reset();
+
+        Ok(vm)
+    }
+
+    /// Clears the memory and restarts the program.
+    pub fn reset(&mut self) {
+        self.ram.iter_mut().for_each(|word| *word = 0);
+        self.hash = 0;
+        self.steps = 0;
+        self.pc = if self.program.is_empty() {
+            None
+        } else {
+            Some(0)
+        };
+
+        if let Some(&init) = self.functions.get("Sys.init") {
+            self.ram[SP] = STACK;
+            // the return address of the bootstrap call is past the end of the program
+            self.call(init, 0, self.program.len());
+        }
+    }
+
+    /// Returns `true` once execution ran past the last command.
+    pub fn finished(&self) -> bool {
+        self.pc.is_none()
+    }
+
+    /// The module of the next command, or `None` once the program has finished.
+    pub fn module(&self) -> Option<&str> {
+        let pc = self.pc?;
+        let i = self.modules.partition_point(|&(_, start)| start <= pc);
+        Some(&self.modules[i - 1].0)
+    }
+
+    /// Executes a single command. Does nothing once the program has finished.
+    pub fn step(&mut self) {
+        let Some(pc) = self.pc else {
+            return;
+        };
+        self.steps += 1;
+
+        let mut next = pc + 1;
+        match self.program[pc] {
+            Command::Arithmetic(op) => self.arithmetic(op),
+            Command::Push(segment, index) => {
+                let value = match segment {
+                    Segment::Constant => index,
+                    segment => self.ram[self.address(segment, index)],
+                };
+                self.push(value);
+            }
+            Command::Pop(segment, index) => {
+                let address = self.address(segment, index);
+                let value = self.pop();
+                self.store(address, value);
+            }
+            Command::Goto(target) => next = target,
+            Command::IfGoto(target) => {
+                if self.pop() != 0 {
+                    next = target;
+                }
+            }
+            Command::Function(locals) => {
+                for _ in 0..locals {
+                    self.push(0);
+                }
+            }
+            Command::Call(target, args) => {
+                self.call(target, args, next);
+                next = target;
+            }
+            Command::Return => {
+                let frame = self.ram[LCL];
+                let return_address = self.ram[frame.wrapping_sub(5) as usize & 0x7fff];
+                let value = self.pop();
+                let arg = self.ram[ARG];
+                self.store(arg as usize & 0x7fff, value);
+                self.store(SP, arg.wrapping_add(1));
+                for (i, pointer) in [THAT, THIS, ARG, LCL].into_iter().enumerate() {
+                    self.store(
+                        pointer,
+                        self.ram[frame.wrapping_sub(i as u16 + 1) as usize & 0x7fff],
+                    );
+                }
+                next = return_address as usize;
+            }
+        }
+
+        self.pc = Some(next).filter(|next| *next < self.program.len());
+    }
+
+    /// Runs at most `limit` commands, stopping early once the program has finished or is
+    /// idle, i.e. it came back to a loop in the exact same state as before.
+    ///
+    /// Returns `true` if the program finished or went idle before the limit was reached.
+    pub fn run_until_idle(&mut self, limit: u64) -> bool {
+        self.run_while(limit, |_| true)
+    }
+
+    /// Like `run_until_idle`, but also stops as soon as `condition` does not hold before a
+    /// command.
+    pub fn run_while(&mut self, limit: u64, mut condition: impl FnMut(&Vm) -> bool) -> bool {
+        // jump target -> memory hash seen the last time the jump was taken
+        let mut visits: HashMap<usize, u64> = HashMap::new();
+
+        for _ in 0..limit {
+            let Some(pc) = self.pc else {
+                return true;
+            };
+            if !condition(self) {
+                return false;
+            }
+            self.step();
+
+            if let Some(next) = self.pc.filter(|next| *next <= pc) {
+                if visits.insert(next, self.hash) == Some(self.hash) {
+                    return true;
+                }
+            }
+        }
+
+        self.pc.is_none()
+    }
+
+    fn call(&mut self, target: usize, args: u16, return_address: usize) {
+        self.push(return_address as u16);
+        for pointer in [LCL, ARG, THIS, THAT] {
+            self.push(self.ram[pointer]);
+        }
+        self.store(ARG, self.ram[SP].wrapping_sub(args + 5));
+        self.store(LCL, self.ram[SP]);
+        self.pc = Some(target);
+    }
+
+    fn address(&self, segment: Segment, index: u16) -> usize {
+        let address = match segment {
+            Segment::Argument => self.ram[ARG].wrapping_add(index),
+            Segment::Local => self.ram[LCL].wrapping_add(index),
+            Segment::Static(base) => base + index,
+            Segment::This => self.ram[THIS].wrapping_add(index),
+            Segment::That => self.ram[THAT].wrapping_add(index),
+            Segment::Pointer => THIS as u16 + index,
+            Segment::Temp => TEMP + index,
+            Segment::Constant => unreachable!(),
+        };
+        address as usize & 0x7fff
+    }
+
+    fn arithmetic(&mut self, op: Op) {
+        let y = self.pop();
+        let value = match op {
+            Op::Neg => y.wrapping_neg(),
+            Op::Not => !y,
+            _ => {
+                let x = self.pop();
+                match op {
+                    Op::Add => x.wrapping_add(y),
+                    Op::Sub => x.wrapping_sub(y),
+                    Op::Eq => bool_value(x == y),
+                    Op::Gt => bool_value((x as i16) > (y as i16)),
+                    Op::Lt => bool_value((x as i16) < (y as i16)),
+                    Op::And => x & y,
+                    Op::Or => x | y,
+                    Op::Neg | Op::Not => unreachable!(),
+                }
+            }
+        };
+        self.push(value);
+    }
+
+    fn push(&mut self, value: u16) {
+        let sp = self.ram[SP];
+        self.store(sp as usize & 0x7fff, value);
+        self.store(SP, sp.wrapping_add(1));
+    }
+
+    fn pop(&mut self) -> u16 {
+        let sp = self.ram[SP].wrapping_sub(1);
+        self.store(SP, sp);
+        self.ram[sp as usize & 0x7fff]
+    }
+
+    fn store(&mut self, address: usize, value: u16) {
+        let old = self.ram[address];
+        self.hash = self
+            .hash
+            .wrapping_sub(word_hash(address, old))
+            .wrapping_add(word_hash(address, value));
+        self.ram[address] = value;
+    }
+}
+
+/// Hashes the contents of a memory, such that updating a single word updates the hash cheaply.
+pub fn memory_hash(ram: &[u16]) -> u64 {
+    ram.iter().enumerate().fold(0, |hash, (address, value)| {
+        hash.wrapping_add(word_hash(address, *value))
+    })
+}
+
+/// The contribution of a single memory word to `memory_hash`.
+pub fn word_hash(address: usize, value: u16) -> u64 {
+    if value == 0 {
+        return 0;
+    }
+    // splitmix64 finalizer
+    let mut x = (address as u64) << 16 | value as u64;
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+fn bool_value(value: bool) -> u16 {
+    if value {
+        0xffff
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module(name: &str, source: &str) -> (String, String) {
+        (name.to_string(), source.to_string())
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let mut vm = Vm::new(&[module(
+            "Main",
+            "push constant 7\npush constant 8\nadd\npush constant 3\nlt\n",
+        )])
+        .unwrap();
+        vm.ram[SP] = 256;
+        while !vm.finished() {
+            vm.step();
+        }
+
+        assert_eq!(vm.ram[SP], 257);
+        assert_eq!(vm.ram[256], 0);
+    }
+
+    #[test]
+    fn test_call() {
+        let sys = "\
+            function Sys.init 0\n\
+            push constant 5\n\
+            call Sys.double 1\n\
+            pop static 0\n\
+            label END\n\
+            goto END\n\
+            function Sys.double 0\n\
+            push argument 0\n\
+            push argument 0\n\
+            add\n\
+            return\n";
+        let mut vm = Vm::new(&[module("Sys", sys)]).unwrap();
+        for _ in 0..20 {
+            vm.step();
+        }
+
+        assert_eq!(vm.ram[16], 10);
+        assert_eq!(vm.ram[SP], 261);
+    }
+
+    #[test]
+    fn test_module() {
+        let main = "function Main.main 0\ncall Sys.wait 0\nlabel END\ngoto END\n";
+        let sys = "function Sys.wait 0\npush constant 0\nreturn\n";
+        let mut vm = Vm::new(&[module("Main", main), module("Sys", sys)]).unwrap();
+        vm.ram[SP] = 256;
+        vm.step();
+        vm.step();
+        assert_eq!(vm.module(), Some("Sys"));
+
+        assert!(!vm.run_while(100, |vm| vm.module() == Some("Sys")));
+        assert_eq!(vm.module(), Some("Main"));
+        assert_eq!(vm.steps, 5);
+    }
+
+    #[test]
+    fn test_undefined_label() {
+        let error = Vm::new(&[module("Main", "function Main.f 0\ngoto NOWHERE\n")])
+            .err()
+            .unwrap();
+        assert_eq!(error, "Main:2: undefined label NOWHERE");
+    }
+}
@@ -0,0 +1,826 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use emulator::cpu::{Cpu, ROM_SIZE};
+
+use crate::script::{self, Limits, Outcome, Runner, Stop, Target};
+use crate::target::{CpuTarget, HardwareTarget, VmTarget};
+use crate::toolchain;
+
+/// The projects graded by default.
+pub const PROJECTS: [&str; 12] = [
+    "01", "02", "03", "04", "05", "06", "07", "08", "09", "10", "11", "12",
+];
+
+// memory compared between the reference and the submitted compiler output of project 11:
+// the screen, and the words ConvertToBin reads and writes
+const SCREEN: std::ops::Range<usize> = 0x4000..0x6000;
+const DATA: std::ops::Range<usize> = 8000..8017;
+const DATA_SEED: u16 = 0x5a5a;
+
+pub struct Config {
+    /// The projects directory of the course, holding the reference tests.
+    pub tests: PathBuf,
+    /// Compiled operating system classes, linked with Jack programs.
+    pub os: PathBuf,
+    pub limits: Limits,
+    pub projects: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Passed,
+    Failed,
+    Error,
+    Timeout,
+    CycleLimit,
+    Missing,
+}
+
+impl Status {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Status::Passed => "passed",
+            Status::Failed => "failed",
+            Status::Error => "error",
+            Status::Timeout => "timeout",
+            Status::CycleLimit => "cycle-limit",
+            Status::Missing => "missing",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TestResult {
+    pub name: String,
+    pub status: Status,
+    /// Fraction of the test that passed, between 0 and 1.
+    pub credit: f64,
+    pub message: Option<String>,
+    pub cycles: u64,
+    pub millis: u128,
+}
+
+impl TestResult {
+    fn new(name: &str, status: Status, credit: f64, message: Option<String>) -> Self {
+        TestResult {
+            name: name.to_string(),
+            status,
+            credit,
+            message,
+            cycles: 0,
+            millis: 0,
+        }
+    }
+
+    fn passed(name: &str) -> Self {
+        Self::new(name, Status::Passed, 1.0, None)
+    }
+
+    fn error(name: &str, message: String) -> Self {
+        Self::new(name, Status::Error, 0.0, Some(message))
+    }
+
+    fn missing(name: &str, message: String) -> Self {
+        Self::new(name, Status::Missing, 0.0, Some(message))
+    }
+
+    // partial credit for the fraction of `matched` lines or words out of `total`
+    fn compared(
+        name: &str,
+        matched: usize,
+        total: usize,
+        message: impl FnOnce() -> String,
+    ) -> Self {
+        if matched == total {
+            Self::passed(name)
+        } else {
+            Self::new(
+                name,
+                Status::Failed,
+                matched as f64 / total.max(1) as f64,
+                Some(message()),
+            )
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ProjectResult {
+    pub project: String,
+    pub tests: Vec<TestResult>,
+}
+
+impl ProjectResult {
+    /// Average credit of the tests, out of 100.
+    pub fn score(&self) -> f64 {
+        if self.tests.is_empty() {
+            return 0.0;
+        }
+        100.0 * self.tests.iter().map(|test| test.credit).sum::<f64>() / self.tests.len() as f64
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StudentResult {
+    pub student: String,
+    pub projects: Vec<ProjectResult>,
+}
+
+impl StudentResult {
+    /// Average score of the projects, out of 100.
+    pub fn score(&self) -> f64 {
+        if self.projects.is_empty() {
+            return 0.0;
+        }
+        self.projects.iter().map(ProjectResult::score).sum::<f64>() / self.projects.len() as f64
+    }
+}
+
+/// Grades the repository of a single student.
+pub fn grade_student(student: &str, root: &Path, config: &Config) -> StudentResult {
+    let projects = config
+        .projects
+        .iter()
+        .map(|project| {
+            // submissions keep the layout of the course, with or without the `projects` directory
+            let dir = [root.join("projects").join(project), root.join(project)]
+                .into_iter()
+                .find(|dir| dir.is_dir());
+            ProjectResult {
+                project: project.clone(),
+                tests: grade_project(project, dir.as_deref(), config),
+            }
+        })
+        .collect();
+
+    StudentResult {
+        student: student.to_string(),
+        projects,
+    }
+}
+
+fn grade_project(project: &str, dir: Option<&Path>, config: &Config) -> Vec<TestResult> {
+    let tests = config.tests.join(project);
+    let missing = |name: &str| TestResult::missing(name, format!("project {} not found", project));
+
+    match project {
+        "01" | "02" | "03" | "05" => {
+            let subdirs: &[&str] = if project == "03" { &["a", "b"] } else { &[""] };
+            let mut results = Vec::new();
+            for subdir in subdirs {
+                let tests = tests.join(subdir);
+                for tst in files(&tests, "tst", false) {
+                    let name = test_name(&config.tests.join(project), &tst);
+                    results.push(match dir {
+                        Some(dir) => {
+                            let mut target = HardwareTarget::new(&dir.join(subdir), &tests);
+                            run_script(&name, &tst, &mut target, config.limits)
+                        }
+                        None => missing(&name),
+                    });
+                }
+            }
+            results
+        }
+        "04" | "07" | "08" => files(&tests, "tst", true)
+            .into_iter()
+            // VME scripts test the VM emulator itself, Fill.tst needs a user at the keyboard
+            .filter(|tst| !tst.to_string_lossy().ends_with("VME.tst") && is_automatic(tst))
+            .map(|tst| {
+                let name = test_name(&tests, &tst);
+                match dir {
+                    Some(dir) => {
+                        let relative = tst.parent().unwrap().strip_prefix(&tests).unwrap();
+                        let mut target = CpuTarget::new(&dir.join(relative));
+                        run_script(&name, &tst, &mut target, config.limits)
+                    }
+                    None => missing(&name),
+                }
+            })
+            .collect(),
+        "06" => files(&tests, "asm", true)
+            .into_iter()
+            .map(|asm| {
+                let name = test_name(&tests, &asm);
+                match dir {
+                    Some(dir) => {
+                        grade_hack(&name, &asm, &dir.join(asm.strip_prefix(&tests).unwrap()))
+                    }
+                    None => missing(&name),
+                }
+            })
+            .collect(),
+        "09" => match dir {
+            Some(dir) => subdirs(dir)
+                .into_iter()
+                .filter(|program| !files(program, "jack", false).is_empty())
+                .map(|program| grade_program(&test_name(dir, &program), &program, config))
+                .collect(),
+            None => vec![missing("09")],
+        },
+        "10" => files(&tests, "xml", true)
+            .into_iter()
+            .map(|xml| {
+                let name = test_name(&tests, &xml);
+                match dir {
+                    Some(dir) => {
+                        grade_xml(&name, &xml, &dir.join(xml.strip_prefix(&tests).unwrap()))
+                    }
+                    None => missing(&name),
+                }
+            })
+            .collect(),
+        "11" => subdirs(&tests)
+            .into_iter()
+            .map(|program| {
+                let name = test_name(&tests, &program);
+                match dir {
+                    Some(dir) => grade_compiler(&name, &program, &dir.join(&name), config),
+                    None => missing(&name),
+                }
+            })
+            .collect(),
+        "12" => subdirs(&tests)
+            .into_iter()
+            .filter_map(|test| Some((test.clone(), files(&test, "tst", false).into_iter().next()?)))
+            .map(|(test, tst)| {
+                let name = test_name(&tests, &test);
+                match dir {
+                    Some(dir) => grade_os(&name, &test, &tst, dir, config),
+                    None => missing(&name),
+                }
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// the path of a test relative to the project, without extension
+fn test_name(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path).with_extension("");
+    relative.to_string_lossy().replace('\\', "/")
+}
+
+// files with the given extension, sorted by path
+fn files(dir: &Path, extension: &str, recursive: bool) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let Ok(entries) = fs::read_dir(dir) else {
+        return files;
+    };
+
+    for path in entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+    {
+        if path.is_dir() && recursive {
+            files.extend(self::files(&path, extension, true));
+        } else if path.extension().is_some_and(|ext| ext == extension) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    files
+}
+
+fn subdirs(dir: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+fn is_automatic(tst: &Path) -> bool {
+    fs::read_to_string(tst).is_ok_and(|source| source.contains("compare-to"))
+}
+
+fn run_script(name: &str, tst: &Path, target: &mut impl Target, limits: Limits) -> TestResult {
+    let started = Instant::now();
+    let statements = match fs::read_to_string(tst)
+        .map_err(|e| e.to_string())
+        .and_then(|source| script::parse(&source))
+    {
+        Ok(statements) => statements,
+        Err(e) => return TestResult::error(name, format!("{}: {}", tst.display(), e)),
+    };
+
+    let outcome = Runner::new(target, tst.parent().unwrap(), limits).run(&statements);
+    let mut result = script_result(name, &outcome, limits);
+    result.cycles = outcome.cycles;
+    result.millis = started.elapsed().as_millis();
+    result
+}
+
+fn script_result(name: &str, outcome: &Outcome, limits: Limits) -> TestResult {
+    if outcome.passed() {
+        return TestResult::passed(name);
+    }
+
+    let (status, message) = match &outcome.stop {
+        Some(stop) => stopped(stop, limits),
+        None => {
+            let expected = outcome.expected.as_ref().map_or(0, Vec::len);
+            let message = match outcome.first_mismatch {
+                Some(line) => format!("comparison failure at line {}", line),
+                None => format!(
+                    "{} output lines, expected {}",
+                    outcome.output.len(),
+                    expected
+                ),
+            };
+            (Status::Failed, message)
+        }
+    };
+    let credit = if status == Status::Error {
+        0.0
+    } else {
+        outcome.credit()
+    };
+    TestResult::new(name, status, credit, Some(message))
+}
+
+fn stopped(stop: &Stop, limits: Limits) -> (Status, String) {
+    match stop {
+        Stop::Error(e) => (Status::Error, e.clone()),
+        Stop::Timeout => (
+            Status::Timeout,
+            format!("timed out after {:?}", limits.timeout),
+        ),
+        Stop::CycleLimit => (
+            Status::CycleLimit,
+            format!("exceeded {} cycles", limits.cycles),
+        ),
+    }
+}
+
+// compares the output of the student's assembler with ours
+fn grade_hack(name: &str, asm: &Path, submitted: &Path) -> TestResult {
+    let expected = match fs::read_to_string(asm)
+        .map_err(|e| e.to_string())
+        .and_then(|source| toolchain::assemble(&source))
+    {
+        Ok(expected) => expected,
+        Err(e) => return TestResult::error(name, format!("{}: {}", asm.display(), e)),
+    };
+
+    let hack = submitted.with_extension("hack");
+    let Ok(source) = fs::read_to_string(&hack) else {
+        return TestResult::missing(name, format!("{} not found", hack.display()));
+    };
+    let actual = match Cpu::parse_hack(&source) {
+        Ok(actual) => actual,
+        Err(e) => return TestResult::error(name, e),
+    };
+
+    let matched = expected.iter().zip(&actual).filter(|(a, b)| a == b).count();
+    let first = expected
+        .iter()
+        .zip(&actual)
+        .position(|(a, b)| a != b)
+        .unwrap_or(matched);
+    TestResult::compared(name, matched, expected.len().max(actual.len()), || {
+        format!("instruction {} differs", first + 1)
+    })
+}
+
+// compares the student's parser output with the reference, ignoring indentation
+fn grade_xml(name: &str, reference: &Path, submitted: &Path) -> TestResult {
+    let lines = |text: String| {
+        text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+    };
+
+    let expected = lines(fs::read_to_string(reference).unwrap_or_default());
+    let Ok(actual) = fs::read_to_string(submitted).map(lines) else {
+        return TestResult::missing(name, format!("{} not found", submitted.display()));
+    };
+
+    let matched = expected.iter().zip(&actual).filter(|(a, b)| a == b).count();
+    let first = expected
+        .iter()
+        .zip(&actual)
+        .position(|(a, b)| a != b)
+        .unwrap_or(matched);
+    TestResult::compared(name, matched, expected.len().max(actual.len()), || {
+        format!("line {} differs", first + 1)
+    })
+}
+
+fn read_modules(dir: &Path, extension: &str) -> Result<Vec<(String, String)>, String> {
+    files(dir, extension, false)
+        .into_iter()
+        .map(|path| {
+            let name = path.file_stem().unwrap().to_string_lossy().to_string();
+            fs::read_to_string(&path)
+                .map(|source| (name, source))
+                .map_err(|e| format!("{}: {}", path.display(), e))
+        })
+        .collect()
+}
+
+fn compile_modules(sources: Vec<(String, String)>) -> Result<Vec<(String, String)>, String> {
+    sources
+        .into_iter()
+        .map(|(name, source)| {
+            toolchain::compile(&source)
+                .map(|vm| (name.clone(), vm))
+                .map_err(|e| format!("{}.jack: {}", name, e))
+        })
+        .collect()
+}
+
+// adds the operating system classes the program does not define itself
+fn link_os(
+    modules: Vec<(String, String)>,
+    config: &Config,
+) -> Result<Vec<(String, String)>, String> {
+    Ok(link(modules, read_modules(&config.os, "vm")?))
+}
+
+fn link(mut modules: Vec<(String, String)>, os: Vec<(String, String)>) -> Vec<(String, String)> {
+    for (name, source) in os {
+        if !modules.iter().any(|(module, _)| *module == name) {
+            modules.push((name, source));
+        }
+    }
+    modules
+}
+
+// translates a program linked with the OS through vm-to-asm, and loads it into the CPU emulator
+fn load(modules: &[(String, String)]) -> Result<Cpu, String> {
+    let program = toolchain::translate(modules).and_then(|asm| toolchain::assemble(&asm))?;
+    if program.len() > ROM_SIZE {
+        return Err(format!(
+            "the program takes {} words of ROM, which holds {}",
+            program.len(),
+            ROM_SIZE
+        ));
+    }
+    let mut cpu = Cpu::new();
+    cpu.load(&program);
+    Ok(cpu)
+}
+
+// runs a program until it is idle, in slices of cycles so that the timeout is checked as well
+fn run(cpu: &mut Cpu, limits: Limits, started: Instant) -> Result<(), Stop> {
+    const SLICE: u64 = 1_000_000;
+    while cpu.cycles < limits.cycles {
+        if started.elapsed() > limits.timeout {
+            return Err(Stop::Timeout);
+        }
+        if cpu.run_until_idle(SLICE.min(limits.cycles - cpu.cycles)) {
+            return Ok(());
+        }
+    }
+    Err(Stop::CycleLimit)
+}
+
+// a Jack program of project 09 passes if it compiles and links with the OS
+fn grade_program(name: &str, program: &Path, config: &Config) -> TestResult {
+    let started = Instant::now();
+    let result = read_modules(program, "jack")
+        .and_then(compile_modules)
+        .and_then(|modules| link_os(modules, config))
+        .and_then(|modules| load(&modules));
+
+    match result {
+        Ok(mut cpu) => {
+            // interactive programs never go idle, and pass once they ran into the limits
+            let _ = run(&mut cpu, config.limits, started);
+            let mut result = TestResult::passed(name);
+            result.cycles = cpu.cycles;
+            result.millis = started.elapsed().as_millis();
+            result
+        }
+        Err(e) => TestResult::error(name, e),
+    }
+}
+
+// runs the VM code the student's compiler produced side by side with the code of ours
+fn grade_compiler(name: &str, program: &Path, submitted: &Path, config: &Config) -> TestResult {
+    let started = Instant::now();
+    let reference = match read_modules(program, "jack")
+        .and_then(compile_modules)
+        .and_then(|modules| link_os(modules, config))
+        .and_then(|modules| load(&modules))
+    {
+        Ok(cpu) => cpu,
+        Err(e) => return TestResult::error(name, format!("reference: {}", e)),
+    };
+
+    let modules = match read_modules(submitted, "vm") {
+        Ok(modules) if !modules.is_empty() => modules,
+        _ => return TestResult::missing(name, format!("no .vm files in {}", submitted.display())),
+    };
+    let actual = match link_os(modules, config).and_then(|modules| load(&modules)) {
+        Ok(cpu) => cpu,
+        Err(e) => return TestResult::error(name, e),
+    };
+
+    // programs that wait for the keyboard are compared once they ran into the cycle limit
+    let mut cycles = 0;
+    let mut timeout = false;
+    let [expected, actual] = [reference, actual].map(|mut cpu| {
+        cpu.ram[DATA.start] = DATA_SEED;
+        timeout |= run(&mut cpu, config.limits, started) == Err(Stop::Timeout);
+        cycles += cpu.cycles;
+        cpu.ram
+    });
+
+    let differs = |range: std::ops::Range<usize>| expected[range.clone()] != actual[range];
+    let mut result = if timeout {
+        let (status, message) = stopped(&Stop::Timeout, config.limits);
+        TestResult::new(name, status, 0.0, Some(message))
+    } else if differs(SCREEN) {
+        TestResult::new(
+            name,
+            Status::Failed,
+            0.0,
+            Some("the screen differs from the reference".to_string()),
+        )
+    } else if differs(DATA) {
+        let message = format!(
+            "RAM[{}..{}] differs from the reference",
+            DATA.start, DATA.end
+        );
+        TestResult::new(name, Status::Failed, 0.0, Some(message))
+    } else {
+        TestResult::passed(name)
+    };
+    result.cycles = cycles;
+    result.millis = started.elapsed().as_millis();
+    result
+}
+
+// runs the test of an OS class, e.g. MathTest, with the student's implementation of the class
+fn grade_os(name: &str, test: &Path, tst: &Path, dir: &Path, config: &Config) -> TestResult {
+    let class = name.strip_suffix("Test").unwrap_or(name);
+    let jack = dir.join(format!("{}.jack", class));
+    let Ok(source) = fs::read_to_string(&jack) else {
+        return TestResult::missing(name, format!("{} not found", jack.display()));
+    };
+
+    let modules = read_modules(test, "jack").and_then(|mut sources| {
+        sources.push((class.to_string(), source));
+        compile_modules(sources)
+    });
+    let modules = match modules {
+        Ok(modules) => modules,
+        Err(e) => return TestResult::error(name, e),
+    };
+
+    // the OS classes the test does not replace are built into the VM emulator
+    let own = modules.len();
+    let modules = match link_os(modules, config) {
+        Ok(modules) => modules,
+        Err(e) => return TestResult::error(name, e),
+    };
+    let builtin = modules[own..]
+        .iter()
+        .map(|(module, _)| module.clone())
+        .collect();
+    run_script(
+        name,
+        tst,
+        &mut VmTarget::new(modules, builtin),
+        config.limits,
+    )
+}
+
+/// Compiles the OS classes in `dir`, the `.jack` files of project 12, into VM code.
+pub fn build_os(dir: &Path) -> Result<Vec<(String, String)>, String> {
+    let modules = read_modules(dir, "jack")?;
+    if modules.is_empty() {
+        return Err(format!("no .jack files in {}", dir.display()));
+    }
+    compile_modules(modules)
+}
+
+/// Runs the test programs of project 12 on an OS built by `build_os`, with no class built in.
+///
+/// Tests with a script are compared with their `.cmp` file. The others only draw on the
+/// screen, which must match the screen of the same program on the OS of `config` once both
+/// went idle. Tests that wait for the keyboard are skipped.
+pub fn verify_os(os: &[(String, String)], config: &Config) -> Vec<TestResult> {
+    let tests = config.tests.join("12");
+    subdirs(&tests)
+        .into_iter()
+        .filter(|test| {
+            fs::read_to_string(test.join("Main.jack"))
+                .is_ok_and(|main| !main.contains("Keyboard.") && !main.contains("Sys.wait"))
+        })
+        .map(|test| {
+            let name = test_name(&tests, &test);
+            let program = match read_modules(&test, "jack").and_then(compile_modules) {
+                Ok(program) => program,
+                Err(e) => return TestResult::error(&name, e),
+            };
+            match files(&test, "tst", false).into_iter().next() {
+                Some(tst) => {
+                    let mut target = VmTarget::new(link(program, os.to_vec()), Vec::new());
+                    run_script(&name, &tst, &mut target, config.limits)
+                }
+                None => compare_screens(&name, program, os, config),
+            }
+        })
+        .collect()
+}
+
+// runs a program on the given OS and on the OS of `config`, and compares the screens
+fn compare_screens(
+    name: &str,
+    program: Vec<(String, String)>,
+    os: &[(String, String)],
+    config: &Config,
+) -> TestResult {
+    let started = Instant::now();
+    let reference = match link_os(program.clone(), config).and_then(|modules| load(&modules)) {
+        Ok(cpu) => cpu,
+        Err(e) => return TestResult::error(name, format!("reference: {}", e)),
+    };
+    let actual = match load(&link(program, os.to_vec())) {
+        Ok(cpu) => cpu,
+        Err(e) => return TestResult::error(name, e),
+    };
+
+    let mut cycles = 0;
+    let mut stop = None;
+    let [expected, actual] = [reference, actual].map(|mut cpu| {
+        if let Err(e) = run(&mut cpu, config.limits, started) {
+            stop.get_or_insert(e);
+        }
+        cycles += cpu.cycles;
+        cpu.ram
+    });
+
+    let matched = expected[SCREEN]
+        .iter()
+        .zip(&actual[SCREEN])
+        .filter(|(a, b)| a == b)
+        .count();
+    let mut result = if let Some(stop) = stop {
+        let (status, message) = stopped(&stop, config.limits);
+        TestResult::new(name, status, 0.0, Some(message))
+    } else {
+        TestResult::compared(name, matched, SCREEN.len(), || {
+            format!("{} screen words differ", SCREEN.len() - matched)
+        })
+    };
+    result.cycles = cycles;
+    result.millis = started.elapsed().as_millis();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn repository() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("..")
+    }
+
+    fn config(projects: Vec<String>) -> Config {
+        Config {
+            tests: repository().join("projects"),
+            os: repository().join("tools/OS"),
+            limits: Limits {
+                timeout: Duration::from_secs(60),
+                cycles: 100_000_000,
+            },
+            projects,
+        }
+    }
+
+    fn grade(project: &str) -> ProjectResult {
+        let config = config(vec![project.to_string()]);
+        // the solutions in this repository stand in for a submission
+        grade_student("solutions", &repository(), &config)
+            .projects
+            .remove(0)
+    }
+
+    fn assert_passed(result: &ProjectResult) {
+        for test in &result.tests {
+            assert_eq!(
+                test.status,
+                Status::Passed,
+                "{}: {:?}",
+                test.name,
+                test.message
+            );
+        }
+        assert_eq!(result.score(), 100.0);
+    }
+
+    #[test]
+    fn test_hardware() {
+        let result = grade("01");
+        assert_eq!(result.tests.len(), 15);
+        assert_passed(&result);
+        assert_passed(&grade("05"));
+    }
+
+    #[test]
+    fn test_programs() {
+        // the programs of project 04 in this repository are still the empty templates
+        let result = grade("04");
+        let names: Vec<&str> = result.tests.iter().map(|test| test.name.as_str()).collect();
+        assert_eq!(names, ["fill/FillAutomatic", "mult/Mult"]);
+        assert!(result
+            .tests
+            .iter()
+            .all(|test| test.status == Status::Failed));
+        // only the header and the lines of the white screen match
+        assert_eq!(result.tests[0].credit, 0.75);
+
+        assert_passed(&grade("06"));
+        assert_passed(&grade("08"));
+    }
+
+    #[test]
+    fn test_jack_programs() {
+        // translated through vm-to-asm and run on the CPU emulator
+        let result = grade("09");
+        assert_eq!(result.tests.len(), 5);
+        assert_passed(&result);
+        assert_passed(&grade("11"));
+    }
+
+    #[test]
+    fn test_os() {
+        let result = grade("12");
+        assert_eq!(result.tests.len(), 3);
+        assert_passed(&result);
+    }
+
+    #[test]
+    fn test_jack_os() {
+        let config = config(Vec::new());
+        let os = build_os(&repository().join("projects/12")).unwrap();
+        assert_eq!(os.len(), 8);
+
+        let result = ProjectResult {
+            project: "12".to_string(),
+            tests: verify_os(&os, &config),
+        };
+        let names: Vec<&str> = result.tests.iter().map(|test| test.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "ArrayTest",
+                "MathTest",
+                "MemoryTest",
+                "OutputTest",
+                "ScreenTest",
+                "StringTest"
+            ]
+        );
+        assert_passed(&result);
+    }
+
+    #[test]
+    fn test_screen_timeout() {
+        let mut config = config(Vec::new());
+        config.limits.timeout = Duration::ZERO;
+        let test = repository().join("projects/12/ScreenTest");
+        let program = read_modules(&test, "jack")
+            .and_then(compile_modules)
+            .unwrap();
+        let os = read_modules(&config.os, "vm").unwrap();
+
+        let result = compare_screens("ScreenTest", program, &os, &config);
+        assert_eq!(result.status, Status::Timeout);
+    }
+
+    #[test]
+    fn test_missing_project() {
+        let result = grade("13");
+        assert!(result.tests.is_empty());
+
+        let config = Config {
+            tests: repository().join("projects"),
+            os: repository().join("tools/OS"),
+            limits: Limits {
+                timeout: Duration::from_secs(1),
+                cycles: 1000,
+            },
+            projects: vec!["02".to_string()],
+        };
+        let result = grade_student("nobody", &repository().join("tools"), &config);
+        assert_eq!(result.score(), 0.0);
+        assert!(result.projects[0]
+            .tests
+            .iter()
+            .all(|test| test.status == Status::Missing));
+    }
+}
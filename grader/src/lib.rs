@@ -0,0 +1,5 @@
+pub mod grade;
+pub mod report;
+pub mod script;
+pub mod target;
+pub mod toolchain;
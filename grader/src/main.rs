@@ -0,0 +1,121 @@
+use std::fs;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use grader::grade::{build_os, grade_student, verify_os, Config, Status, PROJECTS};
+use grader::report;
+use grader::script::Limits;
+
+// removes `--name <value>` from the arguments, returning the value
+fn option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let i = args.iter().position(|arg| arg == name)?;
+    if i + 1 >= args.len() {
+        panic!("{} expects a value.", name);
+    }
+    let value = args.remove(i + 1);
+    args.remove(i);
+    Some(value)
+}
+
+// compiles the OS of project 12 and writes it into `bundle` once it passed the tests
+fn build_bundle(bundle: &Path, config: &Config) {
+    let os = build_os(&config.tests.join("12")).unwrap_or_else(|e| panic!("{}", e));
+    let results = verify_os(&os, config);
+    for result in &results {
+        match &result.message {
+            Some(message) => println!("{}: {} ({})", result.name, result.status.as_str(), message),
+            None => println!("{}: {}", result.name, result.status.as_str()),
+        }
+    }
+    if results.iter().any(|result| result.status != Status::Passed) {
+        panic!("The OS failed its tests.");
+    }
+
+    fs::create_dir_all(bundle).expect("Failed to create the output directory.");
+    for (name, vm) in &os {
+        fs::write(bundle.join(format!("{}.vm", name)), vm).expect("Failed to write the OS.");
+    }
+}
+
+fn main() {
+    let mut args: Vec<String> = std::env::args().collect();
+
+    // the course files of this repository are the default reference
+    let repository = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+    let tests = option(&mut args, "--tests").map_or(repository.join("projects"), PathBuf::from);
+    let os = option(&mut args, "--os").map_or(repository.join("tools/OS"), PathBuf::from);
+    let out = PathBuf::from(option(&mut args, "--out").unwrap_or("grader-report".to_string()));
+    let timeout = option(&mut args, "--timeout")
+        .map_or(30, |secs| secs.parse().expect("--timeout expects seconds."));
+    let cycles = option(&mut args, "--cycles").map_or(100_000_000, |n| {
+        n.parse().expect("--cycles expects a number.")
+    });
+    let build = option(&mut args, "--build-os").map(PathBuf::from);
+    let projects =
+        option(&mut args, "--projects").map_or(PROJECTS.map(str::to_string).to_vec(), |projects| {
+            projects
+                .split(',')
+                .map(|project| format!("{:0>2}", project.trim()))
+                .collect()
+        });
+
+    let config = Config {
+        tests,
+        os,
+        limits: Limits {
+            timeout: Duration::from_secs(timeout),
+            cycles,
+        },
+        projects,
+    };
+
+    if let Some(bundle) = build {
+        build_bundle(&bundle, &config);
+        return;
+    }
+
+    if args.len() < 2 {
+        println!(
+            "help: grader [--tests <dir>] [--os <dir>] [--out <dir>] [--projects 01,02,...] \
+             [--timeout <seconds>] [--cycles <n>] <submissions dir>"
+        );
+        println!(
+            "      grader [--tests <dir>] [--os <dir>] [--cycles <n>] --build-os <output dir>"
+        );
+        panic!("Please provide the directory of submissions.");
+    }
+
+    let mut students: Vec<PathBuf> = fs::read_dir(&args[1])
+        .unwrap_or_else(|_| panic!("{} is not a directory", args[1]))
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.is_dir() && !path.file_name().unwrap().to_string_lossy().starts_with('.')
+        })
+        .collect();
+    students.sort();
+
+    fs::create_dir_all(&out).expect("Failed to create the output directory.");
+
+    // errors in submissions surface as panics of the assembler and compiler, which are
+    // caught and reported per test
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+
+    let mut results = Vec::new();
+    for student in &students {
+        let name = student.file_name().unwrap().to_string_lossy().to_string();
+        let result = grade_student(&name, student, &config);
+        println!("{}: {:.1}", name, result.score());
+
+        fs::write(out.join(format!("{}.json", name)), report::json(&result))
+            .expect("Failed to write the report.");
+        results.push(result);
+    }
+
+    panic::set_hook(default_hook);
+    fs::write(out.join("summary.md"), report::markdown(&results))
+        .expect("Failed to write the summary.");
+    fs::write(out.join("summary.html"), report::html(&results))
+        .expect("Failed to write the summary.");
+}
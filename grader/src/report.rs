@@ -0,0 +1,255 @@
+use std::fmt::Write;
+
+use crate::grade::StudentResult;
+
+fn json_string(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len() + 2);
+    escaped.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(escaped, "\\u{:04x}", c as u32).unwrap(),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// Serializes the results of a student as JSON.
+pub fn json(result: &StudentResult) -> String {
+    let mut out = String::new();
+    writeln!(out, "{{").unwrap();
+    writeln!(out, "  \"student\": {},", json_string(&result.student)).unwrap();
+    writeln!(out, "  \"score\": {:.2},", result.score()).unwrap();
+    writeln!(out, "  \"projects\": [").unwrap();
+
+    for (i, project) in result.projects.iter().enumerate() {
+        writeln!(out, "    {{").unwrap();
+        writeln!(out, "      \"project\": {},", json_string(&project.project)).unwrap();
+        writeln!(out, "      \"score\": {:.2},", project.score()).unwrap();
+        writeln!(out, "      \"tests\": [").unwrap();
+
+        for (j, test) in project.tests.iter().enumerate() {
+            let message = test
+                .message
+                .as_deref()
+                .map_or("null".to_string(), json_string);
+            write!(
+                out,
+                "        {{\"name\": {}, \"status\": \"{}\", \"credit\": {:.4}, \"message\": {}, \"cycles\": {}, \"millis\": {}}}",
+                json_string(&test.name),
+                test.status.as_str(),
+                test.credit,
+                message,
+                test.cycles,
+                test.millis
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "{}",
+                if j + 1 < project.tests.len() { "," } else { "" }
+            )
+            .unwrap();
+        }
+
+        writeln!(out, "      ]").unwrap();
+        writeln!(
+            out,
+            "    }}{}",
+            if i + 1 < result.projects.len() {
+                ","
+            } else {
+                ""
+            }
+        )
+        .unwrap();
+    }
+
+    writeln!(out, "  ]").unwrap();
+    writeln!(out, "}}").unwrap();
+    out
+}
+
+/// Summarizes the results of all students as a Markdown document.
+pub fn markdown(results: &[StudentResult]) -> String {
+    let mut out = String::new();
+    writeln!(out, "# Grading Summary").unwrap();
+    writeln!(out).unwrap();
+
+    let projects: Vec<&str> = results
+        .first()
+        .map(|result| {
+            result
+                .projects
+                .iter()
+                .map(|project| project.project.as_str())
+                .collect()
+        })
+        .unwrap_or_default();
+    writeln!(out, "| Student | {} | Total |", projects.join(" | ")).unwrap();
+    writeln!(out, "|---|{}---|", "---|".repeat(projects.len())).unwrap();
+    for result in results {
+        let scores: Vec<String> = result
+            .projects
+            .iter()
+            .map(|project| format!("{:.1}", project.score()))
+            .collect();
+        writeln!(
+            out,
+            "| {} | {} | {:.1} |",
+            result.student,
+            scores.join(" | "),
+            result.score()
+        )
+        .unwrap();
+    }
+
+    for result in results {
+        let failures: Vec<String> = result
+            .projects
+            .iter()
+            .flat_map(|project| {
+                project
+                    .tests
+                    .iter()
+                    .filter(|test| test.credit < 1.0)
+                    .map(move |test| {
+                        format!(
+                            "- {}/{}: {} ({})",
+                            project.project,
+                            test.name,
+                            test.status.as_str(),
+                            test.message.as_deref().unwrap_or("")
+                        )
+                    })
+            })
+            .collect();
+        if failures.is_empty() {
+            continue;
+        }
+
+        writeln!(out).unwrap();
+        writeln!(out, "## {}", result.student).unwrap();
+        writeln!(out).unwrap();
+        for failure in failures {
+            writeln!(out, "{}", failure).unwrap();
+        }
+    }
+
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Summarizes the results of all students as an HTML page.
+pub fn html(results: &[StudentResult]) -> String {
+    let mut out = String::new();
+    writeln!(out, "<!DOCTYPE html>").unwrap();
+    writeln!(
+        out,
+        "<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Grading Summary</title>"
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "<style>\ntable {{ border-collapse: collapse; }}\ntd, th {{ border: 1px solid #999; padding: 2px 8px; text-align: right; }}\n\
+         td:first-child {{ text-align: left; }}\n.full {{ background: #cfc; }}\n.partial {{ background: #ffc; }}\n.none {{ background: #fcc; }}\n</style>"
+    )
+    .unwrap();
+    writeln!(out, "</head>\n<body>\n<h1>Grading Summary</h1>\n<table>").unwrap();
+
+    let projects: Vec<&str> = results
+        .first()
+        .map(|result| {
+            result
+                .projects
+                .iter()
+                .map(|project| project.project.as_str())
+                .collect()
+        })
+        .unwrap_or_default();
+    write!(out, "<tr><th>Student</th>").unwrap();
+    for project in &projects {
+        write!(out, "<th>{}</th>", project).unwrap();
+    }
+    writeln!(out, "<th>Total</th></tr>").unwrap();
+
+    for result in results {
+        write!(out, "<tr><td>{}</td>", escape_html(&result.student)).unwrap();
+        for project in &result.projects {
+            let score = project.score();
+            let class = if score >= 100.0 {
+                "full"
+            } else if score > 0.0 {
+                "partial"
+            } else {
+                "none"
+            };
+            let failures: Vec<String> = project
+                .tests
+                .iter()
+                .filter(|test| test.credit < 1.0)
+                .map(|test| {
+                    format!(
+                        "{}: {}",
+                        test.name,
+                        test.message.as_deref().unwrap_or(test.status.as_str())
+                    )
+                })
+                .collect();
+            write!(
+                out,
+                "<td class=\"{}\" title=\"{}\">{:.1}</td>",
+                class,
+                escape_html(&failures.join("\n")),
+                score
+            )
+            .unwrap();
+        }
+        writeln!(out, "<td>{:.1}</td></tr>", result.score()).unwrap();
+    }
+
+    writeln!(out, "</table>\n</body>\n</html>").unwrap();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grade::{ProjectResult, Status, TestResult};
+
+    #[test]
+    fn test_json() {
+        let result = StudentResult {
+            student: "ada".to_string(),
+            projects: vec![ProjectResult {
+                project: "01".to_string(),
+                tests: vec![TestResult {
+                    name: "Not".to_string(),
+                    status: Status::Failed,
+                    credit: 0.5,
+                    message: Some("comparison failure at line \"2\"".to_string()),
+                    cycles: 0,
+                    millis: 3,
+                }],
+            }],
+        };
+
+        assert_eq!(
+            json(&result),
+            "{\n  \"student\": \"ada\",\n  \"score\": 50.00,\n  \"projects\": [\n    {\n      \"project\": \"01\",\n      \"score\": 50.00,\n      \"tests\": [\n        \
+             {\"name\": \"Not\", \"status\": \"failed\", \"credit\": 0.5000, \"message\": \"comparison failure at line \\\"2\\\"\", \"cycles\": 0, \"millis\": 3}\n      ]\n    }\n  ]\n}\n"
+        );
+        assert!(markdown(&[result]).contains("| ada | 50.0 | 50.0 |"));
+    }
+}
@@ -0,0 +1,585 @@
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// A statement of a test script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Statement {
+    Command(Vec<String>),
+    /// `repeat n { ... }`, or `repeat { ... }` which repeats until the script is stopped.
+    Repeat(Option<u64>, Vec<Statement>),
+    While(Condition, Vec<Statement>),
+}
+
+/// The condition of a `while` loop, e.g. `out <> 75`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    pub name: String,
+    pub op: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    // ',', ';', '!', '{' or '}'
+    Symbol(char),
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '/' if chars.peek() == Some(&'/') => {
+                while chars.peek().is_some_and(|c| *c != '\n') {
+                    chars.next();
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut last = ' ';
+                loop {
+                    match chars.next() {
+                        Some('/') if last == '*' => break,
+                        Some(c) => last = c,
+                        None => return Err("unterminated comment".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => text.push(c),
+                        None => return Err("unterminated string".to_string()),
+                    }
+                }
+                tokens.push(Token::Word(text));
+            }
+            ',' | ';' | '!' | '{' | '}' => tokens.push(Token::Symbol(c)),
+            c => {
+                let mut word = c.to_string();
+                while let Some(c) = chars
+                    .peek()
+                    .filter(|c| !c.is_whitespace() && !",;!{}\"".contains(**c))
+                {
+                    word.push(*c);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Parses a `.tst` script of the hardware simulator, CPU emulator or VM emulator.
+pub fn parse(source: &str) -> Result<Vec<Statement>, String> {
+    let tokens = tokenize(source)?;
+    let mut pos = 0;
+    let statements = block(&tokens, &mut pos)?;
+    if pos < tokens.len() {
+        return Err("unexpected '}'".to_string());
+    }
+    Ok(statements)
+}
+
+fn block(tokens: &[Token], pos: &mut usize) -> Result<Vec<Statement>, String> {
+    let mut statements = Vec::new();
+
+    while *pos < tokens.len() {
+        let mut words = Vec::new();
+        let terminator = loop {
+            match tokens.get(*pos) {
+                Some(Token::Word(word)) => words.push(word.clone()),
+                Some(Token::Symbol(symbol)) => break Some(*symbol),
+                None => break None,
+            }
+            *pos += 1;
+        };
+
+        match (words.first().map(String::as_str), terminator) {
+            (None, Some('}')) => break,
+            (Some("repeat"), Some('{')) => {
+                *pos += 1;
+                let count = match words.get(1) {
+                    Some(count) => Some(
+                        count
+                            .parse()
+                            .map_err(|_| format!("invalid repeat count {}", count))?,
+                    ),
+                    None => None,
+                };
+                let body = block(tokens, pos)?;
+                expect_close(tokens, pos)?;
+                statements.push(Statement::Repeat(count, body));
+            }
+            (Some("while"), Some('{')) => {
+                *pos += 1;
+                let [_, name, op, value] = words.as_slice() else {
+                    return Err(format!(
+                        "invalid while condition '{}'",
+                        words[1..].join(" ")
+                    ));
+                };
+                let condition = Condition {
+                    name: name.clone(),
+                    op: op.clone(),
+                    value: value.clone(),
+                };
+                let body = block(tokens, pos)?;
+                expect_close(tokens, pos)?;
+                statements.push(Statement::While(condition, body));
+            }
+            (_, Some('{')) => return Err(format!("unexpected '{{' after '{}'", words.join(" "))),
+            (_, Some('}')) => {
+                // the last command of a block may omit its terminator
+                statements.push(Statement::Command(words));
+                break;
+            }
+            (Some(_), _) => {
+                *pos += 1;
+                statements.push(Statement::Command(words));
+            }
+            (None, _) => *pos += 1,
+        }
+    }
+
+    Ok(statements)
+}
+
+fn expect_close(tokens: &[Token], pos: &mut usize) -> Result<(), String> {
+    if tokens.get(*pos) == Some(&Token::Symbol('}')) {
+        *pos += 1;
+        Ok(())
+    } else {
+        Err("missing '}'".to_string())
+    }
+}
+
+/// Parses a value as written in scripts: decimal, or `%B`, `%X` and `%D` prefixed.
+pub fn parse_value(text: &str) -> Result<u16, String> {
+    let invalid = || format!("invalid value {}", text);
+    let value = match text.get(..2) {
+        Some("%B") => i32::from_str_radix(&text[2..], 2).map_err(|_| invalid())?,
+        Some("%X") => i32::from_str_radix(&text[2..], 16).map_err(|_| invalid())?,
+        Some("%D") => text[2..].parse().map_err(|_| invalid())?,
+        _ => text.parse().map_err(|_| invalid())?,
+    };
+    if !(-32768..=65535).contains(&value) {
+        return Err(invalid());
+    }
+    Ok(value as u16)
+}
+
+/// A value read from the simulated machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    /// A pin or register value and its width in bits.
+    Word(u16, u8),
+    Text(String),
+}
+
+/// The machine a script is run against.
+pub trait Target {
+    fn load(&mut self, file: Option<&str>) -> Result<(), String>;
+    fn get(&mut self, name: &str) -> Result<Value, String>;
+    fn set(&mut self, name: &str, value: u16) -> Result<(), String>;
+    /// Runs a target-specific command, returning the number of clock cycles it took.
+    fn command(&mut self, words: &[String]) -> Result<u64, String>;
+    /// Called with the message of `echo`, and with `None` on `clear-echo`.
+    fn echo(&mut self, _message: Option<&str>) {}
+}
+
+// an entry of `output-list`, e.g. `RAM[0]%D2.6.2`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Column {
+    name: String,
+    format: char,
+    left: usize,
+    width: usize,
+    right: usize,
+}
+
+impl Column {
+    fn parse(spec: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid output format {}", spec);
+        let (name, format) = spec.split_once('%').ok_or_else(invalid)?;
+        let mut chars = format.chars();
+        let kind = chars
+            .next()
+            .filter(|c| "BDXS".contains(*c))
+            .ok_or_else(invalid)?;
+        let numbers = chars
+            .as_str()
+            .split('.')
+            .map(|n| n.parse::<usize>().map_err(|_| invalid()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let [left, width, right] = numbers[..] else {
+            return Err(invalid());
+        };
+
+        Ok(Column {
+            name: name.to_string(),
+            format: kind,
+            left,
+            width,
+            right,
+        })
+    }
+
+    fn header(&self) -> String {
+        let space = self.left + self.width + self.right;
+        let name: String = self.name.chars().take(space).collect();
+        let before = (space - name.len()) / 2;
+        format!(
+            "{}{}{}",
+            " ".repeat(before),
+            name,
+            " ".repeat(space - name.len() - before)
+        )
+    }
+
+    fn cell(&self, value: &Value) -> String {
+        let text = match (value, self.format) {
+            (Value::Text(text), _) => format!("{:<width$}", text, width = self.width),
+            (Value::Word(value, bits), 'D') => {
+                let number = if *bits == 16 {
+                    *value as i16 as i32
+                } else {
+                    *value as i32
+                };
+                format!("{:>width$}", number, width = self.width)
+            }
+            (Value::Word(value, _), 'B') => digits(&format!("{:016b}", value), self.width),
+            (Value::Word(value, _), 'X') => digits(&format!("{:04X}", value), self.width),
+            (Value::Word(value, _), _) => format!("{:<width$}", value, width = self.width),
+        };
+        format!(
+            "{}{}{}",
+            " ".repeat(self.left),
+            text,
+            " ".repeat(self.right)
+        )
+    }
+}
+
+// the last `width` digits of a zero-padded number
+fn digits(number: &str, width: usize) -> String {
+    if width >= number.len() {
+        format!("{:0>width$}", number, width = width)
+    } else {
+        number[number.len() - width..].to_string()
+    }
+}
+
+// compares an output line with the expected one, where '*' matches any character
+fn line_matches(output: &str, expected: &str) -> bool {
+    let output = output.trim_end();
+    let expected = expected.trim_end();
+    output.len() == expected.len()
+        && output
+            .chars()
+            .zip(expected.chars())
+            .all(|(out, exp)| exp == '*' || out == exp)
+}
+
+/// Resource limits of a single test.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    pub timeout: Duration,
+    pub cycles: u64,
+}
+
+/// Why a script stopped before reaching its end.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stop {
+    Error(String),
+    Timeout,
+    CycleLimit,
+}
+
+/// The result of running a script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    /// Lines the script wrote to its output file.
+    pub output: Vec<String>,
+    /// Lines of the compare file, if the script has one.
+    pub expected: Option<Vec<String>>,
+    /// Number of output lines that match the compare file.
+    pub matched: usize,
+    /// 1-based number of the first output line that differs from the compare file.
+    pub first_mismatch: Option<usize>,
+    pub cycles: u64,
+    pub stop: Option<Stop>,
+}
+
+impl Outcome {
+    /// Returns `true` if the script ran to completion and its output equals the compare file.
+    pub fn passed(&self) -> bool {
+        match &self.expected {
+            Some(expected) => {
+                self.stop.is_none()
+                    && self.first_mismatch.is_none()
+                    && self.output.len() == expected.len()
+            }
+            None => false,
+        }
+    }
+
+    /// Fraction of the compare file that was matched.
+    pub fn credit(&self) -> f64 {
+        match &self.expected {
+            Some(_) if self.passed() => 1.0,
+            Some(expected) => {
+                self.matched as f64 / expected.len().max(self.output.len()).max(1) as f64
+            }
+            None => 0.0,
+        }
+    }
+}
+
+/// Runs a parsed script against a target. Files named by `compare-to` are read from `dir`.
+pub struct Runner<'a, T: Target> {
+    target: &'a mut T,
+    dir: &'a Path,
+    limits: Limits,
+    started: Instant,
+    columns: Vec<Column>,
+    outcome: Outcome,
+}
+
+impl<'a, T: Target> Runner<'a, T> {
+    pub fn new(target: &'a mut T, dir: &'a Path, limits: Limits) -> Self {
+        Runner {
+            target,
+            dir,
+            limits,
+            started: Instant::now(),
+            columns: Vec::new(),
+            outcome: Outcome {
+                output: Vec::new(),
+                expected: None,
+                matched: 0,
+                first_mismatch: None,
+                cycles: 0,
+                stop: None,
+            },
+        }
+    }
+
+    pub fn run(mut self, statements: &[Statement]) -> Outcome {
+        self.started = Instant::now();
+        if let Err(stop) = self.block(statements) {
+            self.outcome.stop = Some(stop);
+        }
+        self.outcome
+    }
+
+    fn block(&mut self, statements: &[Statement]) -> Result<(), Stop> {
+        for statement in statements {
+            match statement {
+                Statement::Command(words) => self.command(words)?,
+                Statement::Repeat(count, body) => {
+                    let mut i = 0;
+                    while count.is_none_or(|count| i < count) {
+                        self.block(body)?;
+                        i += 1;
+                    }
+                }
+                Statement::While(condition, body) => {
+                    while self.holds(condition)? {
+                        self.block(body)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn holds(&mut self, condition: &Condition) -> Result<bool, Stop> {
+        let left = match self.target.get(&condition.name).map_err(Stop::Error)? {
+            Value::Word(value, 16) => value as i16 as i32,
+            Value::Word(value, _) => value as i32,
+            Value::Text(_) => {
+                return Err(Stop::Error(format!("{} is not a number", condition.name)))
+            }
+        };
+        let right = parse_value(&condition.value).map_err(Stop::Error)? as i16 as i32;
+
+        match condition.op.as_str() {
+            "=" => Ok(left == right),
+            "<>" => Ok(left != right),
+            "<" => Ok(left < right),
+            ">" => Ok(left > right),
+            "<=" => Ok(left <= right),
+            ">=" => Ok(left >= right),
+            op => Err(Stop::Error(format!("unknown operator {}", op))),
+        }
+    }
+
+    fn command(&mut self, words: &[String]) -> Result<(), Stop> {
+        let arg = |n: usize| {
+            words
+                .get(n)
+                .map(String::as_str)
+                .ok_or_else(|| Stop::Error(format!("'{}' is missing an argument", words[0])))
+        };
+
+        match words[0].as_str() {
+            "load" => self
+                .target
+                .load(words.get(1).map(String::as_str))
+                .map_err(Stop::Error)?,
+            "output-file" | "breakpoint" | "clear-breakpoints" => {}
+            "compare-to" => {
+                let path = self.dir.join(arg(1)?);
+                let expected = fs::read_to_string(&path)
+                    .map_err(|e| Stop::Error(format!("{}: {}", path.display(), e)))?;
+                self.outcome.expected = Some(expected.lines().map(str::to_string).collect());
+            }
+            "output-list" => {
+                self.columns = words[1..]
+                    .iter()
+                    .map(|spec| Column::parse(spec))
+                    .collect::<Result<_, _>>()
+                    .map_err(Stop::Error)?;
+                let header = self.columns.iter().map(Column::header).collect::<Vec<_>>();
+                self.write(format!("|{}|", header.join("|")));
+            }
+            "output" => {
+                let mut cells = Vec::new();
+                for column in self.columns.clone() {
+                    let value = self.target.get(&column.name).map_err(Stop::Error)?;
+                    cells.push(column.cell(&value));
+                }
+                self.write(format!("|{}|", cells.join("|")));
+            }
+            "echo" => self.target.echo(Some(arg(1)?)),
+            "clear-echo" => self.target.echo(None),
+            "set" => {
+                let value = parse_value(arg(2)?).map_err(Stop::Error)?;
+                self.target.set(arg(1)?, value).map_err(Stop::Error)?;
+            }
+            _ => self.outcome.cycles += self.target.command(words).map_err(Stop::Error)?,
+        }
+
+        if self.outcome.cycles > self.limits.cycles {
+            return Err(Stop::CycleLimit);
+        }
+        if self.started.elapsed() > self.limits.timeout {
+            return Err(Stop::Timeout);
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, line: String) {
+        let number = self.outcome.output.len() + 1;
+        let matches = self
+            .outcome
+            .expected
+            .as_ref()
+            .and_then(|expected| expected.get(number - 1))
+            .is_some_and(|expected| line_matches(&line, expected));
+
+        if matches {
+            self.outcome.matched += 1;
+        } else if self.outcome.first_mismatch.is_none() {
+            self.outcome.first_mismatch = Some(number);
+        }
+        self.outcome.output.push(line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let statements = parse(
+            "load And.hdl, // the chip\n\
+             output-list a%B3.1.3;\n\
+             repeat 2 { set a 1, tick, tock; }\n\
+             while out <> 75 { tick, tock }\n\
+             echo \"hold it, please\";",
+        )
+        .unwrap();
+
+        assert_eq!(
+            statements,
+            vec![
+                Statement::Command(vec!["load".to_string(), "And.hdl".to_string()]),
+                Statement::Command(vec!["output-list".to_string(), "a%B3.1.3".to_string()]),
+                Statement::Repeat(
+                    Some(2),
+                    vec![
+                        Statement::Command(vec![
+                            "set".to_string(),
+                            "a".to_string(),
+                            "1".to_string()
+                        ]),
+                        Statement::Command(vec!["tick".to_string()]),
+                        Statement::Command(vec!["tock".to_string()]),
+                    ]
+                ),
+                Statement::While(
+                    Condition {
+                        name: "out".to_string(),
+                        op: "<>".to_string(),
+                        value: "75".to_string()
+                    },
+                    vec![
+                        Statement::Command(vec!["tick".to_string()]),
+                        Statement::Command(vec!["tock".to_string()]),
+                    ]
+                ),
+                Statement::Command(vec!["echo".to_string(), "hold it, please".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_value() {
+        assert_eq!(parse_value("-1"), Ok(0xffff));
+        assert_eq!(parse_value("%B0101"), Ok(5));
+        assert_eq!(parse_value("%X2000"), Ok(0x2000));
+        assert_eq!(parse_value("%D-32768"), Ok(0x8000));
+        assert!(parse_value("40000000").is_err());
+    }
+
+    #[test]
+    fn test_columns() {
+        let column = Column::parse("a%B1.16.1").unwrap();
+        assert_eq!(column.header(), "        a         ");
+        assert_eq!(column.cell(&Value::Word(0x1234, 16)), " 0001001000110100 ");
+
+        let column = Column::parse("DRegister[]%D1.6.1").unwrap();
+        assert_eq!(column.header(), "DRegiste");
+        assert_eq!(column.cell(&Value::Word(0xffff, 16)), "     -1 ");
+
+        let column = Column::parse("time%S1.4.1").unwrap();
+        assert_eq!(column.cell(&Value::Text("0+".to_string())), " 0+   ");
+
+        let column = Column::parse("address%B1.15.1").unwrap();
+        assert_eq!(column.cell(&Value::Word(0x4000, 15)), " 100000000000000 ");
+    }
+
+    #[test]
+    fn test_line_matches() {
+        assert!(line_matches(
+            "|1   |     0|*******|",
+            "|1   |     0|*******|"
+        ));
+        assert!(line_matches(
+            "|1   |     0|     -1|",
+            "|1   |     0|*******|"
+        ));
+        assert!(!line_matches("|1   |     1|", "|1   |     0|"));
+    }
+}
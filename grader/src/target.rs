@@ -0,0 +1,345 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use emulator::cpu::Cpu;
+use emulator::hdl::chip::{Chip, Loader};
+use emulator::vm::Vm;
+
+use crate::script::{Target, Value};
+use crate::toolchain;
+
+// splits `name[index]` into its name and index. `name[]` refers to index 0.
+fn indexed(name: &str) -> Option<(&str, usize)> {
+    let (name, index) = name.strip_suffix(']')?.split_once('[')?;
+    let index = if index.is_empty() {
+        0
+    } else {
+        index.parse().ok()?
+    };
+    Some((name, index))
+}
+
+fn unknown<T>(name: &str) -> Result<T, String> {
+    Err(format!("unknown variable {}", name))
+}
+
+/// Runs hardware simulator scripts against chips built from HDL files.
+pub struct HardwareTarget {
+    loader: Loader,
+    // directory of files loaded into ROM
+    files: PathBuf,
+    chip: Option<Chip>,
+    // values of the input pins followed by the output pins
+    pins: Vec<u16>,
+    time: u64,
+    ticked: bool,
+}
+
+impl HardwareTarget {
+    /// Chips are loaded from `chips`, memory images from `files`.
+    pub fn new(chips: &Path, files: &Path) -> Self {
+        HardwareTarget {
+            loader: Loader::new(vec![chips.to_path_buf()]),
+            files: files.to_path_buf(),
+            chip: None,
+            pins: Vec::new(),
+            time: 0,
+            ticked: false,
+        }
+    }
+
+    fn chip(&mut self) -> Result<&mut Chip, String> {
+        self.chip.as_mut().ok_or("no chip loaded".to_string())
+    }
+
+    fn eval(&mut self) -> Result<(), String> {
+        let chip = self.chip.as_mut().ok_or("no chip loaded".to_string())?;
+        let (inputs, outputs) = self.pins.split_at_mut(chip.inputs().len());
+        chip.eval(inputs, outputs);
+        Ok(())
+    }
+
+    fn pin(&self, name: &str) -> Option<(usize, u8)> {
+        let chip = self.chip.as_ref()?;
+        chip.inputs()
+            .iter()
+            .chain(chip.outputs())
+            .enumerate()
+            .find(|(_, pin)| pin.name == name)
+            .map(|(i, pin)| (i, pin.width))
+    }
+}
+
+impl Target for HardwareTarget {
+    fn load(&mut self, file: Option<&str>) -> Result<(), String> {
+        let file = file.ok_or("load expects a chip".to_string())?;
+        let name = file.strip_suffix(".hdl").unwrap_or(file);
+        let chip = self.loader.load_hdl(name)?;
+
+        self.pins = vec![0; chip.inputs().len() + chip.outputs().len()];
+        self.chip = Some(chip);
+        self.time = 0;
+        self.ticked = false;
+        Ok(())
+    }
+
+    fn get(&mut self, name: &str) -> Result<Value, String> {
+        if name == "time" {
+            return Ok(Value::Text(format!(
+                "{}{}",
+                self.time,
+                if self.ticked { "+" } else { "" }
+            )));
+        }
+        if let Some((i, width)) = self.pin(name) {
+            return Ok(Value::Word(self.pins[i], width));
+        }
+        let Some((part, index)) = indexed(name) else {
+            return unknown(name);
+        };
+        match self
+            .chip()?
+            .find_builtin(part)
+            .and_then(|part| part.state(index))
+        {
+            Some(value) => Ok(Value::Word(value, 16)),
+            None => unknown(name),
+        }
+    }
+
+    fn set(&mut self, name: &str, value: u16) -> Result<(), String> {
+        let inputs = self.chip()?.inputs().len();
+        if let Some((i, width)) = self.pin(name) {
+            if i >= inputs {
+                return Err(format!("cannot set output pin {}", name));
+            }
+            self.pins[i] = value & (0xffff >> (16 - width));
+            return Ok(());
+        }
+
+        let Some((part, index)) = indexed(name) else {
+            return unknown(name);
+        };
+        let chip = self.chip()?;
+        if !chip
+            .find_builtin(part)
+            .is_some_and(|part| part.set_state(index, value))
+        {
+            return unknown(name);
+        }
+        chip.invalidate();
+        Ok(())
+    }
+
+    fn command(&mut self, words: &[String]) -> Result<u64, String> {
+        match words {
+            [command] if command == "eval" => {
+                self.eval()?;
+                Ok(0)
+            }
+            [command] if command == "tick" => {
+                self.eval()?;
+                let chip = self.chip.as_mut().unwrap();
+                chip.tick(&self.pins[..chip.inputs().len()]);
+                self.ticked = true;
+                Ok(0)
+            }
+            [command] if command == "tock" => {
+                self.chip()?.tock();
+                self.eval()?;
+                self.time += 1;
+                self.ticked = false;
+                Ok(1)
+            }
+            [part, command, file] if command == "load" => {
+                let path = self.files.join(file);
+                let source =
+                    fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+                let words = Cpu::parse_hack(&source).map_err(|e| format!("{}, {}", file, e))?;
+                let chip = self.chip()?;
+                if !chip
+                    .find_builtin(part)
+                    .is_some_and(|builtin| builtin.load(&words))
+                {
+                    return Err(format!("cannot load {} into {}", file, part));
+                }
+                chip.invalidate();
+                Ok(0)
+            }
+            _ => Err(format!("unsupported command '{}'", words.join(" "))),
+        }
+    }
+
+    // stands in for the user when a script asks to hold down a key
+    fn echo(&mut self, message: Option<&str>) {
+        // e.g. "hold down the 'K' key" or "Hold down 'Y'"
+        let key = message
+            .and_then(|message| {
+                message
+                    .find("hold down")
+                    .or_else(|| message.find("Hold down"))
+                    .map(|i| &message[i..])
+            })
+            .and_then(|rest| rest.split_once('\''))
+            .and_then(|(_, rest)| rest.chars().next())
+            .map_or(0, |key| key as u16);
+
+        if let Some(chip) = self.chip.as_mut() {
+            if let Some(keyboard) = chip.find_builtin("Keyboard") {
+                keyboard.set_state(0, key);
+                chip.invalidate();
+            }
+        }
+    }
+}
+
+/// Runs CPU emulator scripts against programs assembled from `.asm` files.
+pub struct CpuTarget {
+    cpu: Cpu,
+    // directory of the programs to load
+    dir: PathBuf,
+    time: u64,
+}
+
+impl CpuTarget {
+    pub fn new(dir: &Path) -> Self {
+        CpuTarget {
+            cpu: Cpu::new(),
+            dir: dir.to_path_buf(),
+            time: 0,
+        }
+    }
+}
+
+impl Target for CpuTarget {
+    fn load(&mut self, file: Option<&str>) -> Result<(), String> {
+        let file = file.ok_or("load expects a program".to_string())?;
+        let path = self.dir.join(file);
+        let source = fs::read_to_string(&path).map_err(|_| format!("{} not found", file))?;
+
+        let program = if file.ends_with(".hack") {
+            Cpu::parse_hack(&source).map_err(|e| format!("{}, {}", file, e))?
+        } else {
+            toolchain::assemble(&source).map_err(|e| format!("{}, {}", file, e))?
+        };
+        if program.len() > emulator::cpu::ROM_SIZE {
+            return Err(format!("{} does not fit into ROM", file));
+        }
+
+        self.cpu.load(&program);
+        self.time = 0;
+        Ok(())
+    }
+
+    fn get(&mut self, name: &str) -> Result<Value, String> {
+        let value = match name {
+            "time" => return Ok(Value::Text(self.time.to_string())),
+            "A" => self.cpu.a,
+            "D" => self.cpu.d,
+            "PC" => self.cpu.pc,
+            _ => match indexed(name) {
+                Some(("RAM", address)) if address < self.cpu.ram.len() => self.cpu.ram[address],
+                Some(("ROM", address)) if address < self.cpu.rom.len() => self.cpu.rom[address],
+                _ => return unknown(name),
+            },
+        };
+        Ok(Value::Word(value, 16))
+    }
+
+    fn set(&mut self, name: &str, value: u16) -> Result<(), String> {
+        match name {
+            "A" => self.cpu.a = value,
+            "D" => self.cpu.d = value,
+            "PC" => self.cpu.pc = value & 0x7fff,
+            _ => match indexed(name) {
+                Some(("RAM", address)) if address < self.cpu.ram.len() => {
+                    self.cpu.ram[address] = value
+                }
+                _ => return unknown(name),
+            },
+        }
+        Ok(())
+    }
+
+    fn command(&mut self, words: &[String]) -> Result<u64, String> {
+        match words[0].as_str() {
+            // the CPU executes an instruction per clock cycle, at the end of the cycle
+            "tick" if words.len() == 1 => Ok(0),
+            "tock" | "ticktock" if words.len() == 1 => {
+                self.cpu.step();
+                self.time += 1;
+                Ok(1)
+            }
+            _ => Err(format!("unsupported command '{}'", words.join(" "))),
+        }
+    }
+}
+
+/// Runs VM emulator scripts. `load` always loads the given program.
+///
+/// Like the OS of the VM emulator of the course, which is built into the emulator, the
+/// `builtin` modules run to completion within a single `vmstep`.
+pub struct VmTarget {
+    modules: Vec<(String, String)>,
+    builtin: Vec<String>,
+    vm: Option<Vm>,
+}
+
+// commands a builtin function may run within a single step
+const BUILTIN_LIMIT: u64 = 10_000_000;
+
+impl VmTarget {
+    pub fn new(modules: Vec<(String, String)>, builtin: Vec<String>) -> Self {
+        VmTarget {
+            modules,
+            builtin,
+            vm: None,
+        }
+    }
+
+    fn vm(&mut self) -> Result<&mut Vm, String> {
+        self.vm.as_mut().ok_or("no program loaded".to_string())
+    }
+}
+
+impl Target for VmTarget {
+    fn load(&mut self, _file: Option<&str>) -> Result<(), String> {
+        self.vm = Some(Vm::new(&self.modules)?);
+        Ok(())
+    }
+
+    fn get(&mut self, name: &str) -> Result<Value, String> {
+        let vm = self.vm()?;
+        match indexed(name) {
+            Some(("RAM", address)) if address < vm.ram.len() => {
+                Ok(Value::Word(vm.ram[address], 16))
+            }
+            _ => unknown(name),
+        }
+    }
+
+    fn set(&mut self, name: &str, value: u16) -> Result<(), String> {
+        let vm = self.vm()?;
+        match indexed(name) {
+            Some(("RAM", address)) if address < vm.ram.len() => vm.ram[address] = value,
+            _ => return unknown(name),
+        }
+        Ok(())
+    }
+
+    fn command(&mut self, words: &[String]) -> Result<u64, String> {
+        match words {
+            [command] if command == "vmstep" => {
+                let builtin = &self.builtin;
+                let vm = self.vm.as_mut().ok_or("no program loaded".to_string())?;
+                vm.step();
+                vm.run_while(BUILTIN_LIMIT, |vm| {
+                    vm.module()
+                        .is_some_and(|module| builtin.iter().any(|b| b == module))
+                });
+                Ok(1)
+            }
+            _ => Err(format!("unsupported command '{}'", words.join(" "))),
+        }
+    }
+}
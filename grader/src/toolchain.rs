@@ -0,0 +1,48 @@
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+
+use vm_to_asm::bootstrap::Bootstrap;
+use vm_to_asm::options::Options;
+
+// the assembler and the compiler report errors in their input by panicking, and so does the
+// code writer of vm-to-asm
+fn catch<T>(f: impl FnOnce() -> T) -> Result<T, String> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| message(payload.as_ref()))
+}
+
+fn message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown error".to_string()
+    }
+}
+
+/// Assembles the source of an `.asm` file.
+pub fn assemble(source: &str) -> Result<Vec<u16>, String> {
+    catch(|| assembler::stream::assemble(source.as_bytes()))?.map_err(|e| e.to_string())
+}
+
+/// Compiles the source of a `.jack` file into VM code.
+pub fn compile(source: &str) -> Result<String, String> {
+    let compiled = catch(|| compiler::batch::compile_class(source, &Default::default()))?;
+    String::from_utf8(compiled.vm).map_err(|e| e.to_string())
+}
+
+/// Translates the VM code of a whole program, OS included, into assembly. Calls are compact and
+/// the functions the program never calls are left out, so that a program linked with the OS fits
+/// into ROM.
+pub fn translate(modules: &[(String, String)]) -> Result<String, String> {
+    let sources = modules
+        .iter()
+        .map(|(name, vm)| (format!("{}.vm", name), vm.clone()))
+        .collect::<Vec<_>>();
+    let options = Options {
+        compact: true,
+        ..Options::default()
+    };
+    catch(|| vm_to_asm::program::translate(&sources, &options, &Bootstrap::default(), true))?
+        .map_err(|errors| errors.join("\n"))
+}
@@ -0,0 +1,118 @@
+// Compares the cycles the stock OS and the native routines of `--native-os` take per call,
+// measured on the Hack CPU emulator. Run with `cargo bench --bench os_cycles`.
+use std::fs::{self, File};
+use std::io::BufReader;
+
+use emulator::cpu::Cpu;
+use vm_to_asm::bootstrap::{Bootstrap, BootstrapMode};
+use vm_to_asm::code_writer::CodeWriter;
+use vm_to_asm::options::Options;
+use vm_to_asm::parser::parse;
+
+const CALLS: u16 = 100;
+
+struct Workload {
+    routine: &'static str,
+    modules: &'static [&'static str],
+    init: &'static str,
+    // pushes the arguments of call number `local 0`
+    arguments: &'static str,
+}
+
+const WORKLOADS: &[Workload] = &[
+    Workload {
+        routine: "Math.multiply 2",
+        modules: &["Math", "Memory", "Array"],
+        init: "call Math.init 0\npop temp 0\n",
+        arguments: "push local 0\npush constant 1234\n",
+    },
+    Workload {
+        routine: "Screen.drawLine 4",
+        modules: &["Screen", "Math", "Memory", "Array"],
+        init: "call Math.init 0\npop temp 0\ncall Screen.init 0\npop temp 0\n",
+        arguments:
+            "push local 0\npush constant 0\npush constant 511\npush local 0\npush local 0\nadd\n",
+    },
+    Workload {
+        routine: "Output.printChar 1",
+        modules: &["Output", "String", "Math", "Memory", "Array"],
+        init: "call Math.init 0\npop temp 0\ncall Output.init 0\npop temp 0\n",
+        arguments: "push constant 65\npush local 0\nadd\n",
+    },
+];
+
+// Sys.init initializes the OS and calls the routine `calls` times
+fn program(workload: &Workload, calls: u16) -> String {
+    format!(
+        "function Sys.init 1\n\
+        call Memory.init 0\npop temp 0\n\
+        {}\
+        label LOOP\n\
+        push local 0\npush constant {calls}\nlt\nnot\nif-goto END\n\
+        {}\
+        call {}\npop temp 0\n\
+        push local 0\npush constant 1\nadd\npop local 0\n\
+        goto LOOP\n\
+        label END\n\
+        goto END\n\
+        function Sys.error 0\n\
+        label HALT\n\
+        goto HALT\n",
+        workload.init, workload.arguments, workload.routine
+    )
+}
+
+fn cycles(workload: &Workload, calls: u16, options: &Options) -> u64 {
+    let path = std::env::temp_dir().join(format!("vm-to-asm-bench-{}.asm", std::process::id()));
+    let mut writer = CodeWriter::with_options(options);
+    writer.set_output_filename(path.to_str().unwrap());
+    writer.write_init(BootstrapMode::Full, &Bootstrap::default());
+    writer.write_module("Sys.vm", &parse(&program(workload, calls)).unwrap());
+    for module in workload.modules {
+        let source = fs::read_to_string(format!(
+            "{}/../tools/OS/{module}.vm",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap();
+        writer.write_module(&format!("{module}.vm"), &parse(&source).unwrap());
+    }
+    writer.close();
+
+    let program = assembler::stream::assemble(BufReader::new(File::open(&path).unwrap())).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let mut cpu = Cpu::new();
+    cpu.load(&program);
+    assert!(
+        cpu.run_until_idle(1_000_000_000),
+        "The benchmark did not finish."
+    );
+    cpu.cycles
+}
+
+fn main() {
+    let native = Options {
+        native_os: true,
+        verbose: false,
+        compact: false,
+        ..Options::default()
+    };
+
+    println!(
+        "{:<20} {:>12} {:>12} {:>8}",
+        "routine", "stock", "native", "speedup"
+    );
+    for workload in WORKLOADS {
+        // the initialization of the OS is not counted
+        let per_call = |options: &Options| {
+            (cycles(workload, CALLS, options) - cycles(workload, 0, options)) / CALLS as u64
+        };
+        let stock = per_call(&Options::default());
+        let native = per_call(&native);
+        let name = workload.routine.split(' ').next().unwrap();
+        println!(
+            "{name:<20} {stock:>12} {native:>12} {:>7.1}x",
+            stock as f64 / native as f64
+        );
+    }
+}
@@ -0,0 +1,40 @@
+// Math.multiply(x, y)
+// Adds x shifted left by i for every bit i of y that is set, and stops as soon as y has no
+// set bits left. Two's complement makes the product right for negative operands as well.
+    @ARG
+    A=M
+    D=M
+    @Math.multiply$x
+    M=D
+    @Math.multiply$product
+    M=0
+    @Math.multiply$bit
+    M=1
+(Math.multiply$LOOP)
+    @ARG
+    A=M+1
+    D=M
+    @Math.multiply$bit
+    D=D&M
+    @Math.multiply$NEXT
+    D;JEQ
+    @Math.multiply$x
+    D=M
+    @Math.multiply$product
+    M=D+M
+(Math.multiply$NEXT)
+    @Math.multiply$x
+    D=M
+    M=D+M
+    @Math.multiply$bit
+    D=M
+    MD=D+M
+    // -bit has every bit from `bit` upwards set, or none once `bit` has shifted out
+    D=-D
+    @ARG
+    A=M+1
+    D=D&M
+    @Math.multiply$LOOP
+    D;JNE
+    @Math.multiply$product
+    D=M
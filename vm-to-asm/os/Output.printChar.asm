@@ -0,0 +1,142 @@
+// Output.printChar(c)
+// Draws c at the cursor and advances the cursor, like the stock routine. The statics are
+// those of the stock Output: 0 is the column in words, 1 the cursor's offset into the
+// screen, 2 whether the cursor is on the left half of the word, 4 the screen, and 5 and 6
+// the character maps for the left and the right half.
+// New lines and backspaces are left to the stock routine.
+    @ARG
+    A=M
+    D=M
+    @128
+    D=D-A
+    @Output.printChar$STOCK
+    D;JEQ
+    D=D-1
+    @Output.printChar$STOCK
+    D;JEQ
+
+    // characters outside 32..126 are drawn as map 0
+    @ARG
+    A=M
+    D=M
+    @32
+    D=D-A
+    @Output.printChar$UNKNOWN
+    D;JLT
+    @94
+    D=D-A
+    @Output.printChar$UNKNOWN
+    D;JGT
+    @ARG
+    A=M
+    D=M
+    @Output.printChar$INDEXED
+    0;JMP
+(Output.printChar$UNKNOWN)
+    D=0
+(Output.printChar$INDEXED)
+    @Output.printChar$map
+    M=D
+
+    // map = (left ? maps : shifted maps)[c], keep = the half of the word that stays
+    @255
+    D=A
+    @Output.printChar$keep
+    M=D
+    @Output.2
+    D=M
+    @Output.printChar$RIGHT
+    D;JEQ
+    @Output.printChar$keep
+    M=!M
+    @Output.5
+    D=M
+    @Output.printChar$TABLE
+    0;JMP
+(Output.printChar$RIGHT)
+    @Output.6
+    D=M
+(Output.printChar$TABLE)
+    @Output.printChar$map
+    A=D+M
+    D=M
+    @Output.printChar$map
+    M=D
+
+    @Output.4
+    D=M
+    @Output.1
+    D=D+M
+    @Output.printChar$address
+    M=D
+    @11
+    D=A
+    @Output.printChar$rows
+    M=D
+(Output.printChar$ROW)
+    @Output.printChar$address
+    A=M
+    D=M
+    @Output.printChar$keep
+    D=D&M
+    @Output.printChar$map
+    A=M
+    D=D|M
+    @Output.printChar$address
+    A=M
+    M=D
+    @32
+    D=A
+    @Output.printChar$address
+    M=D+M
+    @Output.printChar$map
+    M=M+1
+    @Output.printChar$rows
+    MD=M-1
+    @Output.printChar$ROW
+    D;JGT
+
+    // the cursor moves to the next word after drawing into its right half
+    @Output.2
+    D=M
+    @Output.printChar$ADVANCED
+    D;JNE
+    @Output.0
+    M=M+1
+    @Output.1
+    M=M+1
+(Output.printChar$ADVANCED)
+    @Output.0
+    D=M
+    @32
+    D=D-A
+    @Output.printChar$NEWLINE
+    D;JEQ
+    @Output.2
+    M=!M
+    @Output.printChar$DONE
+    0;JMP
+
+    // what Output.println does: move to the start of the next line, or back to the top
+(Output.printChar$NEWLINE)
+    @Output.0
+    D=M
+    @352
+    D=D-A
+    @Output.1
+    MD=M-D
+    @8128
+    D=D-A
+    @Output.printChar$WRAPPED
+    D;JNE
+    @32
+    D=A
+    @Output.1
+    M=D
+(Output.printChar$WRAPPED)
+    @Output.0
+    M=0
+    @Output.2
+    M=-1
+(Output.printChar$DONE)
+    D=0
@@ -0,0 +1,407 @@
+// Screen.drawLine(x1, y1, x2, y2)
+// Draws the same pixels as the stock routine: the line is walked along its major axis in
+// increasing order, and the minor axis moves whenever the error term is not negative. The
+// screen address and the bit of the current pixel are kept up to date instead of being
+// computed with Math.multiply and Math.divide for every pixel. The statics are those of the
+// stock Screen: 0 is the table of powers of two and 2 the color.
+// Lines with a coordinate off the screen are left to the stock routine, which reports them.
+    @ARG
+    A=M
+    D=M
+    @Screen.drawLine$x1
+    M=D
+    @Screen.drawLine$STOCK
+    D;JLT
+    @511
+    D=D-A
+    @Screen.drawLine$STOCK
+    D;JGT
+    @ARG
+    A=M+1
+    D=M
+    @Screen.drawLine$y1
+    M=D
+    @Screen.drawLine$STOCK
+    D;JLT
+    @255
+    D=D-A
+    @Screen.drawLine$STOCK
+    D;JGT
+    @ARG
+    D=M
+    @2
+    A=D+A
+    D=M
+    @Screen.drawLine$x2
+    M=D
+    @Screen.drawLine$STOCK
+    D;JLT
+    @511
+    D=D-A
+    @Screen.drawLine$STOCK
+    D;JGT
+    @ARG
+    D=M
+    @3
+    A=D+A
+    D=M
+    @Screen.drawLine$y2
+    M=D
+    @Screen.drawLine$STOCK
+    D;JLT
+    @255
+    D=D-A
+    @Screen.drawLine$STOCK
+    D;JGT
+
+    // dx = |x2 - x1|, dy = |y2 - y1|
+    @Screen.drawLine$x1
+    D=M
+    @Screen.drawLine$x2
+    D=M-D
+    @Screen.drawLine$DX
+    D;JGE
+    D=-D
+(Screen.drawLine$DX)
+    @Screen.drawLine$dx
+    M=D
+    @Screen.drawLine$y1
+    D=M
+    @Screen.drawLine$y2
+    D=M-D
+    @Screen.drawLine$DY
+    D;JGE
+    D=-D
+(Screen.drawLine$DY)
+    @Screen.drawLine$dy
+    M=D
+
+    // a steep line is walked along y, any other along x. the end points are swapped if the
+    // walk would go backwards.
+    @Screen.drawLine$steep
+    M=0
+    @Screen.drawLine$dy
+    D=M
+    @Screen.drawLine$dx
+    D=M-D
+    @Screen.drawLine$FLAT
+    D;JGE
+    @Screen.drawLine$steep
+    M=-1
+    @Screen.drawLine$y1
+    D=M
+    @Screen.drawLine$y2
+    D=M-D
+    @Screen.drawLine$ORDER
+    0;JMP
+(Screen.drawLine$FLAT)
+    @Screen.drawLine$x1
+    D=M
+    @Screen.drawLine$x2
+    D=M-D
+(Screen.drawLine$ORDER)
+    @Screen.drawLine$ORDERED
+    D;JGE
+    @Screen.drawLine$x1
+    D=M
+    @Screen.drawLine$t
+    M=D
+    @Screen.drawLine$x2
+    D=M
+    @Screen.drawLine$x1
+    M=D
+    @Screen.drawLine$t
+    D=M
+    @Screen.drawLine$x2
+    M=D
+    @Screen.drawLine$y1
+    D=M
+    @Screen.drawLine$t
+    M=D
+    @Screen.drawLine$y2
+    D=M
+    @Screen.drawLine$y1
+    M=D
+    @Screen.drawLine$t
+    D=M
+    @Screen.drawLine$y2
+    M=D
+(Screen.drawLine$ORDERED)
+
+    // address = SCREEN + 32 * y1 + x1 / 16
+    @Screen.drawLine$y1
+    D=M
+    @Screen.drawLine$address
+    M=D
+    D=M
+    M=D+M
+    D=M
+    M=D+M
+    D=M
+    M=D+M
+    D=M
+    M=D+M
+    D=M
+    M=D+M
+    @Screen.drawLine$x1
+    D=M
+    @Screen.drawLine$x
+    M=D
+    @16
+    D=D&A
+    @Screen.drawLine$BIT4
+    D;JEQ
+    @Screen.drawLine$address
+    M=M+1
+(Screen.drawLine$BIT4)
+    @Screen.drawLine$x
+    D=M
+    @32
+    D=D&A
+    @Screen.drawLine$BIT5
+    D;JEQ
+    @2
+    D=A
+    @Screen.drawLine$address
+    M=D+M
+(Screen.drawLine$BIT5)
+    @Screen.drawLine$x
+    D=M
+    @64
+    D=D&A
+    @Screen.drawLine$BIT6
+    D;JEQ
+    @4
+    D=A
+    @Screen.drawLine$address
+    M=D+M
+(Screen.drawLine$BIT6)
+    @Screen.drawLine$x
+    D=M
+    @128
+    D=D&A
+    @Screen.drawLine$BIT7
+    D;JEQ
+    @8
+    D=A
+    @Screen.drawLine$address
+    M=D+M
+(Screen.drawLine$BIT7)
+    @Screen.drawLine$x
+    D=M
+    @256
+    D=D&A
+    @Screen.drawLine$BIT8
+    D;JEQ
+    @16
+    D=A
+    @Screen.drawLine$address
+    M=D+M
+(Screen.drawLine$BIT8)
+    @SCREEN
+    D=A
+    @Screen.drawLine$address
+    M=D+M
+
+    @Screen.drawLine$steep
+    D=M
+    @Screen.drawLine$STEEP
+    D;JNE
+
+    // along x: err = 2dy - dx, and y moves by a row of 32 words up or down
+    @Screen.drawLine$dy
+    D=M
+    D=D+M
+    @Screen.drawLine$increment
+    M=D
+    @Screen.drawLine$dx
+    D=D-M
+    @Screen.drawLine$err
+    M=D
+    @Screen.drawLine$dx
+    D=M
+    D=D+M
+    @Screen.drawLine$increment
+    D=M-D
+    @Screen.drawLine$correction
+    M=D
+    @32
+    D=A
+    @Screen.drawLine$row
+    M=D
+    @Screen.drawLine$y2
+    D=M
+    @Screen.drawLine$y1
+    D=M-D
+    @Screen.drawLine$FLAT_PLOT
+    D;JLE
+    @Screen.drawLine$row
+    M=-M
+(Screen.drawLine$FLAT_PLOT)
+    @Screen.drawLine$FLAT_LOOP
+    D=A
+    @Screen.drawLine$return
+    M=D
+    @Screen.drawLine$PLOT
+    0;JMP
+(Screen.drawLine$FLAT_LOOP)
+    @Screen.drawLine$x
+    D=M
+    @Screen.drawLine$x2
+    D=D-M
+    @Screen.drawLine$END
+    D;JGE
+    @Screen.drawLine$err
+    D=M
+    @Screen.drawLine$FLAT_KEEP
+    D;JLT
+    @Screen.drawLine$correction
+    D=M
+    @Screen.drawLine$err
+    M=D+M
+    @Screen.drawLine$row
+    D=M
+    @Screen.drawLine$address
+    M=D+M
+    @Screen.drawLine$FLAT_X
+    0;JMP
+(Screen.drawLine$FLAT_KEEP)
+    @Screen.drawLine$increment
+    D=M
+    @Screen.drawLine$err
+    M=D+M
+(Screen.drawLine$FLAT_X)
+    @Screen.drawLine$x
+    MD=M+1
+    @15
+    D=D&A
+    @Screen.drawLine$FLAT_PLOT
+    D;JNE
+    @Screen.drawLine$address
+    M=M+1
+    @Screen.drawLine$FLAT_PLOT
+    0;JMP
+
+    // along y: err = 2dx - dy, and x moves left if x1 > x2
+(Screen.drawLine$STEEP)
+    @Screen.drawLine$dx
+    D=M
+    D=D+M
+    @Screen.drawLine$increment
+    M=D
+    @Screen.drawLine$dy
+    D=D-M
+    @Screen.drawLine$err
+    M=D
+    @Screen.drawLine$dy
+    D=M
+    D=D+M
+    @Screen.drawLine$increment
+    D=M-D
+    @Screen.drawLine$correction
+    M=D
+    @Screen.drawLine$x2
+    D=M
+    @Screen.drawLine$x1
+    D=M-D
+    @Screen.drawLine$left
+    M=D
+(Screen.drawLine$STEEP_PLOT)
+    @Screen.drawLine$STEEP_LOOP
+    D=A
+    @Screen.drawLine$return
+    M=D
+    @Screen.drawLine$PLOT
+    0;JMP
+(Screen.drawLine$STEEP_LOOP)
+    @Screen.drawLine$y1
+    D=M
+    @Screen.drawLine$y2
+    D=D-M
+    @Screen.drawLine$END
+    D;JGE
+    @Screen.drawLine$err
+    D=M
+    @Screen.drawLine$STEEP_KEEP
+    D;JLT
+    @Screen.drawLine$correction
+    D=M
+    @Screen.drawLine$err
+    M=D+M
+    @Screen.drawLine$left
+    D=M
+    @Screen.drawLine$STEEP_RIGHT
+    D;JLE
+    // leaving the first pixel of a word moves to the previous word
+    @Screen.drawLine$x
+    D=M
+    M=D-1
+    @15
+    D=D&A
+    @Screen.drawLine$STEEP_Y
+    D;JNE
+    @Screen.drawLine$address
+    M=M-1
+    @Screen.drawLine$STEEP_Y
+    0;JMP
+(Screen.drawLine$STEEP_RIGHT)
+    @Screen.drawLine$x
+    MD=M+1
+    @15
+    D=D&A
+    @Screen.drawLine$STEEP_Y
+    D;JNE
+    @Screen.drawLine$address
+    M=M+1
+    @Screen.drawLine$STEEP_Y
+    0;JMP
+(Screen.drawLine$STEEP_KEEP)
+    @Screen.drawLine$increment
+    D=M
+    @Screen.drawLine$err
+    M=D+M
+(Screen.drawLine$STEEP_Y)
+    @Screen.drawLine$y1
+    M=M+1
+    @32
+    D=A
+    @Screen.drawLine$address
+    M=D+M
+    @Screen.drawLine$STEEP_PLOT
+    0;JMP
+
+    // sets the pixel at `address` and bit x % 16 in the current color, then jumps to `return`
+(Screen.drawLine$PLOT)
+    @Screen.drawLine$x
+    D=M
+    @15
+    D=D&A
+    @Screen.0
+    A=D+M
+    D=M
+    @Screen.drawLine$mask
+    M=D
+    @Screen.2
+    D=M
+    @Screen.drawLine$WHITE
+    D;JEQ
+    @Screen.drawLine$mask
+    D=M
+    @Screen.drawLine$address
+    A=M
+    M=D|M
+    @Screen.drawLine$return
+    A=M
+    0;JMP
+(Screen.drawLine$WHITE)
+    @Screen.drawLine$mask
+    D=!M
+    @Screen.drawLine$address
+    A=M
+    M=D&M
+    @Screen.drawLine$return
+    A=M
+    0;JMP
+
+(Screen.drawLine$END)
+    D=0
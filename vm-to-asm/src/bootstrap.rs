@@ -0,0 +1,302 @@
+use std::collections::HashSet;
+
+use crate::command::{Segment, VmCommand};
+use crate::parser::Line;
+
+/// What the translator writes before the translated modules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootstrapMode {
+    /// Nothing: the program starts at its first command, with the registers set by whoever
+    /// runs it, as the tests of projects 07 and 08 do.
+    None,
+    /// Sets SP to the stack base, and starts the program at its first command.
+    Stack,
+    /// Sets SP to the stack base and calls the entry function.
+    Full,
+}
+
+impl BootstrapMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "none" => Some(BootstrapMode::None),
+            "stack" => Some(BootstrapMode::Stack),
+            "full" => Some(BootstrapMode::Full),
+            _ => None,
+        }
+    }
+
+    /// Whether the program starts at its first command rather than at the entry function,
+    /// which allows commands outside of any function.
+    pub fn runs_top_level(&self) -> bool {
+        *self != BootstrapMode::Full
+    }
+}
+
+/// The bootstrap code and the memory layout it sets up. The statics take RAM from 16 up to the
+/// stack base, where the stack starts. The defaults follow the book. The heap is left to the OS,
+/// which sets it up in `Memory.init`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bootstrap {
+    /// `None` picks `Full` if one of the modules is `Sys.vm`, and `BootstrapMode::None`
+    /// otherwise.
+    pub mode: Option<BootstrapMode>,
+    pub stack_base: u16,
+    /// The function the full bootstrap calls.
+    pub entry: String,
+}
+
+impl Default for Bootstrap {
+    fn default() -> Self {
+        Bootstrap {
+            mode: None,
+            stack_base: 256,
+            entry: "Sys.init".to_string(),
+        }
+    }
+}
+
+// removes `--name <value>` from the arguments, returning the value
+fn value(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let i = args.iter().position(|arg| arg == name)?;
+    if i + 1 >= args.len() {
+        panic!("{name} expects a value.");
+    }
+    let value = args.remove(i + 1);
+    args.remove(i);
+    Some(value)
+}
+
+fn address(args: &mut Vec<String>, name: &str, default: u16) -> u16 {
+    value(args, name).map_or(default, |value| {
+        value
+            .parse()
+            .unwrap_or_else(|_| panic!("{name} expects an address, found '{value}'."))
+    })
+}
+
+impl Bootstrap {
+    /// Removes `--bootstrap none|stack|full`, `--stack-base <n>` and `--entry <function>`
+    /// from `args`.
+    pub fn from_args(args: &mut Vec<String>) -> Self {
+        let default = Bootstrap::default();
+        let mode = value(args, "--bootstrap").map(|name| {
+            BootstrapMode::from_name(&name).unwrap_or_else(|| {
+                panic!("--bootstrap expects none, stack or full, found '{name}'.")
+            })
+        });
+        Bootstrap {
+            mode,
+            stack_base: address(args, "--stack-base", default.stack_base),
+            entry: value(args, "--entry").unwrap_or(default.entry),
+        }
+    }
+
+    /// The mode for a program made of the given modules.
+    pub fn mode(&self, modules: &[(String, Vec<Line>)]) -> BootstrapMode {
+        self.mode.unwrap_or_else(|| {
+            if modules.iter().any(|(module, _)| module == "Sys.vm") {
+                BootstrapMode::Full
+            } else {
+                BootstrapMode::None
+            }
+        })
+    }
+
+    /// Checks the memory layout against the statics of the modules, and that the full
+    /// bootstrap has a function to call. Returns one message per error.
+    pub fn check(&self, modules: &[(String, Vec<Line>)]) -> Vec<String> {
+        let mut errors = Vec::new();
+        let mode = self.mode(modules);
+        if mode == BootstrapMode::None {
+            return errors;
+        }
+
+        let statics = modules
+            .iter()
+            .flat_map(|(module, commands)| {
+                commands.iter().filter_map(move |line| match line.command {
+                    VmCommand::Push(Segment::Static, index)
+                    | VmCommand::Pop(Segment::Static, index) => Some((module, index)),
+                    _ => None,
+                })
+            })
+            .collect::<HashSet<_>>()
+            .len();
+        if 16 + statics > self.stack_base as usize {
+            errors.push(format!(
+                "The stack base {} leaves room for {} static variables, but the program has {statics}.",
+                self.stack_base,
+                (self.stack_base as usize).saturating_sub(16)
+            ));
+        }
+        if self.stack_base >= 16384 {
+            errors.push(format!(
+                "The stack base {} must lie below 16384, the screen.",
+                self.stack_base
+            ));
+        }
+
+        let defined = modules.iter().any(|(_, commands)| {
+            commands
+                .iter()
+                .any(|line| matches!(&line.command, VmCommand::Function(name, _) if *name == self.entry))
+        });
+        if mode == BootstrapMode::Full && !defined {
+            errors.push(format!(
+                "The bootstrap calls '{}', which none of the modules defines.",
+                self.entry
+            ));
+        }
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use emulator::cpu::Cpu;
+
+    use super::{Bootstrap, BootstrapMode};
+    use crate::code_writer::tests::translate;
+    use crate::options::Options;
+    use crate::parser::{parse, Line};
+
+    // adds 7 and 8 outside of any function, and stays idle in a loop
+    const SIMPLE_ADD: &str = "
+        push constant 7
+        push constant 8
+        add
+        label END
+        goto END
+    ";
+
+    // Main.main stores 42 in static 0 and calls Main.idle, which never returns
+    const MAIN: &str = "
+        function Main.main 0
+        push constant 42
+        pop static 0
+        call Main.idle 0
+        return
+        function Main.idle 0
+        label END
+        goto END
+    ";
+
+    fn run(name: &str, modules: &[(&str, &str)], mode: BootstrapMode, bootstrap: &Bootstrap) -> Cpu {
+        let program = translate(name, modules, &Options::default(), mode, bootstrap);
+        let mut cpu = Cpu::new();
+        cpu.load(&program);
+        // without bootstrap, the stack is set up by whoever runs the program
+        cpu.ram[0] = 400;
+        assert!(cpu.run_until_idle(1_000_000), "The program did not finish ({name}).");
+        cpu
+    }
+
+    fn modules(sources: &[(&str, &str)]) -> Vec<(String, Vec<Line>)> {
+        sources
+            .iter()
+            .map(|(module, source)| (module.to_string(), parse(source).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_from_args() {
+        let mut args = ["vm-to-asm", "--bootstrap", "stack", "--stack-base", "300", "--entry", "Main.main", "Main.vm"]
+            .map(String::from)
+            .to_vec();
+        let bootstrap = Bootstrap::from_args(&mut args);
+        assert_eq!(args, ["vm-to-asm", "Main.vm"]);
+        assert_eq!(
+            bootstrap,
+            Bootstrap {
+                mode: Some(BootstrapMode::Stack),
+                stack_base: 300,
+                entry: "Main.main".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_automatic_mode() {
+        let bootstrap = Bootstrap::default();
+        let program = modules(&[("Main.vm", MAIN)]);
+        assert_eq!(bootstrap.mode(&program), BootstrapMode::None);
+        let program = modules(&[("Main.vm", MAIN), ("Sys.vm", "function Sys.init 0\ncall Main.main 0\nreturn")]);
+        assert_eq!(bootstrap.mode(&program), BootstrapMode::Full);
+
+        let forced = Bootstrap {
+            mode: Some(BootstrapMode::Stack),
+            ..Bootstrap::default()
+        };
+        assert_eq!(forced.mode(&program), BootstrapMode::Stack);
+    }
+
+    #[test]
+    fn test_no_bootstrap() {
+        let cpu = run("no-bootstrap", &[("SimpleAdd.vm", SIMPLE_ADD)], BootstrapMode::None, &Bootstrap::default());
+        assert_eq!(cpu.ram[0], 401);
+        assert_eq!(cpu.ram[400], 15);
+    }
+
+    #[test]
+    fn test_stack_bootstrap() {
+        let bootstrap = Bootstrap {
+            stack_base: 1000,
+            ..Bootstrap::default()
+        };
+        let cpu = run("stack-bootstrap", &[("SimpleAdd.vm", SIMPLE_ADD)], BootstrapMode::Stack, &bootstrap);
+        assert_eq!(cpu.ram[0], 1001);
+        assert_eq!(cpu.ram[1000], 15);
+    }
+
+    #[test]
+    fn test_full_bootstrap() {
+        let bootstrap = Bootstrap {
+            stack_base: 300,
+            entry: "Main.main".to_string(),
+            ..Bootstrap::default()
+        };
+        let cpu = run("full-bootstrap", &[("Main.vm", MAIN)], BootstrapMode::Full, &bootstrap);
+        assert_eq!(cpu.ram[16], 42);
+        // the frames of Main.main and Main.idle, 5 words each
+        assert_eq!(cpu.ram[0], 310);
+        assert_eq!(cpu.ram[1], 310);
+    }
+
+    #[test]
+    fn test_check() {
+        let program = modules(&[("Main.vm", MAIN)]);
+        let full = |bootstrap: Bootstrap| {
+            Bootstrap {
+                mode: Some(BootstrapMode::Full),
+                ..bootstrap
+            }
+            .check(&program)
+        };
+        assert_eq!(full(Bootstrap::default()), ["The bootstrap calls 'Sys.init', which none of the modules defines."]);
+        assert!(full(Bootstrap {
+            entry: "Main.main".to_string(),
+            ..Bootstrap::default()
+        })
+        .is_empty());
+        assert_eq!(
+            full(Bootstrap {
+                stack_base: 16,
+                entry: "Main.main".to_string(),
+                ..Bootstrap::default()
+            }),
+            [
+                "The stack base 16 leaves room for 0 static variables, but the program has 1.",
+            ]
+        );
+        assert_eq!(
+            full(Bootstrap {
+                stack_base: 16384,
+                entry: "Main.main".to_string(),
+                ..Bootstrap::default()
+            }),
+            ["The stack base 16384 must lie below 16384, the screen."]
+        );
+        // without bootstrap the program sets up the memory itself
+        assert!(Bootstrap::default().check(&program).is_empty());
+    }
+}
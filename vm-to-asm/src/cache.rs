@@ -0,0 +1,101 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use compiler::build_cache::{executable_hash, stable_hash};
+
+use crate::options::Options;
+
+/// Directory of translated modules, keyed by module name and source hash.
+///
+/// A module's translation only depends on its own source, since every label the
+/// code writer generates is scoped to the module or to one of its functions. Only the
+/// latest translation of each module name is kept.
+pub struct ModuleCache {
+    dir: PathBuf,
+}
+
+impl ModuleCache {
+    pub fn new(dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(ModuleCache {
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    /// Where the translation of `module` (e.g. "Main.vm") with the given source and options
+    /// is stored. `functions` are the functions of the module that are translated, which are
+    /// fewer than those of the source once dead code is eliminated, and `leaves` the functions
+    /// it calls that get a light frame, see `code_writer::leaf_functions`.
+    pub fn path(&self, module: &str, source: &str, functions: &[&str], leaves: &[&str], options: &Options) -> PathBuf {
+        let basename = module.strip_suffix(".vm").unwrap_or(module);
+        let hash = source_hash(source, functions, leaves, options);
+        self.dir.join(format!("{basename}.{hash:016x}.asm"))
+    }
+
+    /// Removes the translations of `module` other than the one at `current`, which were
+    /// made from older sources or with other options.
+    pub fn evict(&self, module: &str, current: &Path) -> io::Result<()> {
+        let basename = module.strip_suffix(".vm").unwrap_or(module);
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let stale = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix(basename)?.strip_prefix('.')?.strip_suffix(".asm"))
+                .is_some_and(|hash| hash.len() == 16 && hash.bytes().all(|b| b.is_ascii_hexdigit()));
+            if stale && path != current {
+                fs::remove_file(&path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Hash of the source together with the functions translated from it, the leaf functions it
+/// calls, the translator build and the options.
+pub fn source_hash(source: &str, functions: &[&str], leaves: &[&str], options: &Options) -> u64 {
+    let (build, options) = (executable_hash().to_le_bytes(), options.to_string());
+    let fields = [&build[..], options.as_bytes(), source.as_bytes()];
+    // an empty field keeps the functions apart from the leaves
+    let names = functions.iter().chain(&[""]).chain(leaves);
+    stable_hash(fields.into_iter().chain(names.map(|name| name.as_bytes())))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::ModuleCache;
+    use crate::options::Options;
+
+    #[test]
+    fn test_evict() {
+        let dir = std::env::temp_dir().join(format!("vm-to-asm-cache-test-{}", std::process::id()));
+        let cache = ModuleCache::new(&dir).unwrap();
+        let options = Options::default();
+        let old = cache.path("Main.vm", "push constant 1", &[], &[], &options);
+        let new = cache.path("Main.vm", "push constant 2", &[], &[], &options);
+        let other = cache.path("MainMenu.vm", "push constant 1", &[], &[], &options);
+        for path in [&old, &new, &other] {
+            fs::write(path, "").unwrap();
+        }
+
+        cache.evict("Main.vm", &new).unwrap();
+        let (old, new, other) = (old.exists(), new.exists(), other.exists());
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!((old, new, other), (false, true, true));
+    }
+
+    #[test]
+    fn test_functions() {
+        let cache = ModuleCache::new(&std::env::temp_dir()).unwrap();
+        let options = Options::default();
+        let source = "function Main.main 0\nreturn\nfunction Main.unused 0\nreturn";
+        // a module with a function dropped is stored apart from the whole module
+        assert_ne!(
+            cache.path("Main.vm", source, &["Main.main", "Main.unused"], &[], &options),
+            cache.path("Main.vm", source, &["Main.main"], &[], &options)
+        );
+    }
+}
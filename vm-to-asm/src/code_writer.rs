@@ -1,66 +1,242 @@
+use std::collections::HashSet;
 use std::{fs::File, io::Write};
 
-use crate::command::CommandType;
+use crate::bootstrap::{Bootstrap, BootstrapMode};
+use crate::command::{ArithmeticOp, Segment, VmCommand};
+use crate::native_os::NativeRoutine;
+use crate::options::Options;
+use crate::parser::Line;
 
-pub struct CodeWriter {
-    output_file: Option<File>,
+pub struct CodeWriter<W: Write = File> {
+    output_file: Option<W>,
     module: Option<String>,
+    function: Option<String>,
     jump_counter: u16,
     return_counter: u16,
+    options: Options,
+    // whether the current function makes no calls and has no locals, and if so, whether it
+    // changes THIS or THAT
+    leaf: bool,
+    leaf_sets_pointers: bool,
+    // see `leaf_functions`
+    leaves: HashSet<String>,
+}
+
+impl Default for CodeWriter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl CodeWriter {
     pub fn new() -> Self {
+        Self::with_options(&Options::default())
+    }
+
+    pub fn with_options(options: &Options) -> Self {
+        Self::from_output(None, options)
+    }
+
+    pub fn set_output_filename(&mut self, name: &str) {
+        let file = File::create(name).expect("set_filename: file not found");
+        self.output_file = Some(file);
+        self.jump_counter = 0;
+    }
+}
+
+impl<W: Write> CodeWriter<W> {
+    /// A writer that writes to `output` rather than to a file, e.g. to a `Vec<u8>`.
+    pub fn with_output(output: W, options: &Options) -> Self {
+        Self::from_output(Some(output), options)
+    }
+
+    fn from_output(output_file: Option<W>, options: &Options) -> Self {
         CodeWriter {
-            output_file: None,
+            output_file,
             module: None,
+            function: None,
             jump_counter: 0,
             return_counter: 0,
+            options: options.clone(),
+            leaf: false,
+            leaf_sets_pointers: false,
+            leaves: HashSet::new(),
         }
     }
 
-    pub fn set_output_filename(&mut self, name: &str) {
-        let file = File::create(name).expect("set_filename: file not found");
-        self.output_file = Some(file);
-        self.jump_counter = 0;
+    /// Tells the writer the whole program before it writes any of it, so that calls to the
+    /// functions of `leaf_functions` get a lighter frame. The modules may then be written by
+    /// writers of their own, as long as each is given the same program.
+    pub fn set_program(&mut self, modules: &[(String, Vec<Line>)]) {
+        self.leaves = leaf_functions(modules, &self.options);
     }
 
+    /// Gives back the output, which closes the writer.
+    pub fn into_output(self) -> Option<W> {
+        self.output_file
+    }
+
+    // labels generated by the writer are scoped to the module, so that each module
+    // translates the same way regardless of which modules precede it.
     pub fn set_module_name(&mut self, name: &str) {
         self.module = Some(name.to_string());
+        self.function = None;
+        self.jump_counter = 0;
+        self.return_counter = 0;
+    }
+
+    /// Translates the commands of a module. With `optimize`, a `call` immediately followed by
+    /// `return` becomes a tail call, and leaf functions get a shorter epilogue, as well as a
+    /// shorter frame if `set_program` was called. With
+    /// `native_os`, the functions of `native_os::ROUTINES` are replaced by their routine. With
+    /// `verbose`, each command is echoed as a comment before its translation.
+    pub fn write_module(&mut self, name: &str, commands: &[Line]) {
+        self.set_module_name(name);
+
+        let mut i = 0;
+        while i < commands.len() {
+            if self.options.verbose {
+                self.write_source_comment(&commands[i]);
+            }
+            match &commands[i].command {
+                VmCommand::Arithmetic(op) => self.write_arithemtic(*op),
+                command @ (VmCommand::Push(..) | VmCommand::Pop(..)) => self.write_pushpop(command),
+                VmCommand::Label(label) => self.write_label(label),
+                VmCommand::Goto(label) => self.write_goto(label),
+                VmCommand::If(label) => self.write_if(label),
+                VmCommand::Function(function_name, locals_count) => {
+                    match NativeRoutine::find(function_name).filter(|_| self.options.native_os) {
+                        Some(routine) if routine.falls_back() => {
+                            self.write_native(routine);
+                            self.write_function(&format!("{function_name}$STOCK"), *locals_count);
+                        }
+                        Some(routine) => {
+                            self.write_native(routine);
+                            // the VM code of the function is never run
+                            while commands
+                                .get(i + 1)
+                                .is_some_and(|line| !matches!(line.command, VmCommand::Function(..)))
+                            {
+                                i += 1;
+                            }
+                        }
+                        None => self.write_function(function_name, *locals_count),
+                    }
+                    if self.options.optimize && *locals_count == 0 {
+                        let body = commands[i + 1..]
+                            .iter()
+                            .map(|line| &line.command)
+                            .take_while(|command| !matches!(command, VmCommand::Function(..)));
+                        let mut sets_pointers = false;
+                        self.leaf = true;
+                        for command in body {
+                            match command {
+                                VmCommand::Call(..) => self.leaf = false,
+                                VmCommand::Pop(Segment::Pointer, _) => sets_pointers = true,
+                                _ => {}
+                            }
+                        }
+                        self.leaf_sets_pointers = sets_pointers;
+                    }
+                }
+                VmCommand::Call(function_name, args_count) => {
+                    // a tail call builds the full frame, which a leaf with a light one cannot use
+                    let tail = commands.get(i + 1).map(|line| &line.command) == Some(&VmCommand::Return)
+                        && !self.leaves.contains(function_name);
+                    if self.options.optimize && tail {
+                        // the jump into the callee is the translation of the `return`
+                        self.write_tail_frame(function_name, *args_count);
+                        i += 1;
+                        if self.options.verbose {
+                            self.write_source_comment(&commands[i]);
+                        }
+                        self.write_tail_jump(function_name);
+                    } else {
+                        self.write_call(function_name, *args_count);
+                    }
+                }
+                VmCommand::Return => self.write_return(),
+            }
+            i += 1;
+        }
+    }
+
+    // `// Main.vm:12: push local 0`, the form `source_map` looks for
+    fn write_source_comment(&mut self, line: &Line) {
+        let out_file = self
+            .output_file
+            .as_mut()
+            .expect("Target file not set. Call set_filename() before writing commands.");
+        let module = self
+            .module
+            .as_ref()
+            .expect("Target module not set. Call set_module_name() before writing commands.");
+
+        writeln!(out_file, "// {module}:{}: {}", line.line, line.command).unwrap();
     }
 
     pub fn close(&mut self) {
         self.output_file = None;
     }
 
-    pub fn write_init(&mut self) {
+    /// Writes the bootstrap code of the given mode, which sets SP to the stack base and, for the
+    /// full bootstrap, calls the entry function. With `compact`, the shared code of `call` and
+    /// `return` follows.
+    pub fn write_init(&mut self, mode: BootstrapMode, bootstrap: &Bootstrap) {
+        self.write_bootstrap(mode, bootstrap);
+        if self.options.compact {
+            self.write_shared_call();
+        }
+    }
+
+    fn write_bootstrap(&mut self, mode: BootstrapMode, bootstrap: &Bootstrap) {
         let out_file = self
             .output_file
             .as_mut()
             .expect("Target file not set. Call set_filename() before writing commands.");
 
+        if mode == BootstrapMode::None {
+            return;
+        }
+
         write!(
             out_file,
             "\
-                @256\n\
+                @{}\n\
                 D=A\n\
                 @SP\n\
                 M=D\n\
-            "
+            ",
+            bootstrap.stack_base
         )
         .unwrap();
 
-        let function_name = "Sys.init";
-        let args_count = 0;
+        if mode == BootstrapMode::Stack {
+            return;
+        }
 
-        self.return_counter += 1;
-        let return_address_label = Self::get_return_symbol(function_name, self.return_counter);
+        let function_name = &bootstrap.entry;
+        let return_address_label = format!("__bootstrap__{function_name}__return");
+        let light = self.leaves.contains(function_name);
+        Self::write_frame(out_file, function_name, 0, &return_address_label, light);
+    }
+
+    // The code of `call` after the return address, and the code of `return`, are written once
+    // and jumped to. `call` passes the return address in D, the argument count in R13 and the
+    // callee in R14. Both are jumped over, so that the code behind them runs as without them.
+    fn write_shared_call(&mut self) {
+        let out_file = self
+            .output_file
+            .as_mut()
+            .expect("Target file not set. Call set_filename() before writing commands.");
 
         write!(
             out_file,
             "\
-                @{return_address_label}\n\
-                D=A\n\
+                // ----------- shared call -------------\n\
+                @__shared__end\n\
+                0;JMP\n\
+                (__call)\n\
                 {}",
             Self::get_push_code()
         )
@@ -82,19 +258,26 @@ impl CodeWriter {
                 M=D\n\
                 @5\n\
                 D=D-A\n\
-                @{args_count}\n\
-                D=D-A\n\
+                @R13\n\
+                D=D-M\n\
                 @ARG\n\
                 M=D\n\
-                @{function_name}\n\
+                @R14\n\
+                A=M\n\
                 0;JMP\n\
-                ({return_address_label})\n\
-            "
+                // ----------- shared return -------------\n\
+                (__return)\n\
+                {}\
+                (__shared__end)\n\
+            ",
+            Self::get_return_code()
         )
         .unwrap();
     }
 
-    pub fn write_arithemtic(&mut self, command: &str) {
+    pub fn write_arithemtic(&mut self, command: ArithmeticOp) {
+        use ArithmeticOp::*;
+
         let out_file = self
             .output_file
             .as_mut()
@@ -105,12 +288,12 @@ impl CodeWriter {
             .expect("Target module not set. Call set_module_name() before writing commands.");
 
         match command {
-            "add" | "sub" | "and" | "or" => {
+            Add | Sub | And | Or => {
                 let operator = match command {
-                    "add" => "+",
-                    "sub" => "-",
-                    "and" => "&",
-                    "or" => "|",
+                    Add => "+",
+                    Sub => "-",
+                    And => "&",
+                    Or => "|",
                     _ => unreachable!("Only binary operations should reach here."),
                 };
                 write!(
@@ -125,10 +308,10 @@ impl CodeWriter {
                 )
                 .unwrap();
             }
-            "neg" | "not" => {
+            Neg | Not => {
                 let operator = match command {
-                    "neg" => "-",
-                    "not" => "!",
+                    Neg => "-",
+                    Not => "!",
                     _ => unreachable!("Only unary operations should reach here."),
                 };
                 write!(
@@ -141,14 +324,14 @@ impl CodeWriter {
                 )
                 .unwrap();
             }
-            "eq" | "gt" | "lt" => {
-                let branch = command;
+            Eq | Gt | Lt => {
+                let branch = command.name();
                 let id = self.jump_counter;
                 self.jump_counter += 1;
                 let jump_instruction = match command {
-                    "eq" => "JEQ",
-                    "gt" => "JGT",
-                    "lt" => "JLT",
+                    Eq => "JEQ",
+                    Gt => "JGT",
+                    Lt => "JLT",
                     _ => unreachable!("Only eq, gt, lt commands should be reachable."),
                 };
 
@@ -175,12 +358,11 @@ impl CodeWriter {
                 )
                 .unwrap();
             }
-            c => panic!("Non-arithmetic command {c} encountered."),
         }
     }
 
-    pub fn write_pushpop(&mut self, command: CommandType, segment: &str, index: u16) {
-        use CommandType::*;
+    pub fn write_pushpop(&mut self, command: &VmCommand) {
+        use Segment::*;
 
         let out_file = self
             .output_file
@@ -203,10 +385,10 @@ impl CodeWriter {
          *                                         |  pointer
          *                                         |  constant
          */
-        match command {
-            Push => {
+        match *command {
+            VmCommand::Push(segment, index) => {
                 match segment {
-                    "constant" => {
+                    Constant => {
                         let push_code = Self::get_push_code();
                         write!(
                             out_file,
@@ -217,7 +399,7 @@ impl CodeWriter {
                         )
                         .unwrap();
                     }
-                    "argument" | "local" | "this" | "that" => {
+                    Argument | Local | This | That => {
                         let seg_symbol = Self::get_segment_symbol(segment);
                         let push_code = Self::get_push_code();
 
@@ -233,12 +415,8 @@ impl CodeWriter {
                         )
                         .unwrap();
                     }
-                    "pointer" | "temp" => {
-                        let seg_symbol = match segment {
-                            "pointer" => ["THIS", "THAT"][index as usize],
-                            "temp" => ["R5", "R6", "R7", "R8", "R9", "R10", "R11", "R12"][index as usize],
-                            _ => unreachable!("Any segment other than \"pointer\" or \"temp\" should't reach here."),
-                        };
+                    Pointer | Temp => {
+                        let seg_symbol = Self::get_register_symbol(segment, index);
 
                         let push_code = Self::get_push_code();
 
@@ -251,7 +429,7 @@ impl CodeWriter {
                         )
                         .unwrap();
                     }
-                    "static" => {
+                    Static => {
                         let static_symbol = Self::get_static_symbol(filename, index);
                         let push_code = Self::get_push_code();
 
@@ -264,14 +442,13 @@ impl CodeWriter {
                         )
                         .unwrap();
                     }
-                    s => panic!("Invalid segment '{s}' encountered"),
                 }
             }
-            Pop => match segment {
-                "constant" => {
+            VmCommand::Pop(segment, index) => match segment {
+                Constant => {
                     panic!("the \"constant\" segment is virtual. It cannot be written to.")
                 }
-                "argument" | "local" | "this" | "that" => {
+                Argument | Local | This | That => {
                     let seg_symbol = Self::get_segment_symbol(segment);
 
                     write!(
@@ -293,12 +470,8 @@ impl CodeWriter {
                     )
                     .unwrap();
                 }
-                "pointer" | "temp" => {
-                    let seg_symbol = match segment {
-                            "pointer" => ["THIS", "THAT"][index as usize],
-                            "temp" => ["R5", "R6", "R7", "R8", "R9", "R10", "R11", "R12"][index as usize],
-                            _ => unreachable!("Any segment other than \"pointer\" or \"temp\" should't reach here. Encountered {}", segment),
-                        };
+                Pointer | Temp => {
+                    let seg_symbol = Self::get_register_symbol(segment, index);
 
                     write!(
                         out_file,
@@ -312,7 +485,7 @@ impl CodeWriter {
                     )
                     .unwrap();
                 }
-                "static" => {
+                Static => {
                     let static_symbol = Self::get_static_symbol(filename, index);
 
                     write!(
@@ -327,7 +500,6 @@ impl CodeWriter {
                     )
                     .unwrap();
                 }
-                s => panic!("Invalid segment {} encountered.", s),
             },
             _ => panic!("Invalid command {:?} for write_pushpop. This function only concerns push and pop commands.", command),
         }
@@ -338,9 +510,12 @@ impl CodeWriter {
             .output_file
             .as_mut()
             .expect("Target file not set. Call set_filename() before writing commands.");
+        // the labels of a program without bootstrap may lie outside of any function, and are
+        // scoped to the module then
         let func_name = self
-            .module
+            .function
             .as_ref()
+            .or(self.module.as_ref())
             .expect("Target module not set. Call set_module_name() before writing commands.");
 
         if !Self::is_valid_label(label) {
@@ -348,7 +523,7 @@ impl CodeWriter {
         }
 
         // labels are scoped inside a function, therefore we decorate given label with the current function name
-        let function_local_label = Self::get_function_label(&func_name, label);
+        let function_local_label = Self::get_function_label(func_name, label);
         writeln!(out_file, "({function_local_label})").unwrap();
     }
 
@@ -358,15 +533,16 @@ impl CodeWriter {
             .as_mut()
             .expect("Target file not set. Call set_filename() before writing commands.");
         let func_name = self
-            .module
+            .function
             .as_ref()
+            .or(self.module.as_ref())
             .expect("Target module not set. Call set_module_name() before writing commands.");
 
         if !Self::is_valid_label(label) {
             panic!("The label {label} is not valid.");
         }
 
-        let function_local_label = Self::get_function_label(&func_name, label);
+        let function_local_label = Self::get_function_label(func_name, label);
         write!(
             out_file,
             "\
@@ -383,15 +559,16 @@ impl CodeWriter {
             .as_mut()
             .expect("Target file not set. Call set_filename() before writing commands.");
         let func_name = self
-            .module
+            .function
             .as_ref()
+            .or(self.module.as_ref())
             .expect("Target module not set. Call set_module_name() before writing commands.");
 
         if !Self::is_valid_label(label) {
             panic!("The label {label} is not valid.");
         }
 
-        let function_local_label = Self::get_function_label(&func_name, label);
+        let function_local_label = Self::get_function_label(func_name, label);
         write!(
             out_file,
             "\
@@ -410,10 +587,44 @@ impl CodeWriter {
             .output_file
             .as_mut()
             .expect("Target file not set. Call set_filename() before writing commands.");
+        let module = self
+            .module
+            .as_ref()
+            .expect("Target module not set. Call set_module_name() before writing commands.");
 
         self.return_counter += 1;
-        let return_address_label = Self::get_return_symbol(function_name, self.return_counter);
+        let return_address_label = Self::get_return_symbol(module, function_name, self.return_counter);
+        let light = self.leaves.contains(function_name);
 
+        if self.options.compact && !light {
+            write!(
+                out_file,
+                "\
+                    @{args_count}\n\
+                    D=A\n\
+                    @R13\n\
+                    M=D\n\
+                    @{function_name}\n\
+                    D=A\n\
+                    @R14\n\
+                    M=D\n\
+                    @{return_address_label}\n\
+                    D=A\n\
+                    @__call\n\
+                    0;JMP\n\
+                    ({return_address_label})\n\
+                "
+            )
+            .unwrap();
+            return;
+        }
+
+        Self::write_frame(out_file, function_name, args_count, &return_address_label, light);
+    }
+
+    // Pushes the frame of a call and jumps to the callee. A light frame only holds the return
+    // address, LCL and ARG, for a callee of `leaf_functions`.
+    fn write_frame(out_file: &mut W, function_name: &str, args_count: u16, return_address_label: &str, light: bool) {
         write!(
             out_file,
             "\
@@ -426,10 +637,13 @@ impl CodeWriter {
 
         Self::push_symbol(out_file, "LCL");
         Self::push_symbol(out_file, "ARG");
-        Self::push_symbol(out_file, "THIS");
-        Self::push_symbol(out_file, "THAT");
+        if !light {
+            Self::push_symbol(out_file, "THIS");
+            Self::push_symbol(out_file, "THAT");
+        }
+        let frame = if light { 3 } else { 5 };
 
-        // ARG = SP-n-5,
+        // ARG = SP-n-5 (SP-n-3 for a light frame),
         // LCL = SP
         write!(
             out_file,
@@ -438,7 +652,7 @@ impl CodeWriter {
                 D=M\n\
                 @LCL\n\
                 M=D\n\
-                @5\n\
+                @{frame}\n\
                 D=D-A\n\
                 @{args_count}\n\
                 D=D-A\n\
@@ -459,6 +673,8 @@ impl CodeWriter {
             .expect("Target file not set. Call set_module_name() before writing commands.");
 
         writeln!(out_file, "({function_name})").unwrap();
+        self.function = Some(function_name.to_string());
+        self.leaf = false;
 
         // push 0's `locals_count` times
         if locals_count >= 1 {
@@ -513,20 +729,111 @@ impl CodeWriter {
     // LCL = *(FRAME - 4)
     // goto RET
     pub fn write_return(&mut self) {
+        if self.function.as_ref().is_some_and(|function| self.leaves.contains(function)) {
+            self.write_light_return();
+            return;
+        }
+        if self.leaf {
+            self.write_leaf_return();
+            return;
+        }
+
         let out_file = self
             .output_file
             .as_mut()
             .expect("Target file not set. Call set_filename() before writing commands.");
 
+        if self.options.compact {
+            write!(
+                out_file,
+                "\
+                    @__return\n\
+                    0;JMP\n\
+                "
+            )
+            .unwrap();
+            return;
+        }
+
         write!(
             out_file,
             "\
                 // ----------- return -------------\n\
+                {}\
+                // ----------- return finish -------------\n\
+            ",
+            Self::get_return_code()
+        )
+        .unwrap();
+    }
+
+    // The routine leaves its return value in D.
+    fn write_native(&mut self, routine: &NativeRoutine) {
+        let out_file = self
+            .output_file
+            .as_mut()
+            .expect("Target file not set. Call set_filename() before writing commands.");
+
+        writeln!(out_file, "({})", routine.function).unwrap();
+        out_file.write_all(routine.asm.as_bytes()).unwrap();
+        write!(
+            out_file,
+            "\
+                // ----------- native return -------------\n\
+                @R13\n\
+                M=D\n\
                 @LCL\n\
                 D=M\n\
+                @5\n\
+                A=D-A\n\
+                D=M\n\
+                @R14\n\
+                M=D\n\
+\n\
                 @R13\n\
+                D=M\n\
+                @ARG\n\
+                A=M\n\
                 M=D\n\
 \n\
+                @ARG\n\
+                D=M+1\n\
+                @SP\n\
+                M=D\n\
+            "
+        )
+        .unwrap();
+        Self::restore_symbol(out_file, "ARG", 3);
+        Self::restore_symbol(out_file, "LCL", 4);
+        write!(
+            out_file,
+            "\
+                @R14\n\
+                A=M\n\
+                0;JMP\n\
+                // ----------- native return finish -------------\n\
+            "
+        )
+        .unwrap();
+
+        self.function = Some(routine.function.to_string());
+        self.leaf = false;
+    }
+
+    // A function without locals and calls never changes LCL, so its frame is addressed through
+    // LCL directly. THIS and THAT only need to be restored if the function set them.
+    fn write_leaf_return(&mut self) {
+        let out_file = self
+            .output_file
+            .as_mut()
+            .expect("Target file not set. Call set_filename() before writing commands.");
+
+        write!(
+            out_file,
+            "\
+                // ----------- leaf return -------------\n\
+                @LCL\n\
+                D=M\n\
                 @5\n\
                 A=D-A\n\
                 D=M\n\
@@ -534,7 +841,7 @@ impl CodeWriter {
                 M=D\n\
 \n\
                 @SP\n\
-                AM=M-1\n\
+                A=M-1\n\
                 D=M\n\
                 @ARG\n\
                 A=M\n\
@@ -544,41 +851,172 @@ impl CodeWriter {
                 D=M+1\n\
                 @SP\n\
                 M=D\n\
-\n\
-                @R13\n\
-                AM=M-1\n\
+            "
+        )
+        .unwrap();
+
+        if self.leaf_sets_pointers {
+            Self::restore_symbol(out_file, "THAT", 1);
+            Self::restore_symbol(out_file, "THIS", 2);
+        }
+        Self::restore_symbol(out_file, "ARG", 3);
+        Self::restore_symbol(out_file, "LCL", 4);
+
+        write!(
+            out_file,
+            "\
+                @R14\n\
+                A=M\n\
+                0;JMP\n\
+                // ----------- leaf return finish -------------\n\
+            "
+        )
+        .unwrap();
+    }
+
+    // The return of a function of `leaf_functions`, whose light frame holds the return address
+    // at LCL-3, followed by LCL and ARG.
+    fn write_light_return(&mut self) {
+        let out_file = self
+            .output_file
+            .as_mut()
+            .expect("Target file not set. Call set_filename() before writing commands.");
+
+        write!(
+            out_file,
+            "\
+                // ----------- light return -------------\n\
+                @LCL\n\
                 D=M\n\
-                @THAT\n\
-                M=D\n\
-\n\
-                @R13\n\
-                AM=M-1\n\
+                @3\n\
+                A=D-A\n\
                 D=M\n\
-                @THIS\n\
+                @R14\n\
                 M=D\n\
 \n\
-                @R13\n\
-                AM=M-1\n\
+                @SP\n\
+                A=M-1\n\
                 D=M\n\
                 @ARG\n\
+                A=M\n\
                 M=D\n\
 \n\
+                @ARG\n\
+                D=M+1\n\
+                @SP\n\
+                M=D\n\
+            "
+        )
+        .unwrap();
+
+        Self::restore_symbol(out_file, "ARG", 1);
+        Self::restore_symbol(out_file, "LCL", 2);
+
+        write!(
+            out_file,
+            "\
+                @R14\n\
+                A=M\n\
+                0;JMP\n\
+                // ----------- light return finish -------------\n\
+            "
+        )
+        .unwrap();
+    }
+
+    // A `call` immediately followed by `return` reuses the frame of the current function: the
+    // frame saved by its caller is pushed above the arguments, and both are moved down to ARG,
+    // where the callee's frame would begin if the caller had called it directly.
+    pub fn write_tail_call(&mut self, function_name: &str, args_count: u16) {
+        self.write_tail_frame(function_name, args_count);
+        self.write_tail_jump(function_name);
+    }
+
+    fn write_tail_frame(&mut self, function_name: &str, args_count: u16) {
+        let out_file = self
+            .output_file
+            .as_mut()
+            .expect("Target file not set. Call set_filename() before writing commands.");
+
+        writeln!(out_file, "// ----------- tail call {function_name} -------------").unwrap();
+
+        // the saved return address, LCL, ARG, THIS and THAT are at LCL-5 to LCL-1
+        for offset in (1..=5).rev() {
+            write!(
+                out_file,
+                "\
+                    @LCL\n\
+                    D=M\n\
+                    @{offset}\n\
+                    A=D-A\n\
+                    D=M\n\
+                    {}",
+                Self::get_push_code()
+            )
+            .unwrap();
+        }
+
+        // R13 = source, R14 = destination. the destination never lies above the source, so
+        // copying upwards from the lowest word is safe.
+        let block = args_count + 5;
+        write!(
+            out_file,
+            "\
+                @SP\n\
+                D=M\n\
+                @{block}\n\
+                D=D-A\n\
                 @R13\n\
-                AM=M-1\n\
+                M=D\n\
+                @ARG\n\
                 D=M\n\
-                @LCL\n\
+                @R14\n\
                 M=D\n\
-\n\
+            "
+        )
+        .unwrap();
+        for _ in 0..block {
+            write!(
+                out_file,
+                "\
+                    @R13\n\
+                    AM=M+1\n\
+                    A=A-1\n\
+                    D=M\n\
+                    @R14\n\
+                    AM=M+1\n\
+                    A=A-1\n\
+                    M=D\n\
+                "
+            )
+            .unwrap();
+        }
+    }
+
+    // the callee's frame ends where the copy ended
+    fn write_tail_jump(&mut self, function_name: &str) {
+        let out_file = self
+            .output_file
+            .as_mut()
+            .expect("Target file not set. Call set_filename() before writing commands.");
+
+        write!(
+            out_file,
+            "\
                 @R14\n\
-                A=M\n\
+                D=M\n\
+                @LCL\n\
+                M=D\n\
+                @SP\n\
+                M=D\n\
+                @{function_name}\n\
                 0;JMP\n\
-                // ----------- return finish -------------\n\
             "
         )
         .unwrap();
     }
 
-    fn push_symbol(out_file: &mut File, symbol: &str) {
+    fn push_symbol(out_file: &mut W, symbol: &str) {
         let push_code = Self::get_push_code();
 
         write!(
@@ -591,13 +1029,39 @@ impl CodeWriter {
         .unwrap();
     }
 
-    fn get_segment_symbol(segment: &str) -> &'static str {
+    // symbol = *(LCL - offset)
+    fn restore_symbol(out_file: &mut W, symbol: &str, offset: u16) {
+        write!(
+            out_file,
+            "\
+                @LCL\n\
+                D=M\n\
+                @{offset}\n\
+                A=D-A\n\
+                D=M\n\
+                @{symbol}\n\
+                M=D\n\
+            "
+        )
+        .unwrap();
+    }
+
+    fn get_segment_symbol(segment: Segment) -> &'static str {
+        match segment {
+            Segment::Argument => "ARG",
+            Segment::Local => "LCL",
+            Segment::This => "THIS",
+            Segment::That => "THAT",
+            _ => panic!("Segment {} has no base register.", segment.name()),
+        }
+    }
+
+    // the pointer and temp segments are mapped directly onto registers
+    fn get_register_symbol(segment: Segment, index: u16) -> &'static str {
         match segment {
-            "argument" => "ARG",
-            "local" => "LCL",
-            "this" => "THIS",
-            "that" => "THAT",
-            _ => panic!("Invalid segment name {} encountered.", segment),
+            Segment::Pointer => ["THIS", "THAT"][index as usize],
+            Segment::Temp => ["R5", "R6", "R7", "R8", "R9", "R10", "R11", "R12"][index as usize],
+            _ => unreachable!("Only the pointer and temp segments are mapped onto registers."),
         }
     }
 
@@ -608,14 +1072,70 @@ impl CodeWriter {
         format!("{basename}.{index}")
     }
 
-    fn get_return_symbol(function_name: &str, counter: u16) -> String {
-        format!("{function_name}__return_{}", counter)
+    fn get_return_symbol(module: &str, function_name: &str, counter: u16) -> String {
+        let basename = module.strip_suffix(".vm").unwrap_or(module);
+        format!("__{basename}__{function_name}__return_{}", counter)
     }
 
     fn get_function_label(function_name: &str, label: &str) -> String {
         format!("{function_name}_local__{label}")
     }
 
+    fn get_return_code() -> &'static str {
+        "\
+            @LCL\n\
+            D=M\n\
+            @R13\n\
+            M=D\n\
+\n\
+            @5\n\
+            A=D-A\n\
+            D=M\n\
+            @R14\n\
+            M=D\n\
+\n\
+            @SP\n\
+            AM=M-1\n\
+            D=M\n\
+            @ARG\n\
+            A=M\n\
+            M=D\n\
+\n\
+            @ARG\n\
+            D=M+1\n\
+            @SP\n\
+            M=D\n\
+\n\
+            @R13\n\
+            AM=M-1\n\
+            D=M\n\
+            @THAT\n\
+            M=D\n\
+\n\
+            @R13\n\
+            AM=M-1\n\
+            D=M\n\
+            @THIS\n\
+            M=D\n\
+\n\
+            @R13\n\
+            AM=M-1\n\
+            D=M\n\
+            @ARG\n\
+            M=D\n\
+\n\
+            @R13\n\
+            AM=M-1\n\
+            D=M\n\
+            @LCL\n\
+            M=D\n\
+\n\
+            @R14\n\
+            A=M\n\
+            0;JMP\n\
+        "
+    }
+
     // Push content in D register on top of stack and increment SP by 1
     fn get_push_code() -> &'static str {
         "\
@@ -628,7 +1148,7 @@ impl CodeWriter {
     }
 
     fn is_valid_label(label: &str) -> bool {
-        if label.len() == 0 {
+        if label.is_empty() {
             return false;
         }
 
@@ -649,3 +1169,397 @@ impl CodeWriter {
         true
     }
 }
+
+/// The functions of a program that get a light frame with `optimize`: functions without locals
+/// that make no calls and leave THIS and THAT alone. Their frame does not need THIS and THAT,
+/// so it only holds the return address, LCL and ARG. Functions replaced by a routine of
+/// `native_os` keep the full frame its return expects.
+pub fn leaf_functions(modules: &[(String, Vec<Line>)], options: &Options) -> HashSet<String> {
+    let mut leaves = HashSet::new();
+    if !options.optimize {
+        return leaves;
+    }
+
+    for (_, commands) in modules {
+        let mut current: Option<&str> = None;
+        for line in commands {
+            match &line.command {
+                VmCommand::Function(name, locals) => {
+                    leaves.extend(current.map(str::to_string));
+                    let native = options.native_os && NativeRoutine::find(name).is_some();
+                    current = (*locals == 0 && !native).then_some(name.as_str());
+                }
+                VmCommand::Call(..) | VmCommand::Pop(Segment::Pointer, _) => current = None,
+                _ => {}
+            }
+        }
+        leaves.extend(current.map(str::to_string));
+    }
+    leaves
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::fs;
+
+    use emulator::cpu::Cpu;
+
+    use super::CodeWriter;
+    use crate::bootstrap::{Bootstrap, BootstrapMode};
+    use crate::cache::source_hash;
+    use crate::options::Options;
+    use crate::parser::parse;
+
+    // translates the modules behind the given bootstrap code
+    pub(crate) fn write_asm(
+        name: &str,
+        modules: &[(&str, &str)],
+        options: &Options,
+        mode: BootstrapMode,
+        bootstrap: &Bootstrap,
+    ) -> String {
+        let path = std::env::temp_dir().join(format!(
+            "vm-to-asm-test-{name}-{:016x}-{}.asm",
+            source_hash(name, &[], &[], options),
+            std::process::id()
+        ));
+        let modules = modules
+            .iter()
+            .map(|(module, source)| (module.to_string(), parse(source).unwrap()))
+            .collect::<Vec<_>>();
+        let mut writer = CodeWriter::with_options(options);
+        writer.set_output_filename(path.to_str().unwrap());
+        writer.set_program(&modules);
+        writer.write_init(mode, bootstrap);
+        for (module, commands) in &modules {
+            writer.write_module(module, commands);
+        }
+        writer.close();
+
+        let asm = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        asm
+    }
+
+    pub(crate) fn assemble(asm: &str) -> Vec<u16> {
+        assembler::stream::assemble(asm.as_bytes()).unwrap()
+    }
+
+    // translates the modules behind the given bootstrap code, and assembles them
+    pub(crate) fn translate(
+        name: &str,
+        modules: &[(&str, &str)],
+        options: &Options,
+        mode: BootstrapMode,
+        bootstrap: &Bootstrap,
+    ) -> Vec<u16> {
+        assemble(&write_asm(name, modules, options, mode, bootstrap))
+    }
+
+    // translates the modules behind the full bootstrap, and runs the program until it is idle
+    pub(crate) fn run(name: &str, modules: &[(&str, &str)], options: &Options) -> Cpu {
+        let program = translate(name, modules, options, BootstrapMode::Full, &Bootstrap::default());
+        let mut cpu = Cpu::new();
+        cpu.load(&program);
+        assert!(cpu.run_until_idle(100_000_000), "The program did not finish ({name}, {options}).");
+        cpu
+    }
+
+    const OPTIMIZE: Options = Options {
+        optimize: true,
+        native_os: false,
+        verbose: false,
+        compact: false,
+    };
+
+    // Main.count(n, acc) counts n down to 0 by tail recursion, returning acc + n
+    const COUNT: &str = "
+        function Main.count 0
+        push argument 0
+        if-goto RECURSE
+        push argument 1
+        return
+        label RECURSE
+        push argument 0
+        push constant 1
+        sub
+        push argument 1
+        push constant 1
+        add
+        call Main.count 2
+        return
+    ";
+
+    #[test]
+    fn test_tail_call() {
+        let sys = "
+            function Sys.init 0
+            push constant 100
+            push constant 5
+            call Main.count 2
+            pop static 0
+            label END
+            goto END
+        ";
+        let modules = [("Sys.vm", sys), ("Main.vm", COUNT)];
+
+        let plain = run("tail-call", &modules, &Options::default());
+        let optimized = run("tail-call", &modules, &OPTIMIZE);
+        assert_eq!(plain.ram[16], 105);
+        assert_eq!(optimized.ram[16], 105);
+        assert_eq!(optimized.ram[0], plain.ram[0]);
+    }
+
+    #[test]
+    fn test_function_labels() {
+        // as in Sys.vm of project 08, two functions of one module use the same label
+        let main = "
+            function Main.f 0
+            goto END
+            push constant 1
+            return
+            label END
+            push constant 10
+            return
+            function Main.g 0
+            goto END
+            push constant 2
+            return
+            label END
+            push constant 20
+            return
+        ";
+        let sys = "
+            function Sys.init 0
+            call Main.f 0
+            pop static 0
+            call Main.g 0
+            pop static 1
+            label END
+            goto END
+        ";
+        let cpu = run("function-labels", &[("Sys.vm", sys), ("Main.vm", main)], &Options::default());
+        assert_eq!(&cpu.ram[16..18], &[10, 20]);
+    }
+
+    #[test]
+    fn test_compact() {
+        let sys = "
+            function Sys.init 0
+            push constant 100
+            push constant 5
+            call Main.count 2
+            pop static 0
+            label END
+            goto END
+        ";
+        let modules = [("Sys.vm", sys), ("Main.vm", COUNT)];
+        let compact = Options {
+            compact: true,
+            ..Options::default()
+        };
+
+        let plain = run("compact", &modules, &Options::default());
+        for options in [compact.clone(), Options { compact: true, ..OPTIMIZE }] {
+            let cpu = run("compact", &modules, &options);
+            assert_eq!(cpu.ram[16], 105, "{options}");
+            assert_eq!(cpu.ram[0], plain.ram[0], "{options}");
+        }
+        let size = |options| translate("compact", &modules, options, BootstrapMode::Full, &Bootstrap::default()).len();
+        assert!(size(&compact) < size(&Options::default()));
+
+        // a program without bootstrap starts by jumping over the shared code
+        let main = "
+            push constant 7
+            pop static 0
+            label END
+            goto END
+        ";
+        let program = translate("compact-none", &[("Main.vm", main)], &compact, BootstrapMode::None, &Bootstrap::default());
+        let mut cpu = Cpu::new();
+        cpu.load(&program);
+        cpu.ram[0] = 256;
+        assert!(cpu.run_until_idle(1000));
+        assert_eq!(cpu.ram[16], 7);
+    }
+
+    #[test]
+    fn test_deep_recursion() {
+        // the Fibonacci program of project 8 gives the result its test expects
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../projects/08/FunctionCalls/FibonacciElement");
+        let main = fs::read_to_string(dir.join("Main.vm")).unwrap();
+        let sys = fs::read_to_string(dir.join("Sys.vm")).unwrap();
+        let cpu = run("fibonacci", &[("Main.vm", &main), ("Sys.vm", &sys)], &OPTIMIZE);
+        assert_eq!((cpu.ram[0], cpu.ram[261]), (262, 3));
+
+        // the same sequence computed by tail calls, as deep as 20000 frames of 8 words, which
+        // would not fit into RAM
+        let main = "
+            function Main.fibonacci 0
+            push argument 0
+            if-goto NEXT
+            push argument 1
+            return
+            label NEXT
+            push argument 0
+            push constant 1
+            sub
+            push argument 2
+            push argument 1
+            push argument 2
+            add
+            call Main.fibonacci 3
+            return
+        ";
+        let sys = "
+            function Sys.init 0
+            push constant 20000
+            push constant 0
+            push constant 1
+            call Main.fibonacci 3
+            pop static 0
+            label END
+            goto END
+        ";
+        let cpu = run("deep-fibonacci", &[("Sys.vm", sys), ("Main.vm", main)], &OPTIMIZE);
+        let (mut a, mut b) = (0u16, 1u16);
+        for _ in 0..20000 {
+            (a, b) = (b, a.wrapping_add(b));
+        }
+        assert_eq!(cpu.ram[16], a);
+        // only the frame of Sys.init is left on the stack
+        assert_eq!(cpu.ram[0], 261);
+    }
+
+    #[test]
+    fn test_tail_call_frames() {
+        // Main.even and Main.odd call each other with different argument counts, so the frame
+        // moves up and down. THIS and THAT of Sys.init must survive.
+        let main = "
+            function Main.even 1
+            push argument 0
+            pop local 0
+            push local 0
+            if-goto ODD
+            push constant 1
+            return
+            label ODD
+            push local 0
+            push constant 1
+            sub
+            push constant 7
+            push constant 8
+            push constant 3000
+            pop pointer 0
+            call Main.odd 3
+            return
+            function Main.odd 0
+            push argument 0
+            if-goto EVEN
+            push argument 1
+            push argument 2
+            add
+            return
+            label EVEN
+            push argument 0
+            push constant 1
+            sub
+            push constant 4000
+            pop pointer 1
+            call Main.even 1
+            return
+        ";
+        let sys = |n: u16| {
+            format!(
+                "
+                function Sys.init 0
+                push constant 1234
+                pop pointer 0
+                push constant 4321
+                pop pointer 1
+                push constant {n}
+                call Main.even 1
+                pop static 0
+                push pointer 0
+                pop static 1
+                push pointer 1
+                pop static 2
+                label END
+                goto END
+                "
+            )
+        };
+
+        for (n, expected) in [(0, 1), (10, 1), (11, 15), (3001, 15)] {
+            let sys = sys(n);
+            let modules = [("Sys.vm", sys.as_str()), ("Main.vm", main)];
+            let cpu = run("tail-call-frames", &modules, &OPTIMIZE);
+            assert_eq!(&cpu.ram[16..19], &[expected, 1234, 4321], "n = {n}");
+            if n < 100 {
+                let plain = run("tail-call-frames", &modules, &Options::default());
+                assert_eq!(&plain.ram[16..19], &cpu.ram[16..19], "n = {n}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_leaf_functions() {
+        // Main.double is a leaf that leaves THIS and THAT alone, Main.point sets THAT
+        let main = "
+            function Main.double 0
+            push argument 0
+            push argument 0
+            add
+            return
+            function Main.point 0
+            push argument 0
+            pop pointer 1
+            push that 0
+            return
+        ";
+        let sys = "
+            function Sys.init 1
+            push constant 1000
+            pop pointer 0
+            push constant 2000
+            pop pointer 1
+            push constant 77
+            pop temp 0
+            push constant 21
+            call Main.double 1
+            pop static 0
+            push constant 3000
+            pop pointer 0
+            push constant 42
+            pop this 0
+            push constant 3000
+            call Main.point 1
+            pop static 1
+            push pointer 0
+            pop static 2
+            push pointer 1
+            pop static 3
+            label END
+            goto END
+        ";
+        let modules = [("Sys.vm", sys), ("Main.vm", main)];
+
+        let plain = run("leaf", &modules, &Options::default());
+        let optimized = run("leaf", &modules, &OPTIMIZE);
+        assert_eq!(&plain.ram[16..20], &[42, 42, 3000, 2000]);
+        assert_eq!(&optimized.ram[16..20], &plain.ram[16..20]);
+        assert_eq!(optimized.ram[0], plain.ram[0]);
+        assert!(optimized.cycles < plain.cycles);
+
+        // only the call to Main.double leaves THIS and THAT out of the frame
+        let asm = write_asm("leaf", &modules, &OPTIMIZE, BootstrapMode::Full, &Bootstrap::default());
+        assert_eq!(asm.matches("// ----------- light return -------------").count(), 1);
+        assert_eq!(asm.matches("// ----------- leaf return -------------").count(), 1);
+        let frames = |saved: &str| asm.matches(saved).count();
+        let push = "D=M\n@SP\nA=M\nM=D\n@SP\nM=M+1\n";
+        let full = format!("@LCL\n{push}@ARG\n{push}@THIS\n{push}@THAT\n{push}");
+        assert_eq!((frames(&format!("@LCL\n{push}@ARG\n{push}")), frames(&full)), (3, 2));
+        let compact = Options { compact: true, ..OPTIMIZE };
+        assert_eq!(&run("leaf", &modules, &compact).ram[16..20], &plain.ram[16..20]);
+    }
+}
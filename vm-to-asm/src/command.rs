@@ -1,12 +1,131 @@
-#[derive(Debug)]
-pub enum CommandType {
-    Arithmetic,
-    Push,
-    Pop,
-    Label,
-    Goto,
-    If,
-    Function,
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArithmeticOp {
+    Add,
+    Sub,
+    Neg,
+    Eq,
+    Gt,
+    Lt,
+    And,
+    Or,
+    Not,
+}
+
+impl ArithmeticOp {
+    pub fn from_name(name: &str) -> Option<Self> {
+        use ArithmeticOp::*;
+        Some(match name {
+            "add" => Add,
+            "sub" => Sub,
+            "neg" => Neg,
+            "eq" => Eq,
+            "gt" => Gt,
+            "lt" => Lt,
+            "and" => And,
+            "or" => Or,
+            "not" => Not,
+            _ => return None,
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        use ArithmeticOp::*;
+        match self {
+            Add => "add",
+            Sub => "sub",
+            Neg => "neg",
+            Eq => "eq",
+            Gt => "gt",
+            Lt => "lt",
+            And => "and",
+            Or => "or",
+            Not => "not",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Segment {
+    Argument,
+    Local,
+    Static,
+    Constant,
+    This,
+    That,
+    Pointer,
+    Temp,
+}
+
+impl Segment {
+    pub fn from_name(name: &str) -> Option<Self> {
+        use Segment::*;
+        Some(match name {
+            "argument" => Argument,
+            "local" => Local,
+            "static" => Static,
+            "constant" => Constant,
+            "this" => This,
+            "that" => That,
+            "pointer" => Pointer,
+            "temp" => Temp,
+            _ => return None,
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        use Segment::*;
+        match self {
+            Argument => "argument",
+            Local => "local",
+            Static => "static",
+            Constant => "constant",
+            This => "this",
+            That => "that",
+            Pointer => "pointer",
+            Temp => "temp",
+        }
+    }
+
+    /// The largest index the segment accepts. Statics live in RAM[16..255], and constants
+    /// must fit in an A instruction.
+    pub fn max_index(self) -> u16 {
+        use Segment::*;
+        match self {
+            Pointer => 1,
+            Temp => 7,
+            Static => 239,
+            Argument | Local | Constant | This | That => 32767,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VmCommand {
+    Arithmetic(ArithmeticOp),
+    Push(Segment, u16),
+    Pop(Segment, u16),
+    Label(String),
+    Goto(String),
+    If(String),
+    Function(String, u16),
+    Call(String, u16),
     Return,
-    Call,
+}
+
+impl fmt::Display for VmCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VmCommand::Arithmetic(op) => write!(f, "{}", op.name()),
+            VmCommand::Push(segment, index) => write!(f, "push {} {index}", segment.name()),
+            VmCommand::Pop(segment, index) => write!(f, "pop {} {index}", segment.name()),
+            VmCommand::Label(label) => write!(f, "label {label}"),
+            VmCommand::Goto(label) => write!(f, "goto {label}"),
+            VmCommand::If(label) => write!(f, "if-goto {label}"),
+            VmCommand::Function(name, locals) => write!(f, "function {name} {locals}"),
+            VmCommand::Call(name, args) => write!(f, "call {name} {args}"),
+            VmCommand::Return => write!(f, "return"),
+        }
+    }
 }
@@ -0,0 +1,297 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::command::VmCommand;
+use crate::parser::Line;
+
+/// A program without the functions it never calls.
+pub struct Elimination {
+    pub modules: Vec<(String, Vec<Line>)>,
+    /// The (module, function) pairs that were dropped, in the order of the modules.
+    pub dropped: Vec<(String, String)>,
+}
+
+// whether `function` matches a `--keep` pattern: a function name, or `Class.*` for every
+// function of a class
+fn matches(pattern: &str, function: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => function.starts_with(prefix),
+        None => pattern == function,
+    }
+}
+
+// the commands of each function, from its `function` command up to the next one
+fn functions(commands: &[Line]) -> Vec<(&str, &[Line])> {
+    let starts = commands
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| matches!(line.command, VmCommand::Function(..)))
+        .map(|(i, _)| i)
+        .chain([commands.len()])
+        .collect::<Vec<usize>>();
+    starts
+        .windows(2)
+        .map(|bounds| {
+            let VmCommand::Function(name, _) = &commands[bounds[0]].command else {
+                unreachable!("Functions start with a function command.");
+            };
+            (name.as_str(), &commands[bounds[0]..bounds[1]])
+        })
+        .collect()
+}
+
+fn callees(commands: &[Line]) -> impl Iterator<Item = &str> {
+    commands.iter().filter_map(|line| match &line.command {
+        VmCommand::Call(callee, _) => Some(callee.as_str()),
+        _ => None,
+    })
+}
+
+/// Keeps the functions that a chain of calls reaches from `roots`, from the code outside of any
+/// function, or from a function matching one of the `keep` patterns, and drops the others.
+/// `keep` is the escape hatch for functions the calls do not show, such as those only hand-
+/// written assembly jumps to. A pattern that matches no function is an error, and so is a
+/// program with functions but nothing to start from, which would lose all of them.
+pub fn eliminate(
+    modules: &[(String, Vec<Line>)],
+    roots: &[&str],
+    keep: &[String],
+) -> Result<Elimination, Vec<String>> {
+    let calls = modules
+        .iter()
+        .flat_map(|(_, commands)| functions(commands))
+        .map(|(name, body)| (name, callees(body).collect::<Vec<&str>>()))
+        .collect::<HashMap<&str, Vec<&str>>>();
+
+    let errors = keep
+        .iter()
+        .filter(|pattern| !calls.keys().any(|function| matches(pattern, function)))
+        .map(|pattern| format!("--keep {pattern} matches no function."))
+        .collect::<Vec<String>>();
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let mut pending = roots.to_vec();
+    pending.extend(
+        calls
+            .keys()
+            .filter(|function| keep.iter().any(|pattern| matches(pattern, function))),
+    );
+    for (_, commands) in modules {
+        let top_level = commands
+            .iter()
+            .take_while(|line| !matches!(line.command, VmCommand::Function(..)));
+        pending.extend(callees(&commands[..top_level.count()]));
+    }
+    if pending.is_empty() && !calls.is_empty() {
+        return Err(vec![
+            "--eliminate-dead has no function to start from: use the full bootstrap, or name the functions to keep with --keep."
+                .to_string(),
+        ]);
+    }
+
+    let mut reachable = HashSet::new();
+    while let Some(function) = pending.pop() {
+        if reachable.insert(function) {
+            pending.extend(calls.get(function).into_iter().flatten());
+        }
+    }
+
+    let mut dropped = Vec::new();
+    let modules = modules
+        .iter()
+        .map(|(module, commands)| {
+            let top_level = commands
+                .iter()
+                .take_while(|line| !matches!(line.command, VmCommand::Function(..)))
+                .count();
+            let mut kept = commands[..top_level].to_vec();
+            for (name, body) in functions(commands) {
+                if reachable.contains(name) {
+                    kept.extend_from_slice(body);
+                } else {
+                    dropped.push((module.clone(), name.to_string()));
+                }
+            }
+            (module.clone(), kept)
+        })
+        .collect();
+
+    Ok(Elimination { modules, dropped })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::eliminate;
+    use crate::code_writer::tests::run;
+    use crate::options::Options;
+    use crate::parser::{parse, Line};
+
+    const MAIN: &str = "
+        function Main.main 0
+        push constant 6
+        push constant 7
+        call Main.multiply 2
+        pop static 0
+        push constant 0
+        return
+        function Main.multiply 0
+        push argument 0
+        push argument 1
+        call Math.multiply 2
+        return
+        function Main.unused 0
+        call Main.alsoUnused 0
+        return
+        function Main.alsoUnused 0
+        call Main.unused 0
+        return
+    ";
+
+    fn modules(sources: &[(&str, &str)]) -> Vec<(String, Vec<Line>)> {
+        sources
+            .iter()
+            .map(|(module, source)| (module.to_string(), parse(source).unwrap()))
+            .collect()
+    }
+
+    fn os(module: &str) -> String {
+        let path = format!("{}/../tools/OS/{module}.vm", env!("CARGO_MANIFEST_DIR"));
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    fn source(commands: &[Line]) -> String {
+        commands
+            .iter()
+            .map(|line| format!("{}\n", line.command))
+            .collect()
+    }
+
+    #[test]
+    fn test_eliminate() {
+        let math = os("Math");
+        let program = modules(&[("Main.vm", MAIN), ("Math.vm", &math)]);
+
+        let elimination = eliminate(&program, &["Main.main"], &[]).unwrap();
+        let dropped = elimination
+            .dropped
+            .iter()
+            .map(|(module, function)| format!("{module} {function}"))
+            .collect::<Vec<String>>();
+        assert_eq!(
+            dropped,
+            [
+                "Main.vm Main.unused",
+                "Main.vm Main.alsoUnused",
+                "Math.vm Math.init",
+                "Math.vm Math.divide",
+                "Math.vm Math.sqrt",
+                "Math.vm Math.max",
+                "Math.vm Math.min",
+            ]
+        );
+        assert!(source(&elimination.modules[1].1).starts_with("function Math.abs 0\n"));
+
+        // kept functions, whole classes, and the functions they call
+        let keep = ["Main.unused".to_string(), "Math.*".to_string()];
+        let elimination = eliminate(&program, &["Main.main"], &keep).unwrap();
+        assert!(elimination.dropped.is_empty());
+
+        let keep = ["Main.missing".to_string(), "Screen.*".to_string()];
+        assert_eq!(
+            eliminate(&program, &["Main.main"], &keep).err().unwrap(),
+            [
+                "--keep Main.missing matches no function.",
+                "--keep Screen.* matches no function."
+            ]
+        );
+    }
+
+    #[test]
+    fn test_top_level_calls() {
+        let main = "
+            call Main.main 0
+            label END
+            goto END
+            function Main.main 0
+            push constant 0
+            return
+            function Main.unused 0
+            push constant 0
+            return
+        ";
+        let program = modules(&[("Main.vm", main)]);
+        let elimination = eliminate(&program, &[], &[]).unwrap();
+        assert_eq!(
+            elimination.dropped,
+            [("Main.vm".to_string(), "Main.unused".to_string())]
+        );
+        assert_eq!(elimination.modules[0].1.len(), 6);
+    }
+
+    #[test]
+    fn test_no_roots() {
+        // without the full bootstrap and calls outside of functions, every function would go
+        let program = modules(&[("Main.vm", MAIN)]);
+        assert_eq!(
+            eliminate(&program, &[], &[]).err().unwrap(),
+            ["--eliminate-dead has no function to start from: use the full bootstrap, or name the functions to keep with --keep."]
+        );
+        let keep = ["Main.main".to_string()];
+        assert_eq!(eliminate(&program, &[], &keep).unwrap().dropped.len(), 2);
+
+        // a program without functions has nothing to drop
+        let program = modules(&[("SimpleAdd.vm", "push constant 7\npush constant 8\nadd")]);
+        assert!(eliminate(&program, &[], &[]).unwrap().dropped.is_empty());
+    }
+
+    #[test]
+    fn test_stock_os() {
+        // the whole stock OS does not fit into the ROM, but what a program calls of it does
+        let sys = "
+            function Sys.init 0
+            call Memory.init 0
+            pop temp 0
+            call Math.init 0
+            pop temp 0
+            call Main.main 0
+            pop temp 0
+            label HALT
+            goto HALT
+        ";
+        let names = [
+            "Array", "Keyboard", "Math", "Memory", "Output", "Screen", "String",
+        ];
+        let sources = names.map(os);
+        let mut program = vec![("Main.vm", MAIN), ("Sys.vm", sys)];
+        let files = names.map(|name| format!("{name}.vm"));
+        program.extend(
+            files
+                .iter()
+                .map(String::as_str)
+                .zip(sources.iter().map(String::as_str)),
+        );
+
+        let elimination = eliminate(&modules(&program), &["Sys.init"], &[]).unwrap();
+        let kept = elimination
+            .modules
+            .iter()
+            .map(|(module, commands)| (module.as_str(), source(commands)))
+            .collect::<Vec<(&str, String)>>();
+        let kept = kept
+            .iter()
+            .map(|(module, source)| (*module, source.as_str()))
+            .collect::<Vec<(&str, &str)>>();
+
+        let cpu = run("dead-code-stock-os", &kept, &Options::default());
+        assert_eq!(cpu.ram[16], 42);
+        let dropped = elimination
+            .dropped
+            .iter()
+            .map(|(_, function)| function.as_str())
+            .collect::<Vec<&str>>();
+        assert!(dropped.contains(&"Screen.drawCircle"));
+        assert!(dropped.contains(&"String.setInt"));
+        assert!(!dropped.contains(&"Math.multiply"));
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Collects the modules of a program from `.vm` files and directories holding them, e.g. the
+/// directory of an application followed by the OS. Returns (module name, path) pairs sorted by
+/// module name, so the output does not depend on the order of the arguments or of the file
+/// system. The modules may not repeat, since their statics would be shared.
+pub fn collect(paths: &[PathBuf]) -> Result<Vec<(String, PathBuf)>, Vec<String>> {
+    let mut errors = Vec::new();
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            match fs::read_dir(path) {
+                Ok(entries) => files.extend(
+                    entries
+                        .filter_map(|entry| entry.ok())
+                        .map(|entry| entry.path())
+                        .filter(|file| file.is_file() && is_vm(file)),
+                ),
+                Err(e) => errors.push(format!("{}: {e}", path.display())),
+            }
+        } else if path.is_file() && is_vm(path) {
+            files.push(path.clone());
+        } else {
+            errors.push(format!("{} is neither a .vm file nor a directory.", path.display()));
+        }
+    }
+
+    let mut modules = files
+        .into_iter()
+        .map(|file| (file.file_name().unwrap().to_string_lossy().to_string(), file))
+        .collect::<Vec<(String, PathBuf)>>();
+    modules.sort();
+
+    let mut seen: HashMap<&str, &Path> = HashMap::new();
+    for (module, file) in &modules {
+        if let Some(first) = seen.insert(module, file) {
+            errors.push(format!(
+                "{module} is given more than once: {} and {}.",
+                first.display(),
+                file.display()
+            ));
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(modules)
+    } else {
+        Err(errors)
+    }
+}
+
+fn is_vm(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "vm")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use super::collect;
+
+    // a fresh directory with the given files, each holding an empty module
+    fn dir(name: &str, files: &[&str]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("vm-to-asm-inputs-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for file in files {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        dir
+    }
+
+    fn names(modules: &[(String, PathBuf)]) -> Vec<&str> {
+        modules.iter().map(|(module, _)| module.as_str()).collect()
+    }
+
+    #[test]
+    fn test_sorted() {
+        let app = dir("app", &["Main.vm", "Ball.vm", "Notes.txt"]);
+        let os = dir("os", &["Sys.vm", "Array.vm"]);
+        let expected = ["Array.vm", "Ball.vm", "Main.vm", "Sys.vm"];
+
+        let modules = collect(&[app.clone(), os.clone()]).unwrap();
+        assert_eq!(names(&modules), expected);
+        assert_eq!(modules[1].1, app.join("Ball.vm"));
+        let modules = collect(&[os.join("Sys.vm"), app.clone(), os.join("Array.vm")]).unwrap();
+        assert_eq!(names(&modules), expected);
+
+        fs::remove_dir_all(app).unwrap();
+        fs::remove_dir_all(os).unwrap();
+    }
+
+    #[test]
+    fn test_errors() {
+        let app = dir("app-errors", &["Main.vm", "Math.vm"]);
+        let os = dir("os-errors", &["Math.vm"]);
+
+        let errors = collect(&[app.clone(), os.clone(), app.join("Missing.vm")]).unwrap_err();
+        assert_eq!(
+            errors,
+            [
+                format!("{} is neither a .vm file nor a directory.", app.join("Missing.vm").display()),
+                format!(
+                    "Math.vm is given more than once: {} and {}.",
+                    app.join("Math.vm").display(),
+                    os.join("Math.vm").display()
+                ),
+            ]
+        );
+
+        fs::remove_dir_all(app).unwrap();
+        fs::remove_dir_all(os).unwrap();
+    }
+}
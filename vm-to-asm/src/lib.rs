@@ -1,3 +1,13 @@
+pub mod bootstrap;
+pub mod cache;
 pub mod code_writer;
 pub mod command;
+pub mod dead_code;
+pub mod inputs;
+pub mod native_os;
+pub mod options;
 pub mod parser;
+pub mod program;
+pub mod source_map;
+pub mod stack_analysis;
+pub mod validation;
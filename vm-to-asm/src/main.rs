@@ -1,90 +1,184 @@
-use std::{fs, path::Path};
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
 
-use vm_to_asm::{code_writer::CodeWriter, command::CommandType, parser::Parser};
+use vm_to_asm::{
+    bootstrap::Bootstrap,
+    cache::ModuleCache,
+    code_writer::{leaf_functions, CodeWriter},
+    command::VmCommand,
+    dead_code::eliminate,
+    inputs,
+    options::Options,
+    parser::Line,
+    program::{check, roots, Program},
+    source_map::source_map,
+};
+
+// removes `--name <value>` from the arguments, returning the value
+fn option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let i = args.iter().position(|arg| arg == name)?;
+    if i + 1 >= args.len() {
+        panic!("{name} expects a value.");
+    }
+    let value = args.remove(i + 1);
+    args.remove(i);
+    Some(value)
+}
 
 fn main() {
-    let args: Vec<String> = std::env::args().collect();
+    let mut args: Vec<String> = std::env::args().collect();
+
+    // `--cache-dir <dir>` reuses the translation of modules whose source did not change
+    let cache_dir = option(&mut args, "--cache-dir");
+    // the source map is read off the comments of the verbose output
+    let source_map_file = option(&mut args, "--source-map");
 
-    if args.len() < 2 {
-        println!("help: vm-to-asm <input vm file> [output asm file]");
-        panic!("Please provide input and output filenames.");
+    // `--eliminate-dead` translates only the functions the program calls, and `--keep <function>`
+    // keeps one it does not call, or every function of a class with `Class.*`
+    let eliminate_dead = args.iter().any(|arg| arg == "--eliminate-dead");
+    args.retain(|arg| arg != "--eliminate-dead");
+    let mut keep = Vec::new();
+    while let Some(patterns) = option(&mut args, "--keep") {
+        keep.extend(patterns.split(',').map(str::to_string));
+    }
+    if !keep.is_empty() && !eliminate_dead {
+        panic!("--keep only applies with --eliminate-dead.");
     }
 
-    let filenames: Vec<String> = if args[1].ends_with(".vm") {
-        vec![args[1].to_string()]
-    } else {
-        fs::read_dir(&args[1])
-            .expect(&format!("{} is not a directory", args[1]))
-            .map(|entry| {
-                entry
-                    .unwrap()
-                    .path()
-                    .into_os_string()
-                    .into_string()
-                    .unwrap()
-            })
-            .filter(|path| path.ends_with(".vm"))
-            .collect::<Vec<String>>()
-    };
+    let mut options = Options::from_args(&mut args);
+    options.verbose |= source_map_file.is_some();
+    let bootstrap = Bootstrap::from_args(&mut args);
+
+    let output = option(&mut args, "-o");
+    let mut inputs = args[1..].iter().map(PathBuf::from).collect::<Vec<PathBuf>>();
+    // `<input> <output.asm>`, as before `-o`
+    let output = output.map(PathBuf::from).or_else(|| {
+        (inputs.len() == 2 && inputs[1].extension().is_some_and(|extension| extension == "asm"))
+            .then(|| inputs.pop().unwrap())
+    });
 
-    let mut code_writer = CodeWriter::new();
-    let output = if args.len() < 3 {
-        format!("{}.asm", &args[1][0..args[1].len() - 3])
-    } else {
-        args[2].clone()
-    };
+    if inputs.is_empty() {
+        println!(
+            "help: vm-to-asm [--cache-dir <dir>] [--optimize] [--compact] [--native-os] [--verbose] [--source-map <file>] \
+             [--bootstrap none|stack|full] [--stack-base <n>] [--entry <function>] \
+             [--eliminate-dead] [--keep <function>]... [-o <output asm file>] <input vm file or directory>..."
+        );
+        panic!("Please provide the input files.");
+    }
+
+    // `Main.vm` translates to `Main.asm`, and the directory `Pong` to `Pong/Pong.asm`
+    let output = output.unwrap_or_else(|| {
+        let first = &inputs[0];
+        if first.is_dir() {
+            first.join(first.file_name().unwrap_or_default()).with_extension("asm")
+        } else {
+            first.with_extension("asm")
+        }
+    });
+    let output = output.to_str().expect("The output path is not valid UTF-8.").to_string();
+
+    let files = inputs::collect(&inputs).unwrap_or_else(|errors| panic!("{}", errors.join("\n")));
+    let sources = files
+        .iter()
+        .map(|(module, path)| {
+            let input = fs::read_to_string(path)
+                .unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+            (module.clone(), input)
+        })
+        .collect::<Vec<(String, String)>>();
 
+    let Program { mode, mut modules } =
+        check(&sources, &options, &bootstrap).unwrap_or_else(|errors| panic!("{}", errors.join("\n")));
+
+    if eliminate_dead {
+        let elimination = eliminate(&modules, &roots(mode, &bootstrap), &keep).unwrap_or_else(|errors| panic!("{}", errors.join("\n")));
+        if !elimination.dropped.is_empty() {
+            eprintln!("Dropped {} functions that are never called:", elimination.dropped.len());
+        }
+        for (module, function) in &elimination.dropped {
+            eprintln!("  {function} ({module})");
+        }
+        modules = elimination.modules;
+    }
+
+    let mut code_writer = CodeWriter::with_options(&options);
     code_writer.set_output_filename(&output);
-    code_writer.write_init();
-
-    for filepath in &filenames {
-        let input = fs::read_to_string(filepath).expect("Source file not found.");
-        let mut parser = Parser::new(&input);
-
-        // `filepath` contains a full path. extract the filename only.
-        let filename = Path::new(filepath).file_name().unwrap().to_str().unwrap();
-
-        code_writer.set_module_name(filename);
-        while parser.has_more_commands() {
-            let command_type = parser.command_type();
-            match command_type {
-                CommandType::Arithmetic => {
-                    let command = parser.arg1();
-                    code_writer.write_arithemtic(command);
-                }
-                CommandType::Push | CommandType::Pop => {
-                    let segment = parser.arg1();
-                    let index = parser.arg2();
-                    code_writer.write_pushpop(command_type, segment, index);
-                }
-                CommandType::Label => {
-                    let label = parser.arg1();
-                    code_writer.write_label(label);
-                }
-                CommandType::Goto => {
-                    let label = parser.arg1();
-                    code_writer.write_goto(label);
-                }
-                CommandType::If => {
-                    let label = parser.arg1();
-                    code_writer.write_if(label);
-                }
-                CommandType::Function => {
-                    let function_name = parser.arg1();
-                    let locals_count = parser.arg2();
-                    code_writer.write_function(function_name, locals_count);
-                }
-                CommandType::Call => {
-                    let function_name = parser.arg1();
-                    let arg_count = parser.arg2();
-                    code_writer.write_call(function_name, arg_count);
-                }
-                CommandType::Return => {
-                    code_writer.write_return();
-                }
+    code_writer.set_program(&modules);
+    code_writer.write_init(mode, &bootstrap);
+
+    match cache_dir {
+        Some(cache_dir) => {
+            code_writer.close();
+            write_cached(&cache_dir, &output, &options, &sources, &modules);
+        }
+        None => {
+            for (filename, commands) in &modules {
+                code_writer.write_module(filename, commands);
             }
+            code_writer.close();
+        }
+    }
 
-            parser.advance();
+    if let Some(source_map_file) = source_map_file {
+        let asm = fs::read_to_string(&output).expect("Failed to read the output file.");
+        let entries = source_map(&asm)
+            .iter()
+            .map(|entry| format!("{entry}\n"))
+            .collect::<String>();
+        fs::write(&source_map_file, entries).expect("Failed to write the source map.");
+    }
+}
+
+// appends the translation of each module to `output`, translating only the modules that are
+// not in the cache yet
+fn write_cached(
+    cache_dir: &str,
+    output: &str,
+    options: &Options,
+    sources: &[(String, String)],
+    modules: &[(String, Vec<Line>)],
+) {
+    let cache = ModuleCache::new(Path::new(cache_dir)).expect("Failed to create the cache directory.");
+    let leaves = leaf_functions(modules, options);
+    let mut out_file = OpenOptions::new()
+        .append(true)
+        .open(output)
+        .expect("Failed to reopen the output file.");
+
+    for ((filename, input), (_, commands)) in sources.iter().zip(modules) {
+        // the translation depends on which functions of the module dead code elimination kept
+        let functions = commands
+            .iter()
+            .filter_map(|line| match &line.command {
+                VmCommand::Function(name, _) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        // as does the frame of each call, which is lighter for a leaf function
+        let mut called = commands
+            .iter()
+            .filter_map(|line| match &line.command {
+                VmCommand::Call(name, _) if leaves.contains(name) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        called.sort_unstable();
+        called.dedup();
+        let cached = cache.path(filename, input, &functions, &called, options);
+        if !cached.exists() {
+            // translate into a temporary file first, so an interrupted run leaves no partial entry
+            let partial = cached.with_extension("partial");
+            let mut module_writer = CodeWriter::with_options(options);
+            module_writer.set_output_filename(partial.to_str().unwrap());
+            module_writer.set_program(modules);
+            module_writer.write_module(filename, commands);
+            module_writer.close();
+            fs::rename(&partial, &cached).expect("Failed to store the translated module.");
+            cache.evict(filename, &cached).expect("Failed to evict older translations of the module.");
         }
+
+        let mut module_file = File::open(&cached).expect("Cached module not found.");
+        io::copy(&mut module_file, &mut out_file).expect("Failed to write the output file.");
     }
 }
@@ -0,0 +1,330 @@
+use crate::command::{Segment, VmCommand};
+use crate::parser::{parse, Line};
+
+/// Hand-written Hack assembly for the OS routines that dominate the runtime of Jack programs.
+///
+/// A routine replaces the VM code of the function it is named after, and keeps its calling
+/// convention: it is entered through a regular `call`, finds its arguments at ARG, and leaves
+/// its return value in D at the end of its code, after which the translator returns from the
+/// function. It may use R13 to R15 and variables of its own, named `<function>$<name>`, but
+/// must not change SP, LCL, ARG, THIS or THAT.
+///
+/// A routine may jump to `<function>$STOCK` for cases it does not handle, where the VM code
+/// of the function is translated as usual. The routines work on the statics of the stock OS
+/// in `tools/OS`, so they must be linked with it, which `check` makes sure of.
+pub struct NativeRoutine {
+    pub function: &'static str,
+    pub asm: &'static str,
+    /// The VM code of the class in the stock OS, if the routine works on its statics.
+    pub stock: Option<&'static str>,
+}
+
+pub const ROUTINES: &[NativeRoutine] = &[
+    NativeRoutine {
+        function: "Math.multiply",
+        asm: include_str!("../os/Math.multiply.asm"),
+        stock: None,
+    },
+    NativeRoutine {
+        function: "Output.printChar",
+        asm: include_str!("../os/Output.printChar.asm"),
+        stock: Some(include_str!("../../tools/OS/Output.vm")),
+    },
+    NativeRoutine {
+        function: "Screen.drawLine",
+        asm: include_str!("../os/Screen.drawLine.asm"),
+        stock: Some(include_str!("../../tools/OS/Screen.vm")),
+    },
+];
+
+impl NativeRoutine {
+    pub fn find(function: &str) -> Option<&'static NativeRoutine> {
+        ROUTINES.iter().find(|routine| routine.function == function)
+    }
+
+    /// Whether the routine leaves some cases to the VM code of the function.
+    pub fn falls_back(&self) -> bool {
+        self.asm.contains(&format!("{}$STOCK", self.function))
+    }
+}
+
+// the number of statics of a module, and its functions with their number of locals
+fn layout(commands: &[Line]) -> (u16, Vec<(&str, u16)>) {
+    let mut statics = 0;
+    let mut functions = Vec::new();
+    for line in commands {
+        match &line.command {
+            VmCommand::Push(Segment::Static, index) | VmCommand::Pop(Segment::Static, index) => {
+                statics = statics.max(index + 1);
+            }
+            VmCommand::Function(name, locals) => functions.push((name.as_str(), *locals)),
+            _ => {}
+        }
+    }
+    (statics, functions)
+}
+
+/// Checks that the modules defining a function the routines replace are laid out as the class
+/// of the stock OS: the same number of statics, and the same functions with the same locals.
+/// Returns one message per module that is not.
+pub fn check(modules: &[(String, Vec<Line>)]) -> Vec<String> {
+    let mut errors = Vec::new();
+    for routine in ROUTINES {
+        let Some(stock) = routine.stock else {
+            continue;
+        };
+        let stock = parse(stock).expect("The stock OS parses.");
+        let (stock_statics, stock_functions) = layout(&stock);
+
+        let defining = modules.iter().filter(|(_, commands)| {
+            commands
+                .iter()
+                .any(|line| matches!(&line.command, VmCommand::Function(name, _) if name == routine.function))
+        });
+        for (module, commands) in defining {
+            let (statics, functions) = layout(commands);
+            if statics != stock_statics {
+                errors.push(format!(
+                    "{module}: the native {} works on the {stock_statics} statics of the stock OS, but this module has {statics}.",
+                    routine.function
+                ));
+            } else if functions != stock_functions {
+                errors.push(format!(
+                    "{module}: the native {} needs the stock OS, but the functions of this module differ from it.",
+                    routine.function
+                ));
+            }
+        }
+    }
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use emulator::cpu::{Cpu, SCREEN};
+
+    use super::check;
+    use crate::code_writer::tests::run;
+    use crate::options::Options;
+    use crate::parser::parse;
+
+    const NATIVE: Options = Options {
+        optimize: false,
+        native_os: true,
+        verbose: false,
+        compact: false,
+    };
+
+    // Sys.error records the error code in Sys.0 and halts
+    const SYS_ERROR: &str = "
+        function Sys.error 0
+        push argument 0
+        pop static 0
+        label HALT
+        goto HALT
+    ";
+
+    fn os(module: &str) -> String {
+        let path = format!("{}/../tools/OS/{module}.vm", env!("CARGO_MANIFEST_DIR"));
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    // runs Sys.init, followed by Sys.error, with the given OS modules, on the stock and on the
+    // native OS
+    fn run_both(name: &str, init: &str, modules: &[&str]) -> (Cpu, Cpu) {
+        let sys = format!("{init}{SYS_ERROR}");
+        let sources = modules
+            .iter()
+            .map(|module| os(module))
+            .collect::<Vec<String>>();
+        let mut program = vec![("Sys.vm", sys.as_str())];
+        let names = modules
+            .iter()
+            .map(|module| format!("{module}.vm"))
+            .collect::<Vec<String>>();
+        program.extend(
+            names
+                .iter()
+                .map(String::as_str)
+                .zip(sources.iter().map(String::as_str)),
+        );
+
+        let stock = run(name, &program, &Options::default());
+        let native = run(name, &program, &NATIVE);
+        (stock, native)
+    }
+
+    fn screen(cpu: &Cpu) -> &[u16] {
+        &cpu.ram[SCREEN as usize..SCREEN as usize + 8192]
+    }
+
+    #[test]
+    fn test_multiply() {
+        let pairs: [(i16, i16); 9] = [
+            (6, 7),
+            (0, 5),
+            (1, -1),
+            (-180, 100),
+            (123, -45),
+            (-181, -181),
+            (255, 128),
+            (-3000, 10),
+            (32767, 1),
+        ];
+        let mut init =
+            "function Sys.init 0\ncall Memory.init 0\npop temp 0\ncall Math.init 0\npop temp 0\n"
+                .to_string();
+        for (i, (x, y)) in pairs.iter().enumerate() {
+            for n in [x, y] {
+                init += &format!("push constant {}\n", n.unsigned_abs());
+                if *n < 0 {
+                    init += "neg\n";
+                }
+            }
+            init += &format!("call Math.multiply 2\npop static {}\n", i + 1);
+        }
+        init += "label END\ngoto END\n";
+
+        let (stock, native) = run_both("multiply", &init, &["Math", "Memory", "Array"]);
+        let expected = pairs.map(|(x, y)| x.wrapping_mul(y) as u16);
+        assert_eq!(&stock.ram[16..25], &expected);
+        assert_eq!(&native.ram[16..25], &expected);
+        assert!(native.cycles < stock.cycles);
+    }
+
+    #[test]
+    fn test_draw_line() {
+        // every octant, horizontal and vertical lines, single points, and white lines on top
+        let lines = [
+            (256, 128, 511, 140),
+            (256, 128, 500, 255),
+            (256, 128, 260, 255),
+            (256, 128, 0, 250),
+            (256, 128, 0, 120),
+            (256, 128, 10, 0),
+            (256, 128, 250, 0),
+            (256, 128, 511, 3),
+            (0, 0, 511, 0),
+            (17, 5, 17, 200),
+            (100, 100, 100, 100),
+            (31, 64, 32, 65),
+            (15, 70, 16, 250),
+            (400, 250, 401, 1),
+        ];
+        let mut init = "function Sys.init 0\ncall Memory.init 0\npop temp 0\ncall Math.init 0\npop temp 0\ncall Screen.init 0\npop temp 0\n".to_string();
+        for (i, (x1, y1, x2, y2)) in lines.iter().enumerate() {
+            if i == 9 {
+                init += "push constant 0\ncall Screen.setColor 1\npop temp 0\n";
+            }
+            init += &format!("push constant {x1}\npush constant {y1}\npush constant {x2}\npush constant {y2}\ncall Screen.drawLine 4\npop temp 0\n");
+        }
+        // a line off the screen is reported by the stock routine
+        init += "push constant 0\npush constant 0\npush constant 512\npush constant 0\ncall Screen.drawLine 4\npop temp 0\nlabel END\ngoto END\n";
+
+        let (stock, native) = run_both("draw-line", &init, &["Screen", "Math", "Memory", "Array"]);
+        assert!(screen(&stock).iter().any(|&word| word != 0));
+        assert!(screen(&native) == screen(&stock));
+        assert_eq!(stock.ram[16], 8);
+        assert_eq!(native.ram[16], 8);
+        assert!(native.cycles < stock.cycles);
+    }
+
+    #[test]
+    fn test_print_char() {
+        // prints 1600 characters, cycling through 31..126, with a new line at every 64th and a
+        // backspace at every 64th + 17th character. the text wraps around the bottom of the
+        // screen.
+        let init = "
+            function Sys.init 2
+            call Memory.init 0
+            pop temp 0
+            call Math.init 0
+            pop temp 0
+            call Output.init 0
+            pop temp 0
+            push constant 31
+            pop local 1
+            label LOOP
+            push local 0
+            push constant 1600
+            lt
+            not
+            if-goto END
+            push local 0
+            push constant 63
+            and
+            push constant 0
+            eq
+            if-goto NEWLINE
+            push local 0
+            push constant 63
+            and
+            push constant 17
+            eq
+            if-goto BACKSPACE
+            push local 1
+            call Output.printChar 1
+            pop temp 0
+            push local 1
+            push constant 1
+            add
+            pop local 1
+            push local 1
+            push constant 127
+            eq
+            not
+            if-goto NEXT
+            push constant 31
+            pop local 1
+            goto NEXT
+            label NEWLINE
+            push constant 128
+            call Output.printChar 1
+            pop temp 0
+            goto NEXT
+            label BACKSPACE
+            push constant 129
+            call Output.printChar 1
+            pop temp 0
+            label NEXT
+            push local 0
+            push constant 1
+            add
+            pop local 0
+            goto LOOP
+            label END
+            goto END
+        ";
+
+        let (stock, native) = run_both(
+            "print-char",
+            init,
+            &["Output", "String", "Math", "Memory", "Array"],
+        );
+        assert!(screen(&stock).iter().any(|&word| word != 0));
+        assert!(screen(&native) == screen(&stock));
+        assert!(native.cycles < stock.cycles);
+    }
+
+    #[test]
+    fn test_check() {
+        let stock = ["Output", "Screen", "Math"]
+            .map(|module| (format!("{module}.vm"), parse(&os(module)).unwrap()));
+        assert!(check(&stock).is_empty());
+
+        // the Output of project 12 keeps its cursor in statics of its own
+        let path = format!("{}/../projects/12/Output.jack", env!("CARGO_MANIFEST_DIR"));
+        let jack = std::fs::read_to_string(path).unwrap();
+        let vm = String::from_utf8(compiler::batch::compile_class(&jack, &Default::default()).vm).unwrap();
+        assert_eq!(
+            check(&[("Output.vm".to_string(), parse(&vm).unwrap())]),
+            ["Output.vm: the native Output.printChar works on the 7 statics of the stock OS, but this module has 3."]
+        );
+
+        let screen = format!("{}function Screen.extra 0\npush constant 0\nreturn\n", os("Screen"));
+        assert_eq!(
+            check(&[("Screen.vm".to_string(), parse(&screen).unwrap())]),
+            ["Screen.vm: the native Screen.drawLine needs the stock OS, but the functions of this module differ from it."]
+        );
+    }
+}
@@ -0,0 +1,65 @@
+use std::fmt;
+
+/// Switches that change the generated assembly. The defaults follow the book.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Options {
+    /// Reuse the current frame for a `call` that is immediately followed by `return`, and give
+    /// functions without calls and locals a shorter epilogue.
+    pub optimize: bool,
+    /// Replace the hot routines of the stock OS with the hand-written assembly of `native_os`.
+    pub native_os: bool,
+    /// Precede the translation of every VM command with a comment naming the command and where
+    /// it comes from, which `source_map` reads back.
+    pub verbose: bool,
+    /// Translate `call` and `return` into jumps to a single copy of their code, which
+    /// `write_init` writes before the modules. Programs get much smaller for a few more cycles
+    /// per call.
+    pub compact: bool,
+}
+
+impl Options {
+    /// Removes the flags this struct knows about from `args`, setting the matching options.
+    pub fn from_args(args: &mut Vec<String>) -> Self {
+        let mut options = Options::default();
+        args.retain(|arg| match arg.as_str() {
+            "--optimize" => {
+                options.optimize = true;
+                false
+            }
+            "--native-os" => {
+                options.native_os = true;
+                false
+            }
+            "--verbose" => {
+                options.verbose = true;
+                false
+            }
+            "--compact" => {
+                options.compact = true;
+                false
+            }
+            _ => true,
+        });
+        options
+    }
+}
+
+// the command line flags that select these options
+impl fmt::Display for Options {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut flags = Vec::new();
+        if self.optimize {
+            flags.push("--optimize");
+        }
+        if self.native_os {
+            flags.push("--native-os");
+        }
+        if self.verbose {
+            flags.push("--verbose");
+        }
+        if self.compact {
+            flags.push("--compact");
+        }
+        write!(f, "{}", flags.join(" "))
+    }
+}
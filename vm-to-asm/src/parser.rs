@@ -1,78 +1,221 @@
-use crate::command::CommandType;
+use std::fmt;
 
-pub struct Parser {
-    source: Vec<Vec<String>>,
-    line: usize,
+use crate::command::{ArithmeticOp, Segment, VmCommand};
+
+/// A command together with the (1-based) source line it was read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub line: usize,
+    pub command: VmCommand,
 }
 
-impl Parser {
-    pub fn new(input: &str) -> Self {
-        let source: Vec<Vec<String>> = input
-            .lines()
-            .map(|s| {
-                s.split_ascii_whitespace()
-                    .map(str::to_owned)
-                    .collect::<Vec<String>>()
-            })
-            .filter(Self::should_include_line)
-            .collect();
-        let line = 0;
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Parses the commands of a .vm file. Comments start with `//` and may follow a command.
+/// Every malformed line is reported, not only the first one.
+pub fn parse(input: &str) -> Result<Vec<Line>, Vec<ParseError>> {
+    let mut commands = Vec::new();
+    let mut errors = Vec::new();
+
+    for (i, text) in input.lines().enumerate() {
+        let text = text.split_once("//").map_or(text, |(code, _)| code);
+        let words = text.split_ascii_whitespace().collect::<Vec<&str>>();
+        if words.is_empty() {
+            continue;
+        }
 
-        Parser { source, line }
+        match parse_command(&words) {
+            Ok(command) => commands.push(Line {
+                line: i + 1,
+                command,
+            }),
+            Err(message) => errors.push(ParseError {
+                line: i + 1,
+                message,
+            }),
+        }
     }
 
-    pub fn advance(&mut self) {
-        self.line += 1;
+    if errors.is_empty() {
+        Ok(commands)
+    } else {
+        Err(errors)
     }
+}
+
+fn parse_command(words: &[&str]) -> Result<VmCommand, String> {
+    let command = words[0];
+    let operands = &words[1..];
 
-    pub fn has_more_commands(&self) -> bool {
-        self.line < self.source.len()
+    if let Some(op) = ArithmeticOp::from_name(command) {
+        expect_operands(command, operands, 0)?;
+        return Ok(VmCommand::Arithmetic(op));
     }
 
-    pub fn command_type(&self) -> CommandType {
-        let line = &self.source[self.line];
+    match command {
+        "push" | "pop" => {
+            expect_operands(command, operands, 2)?;
+            let segment = Segment::from_name(operands[0])
+                .ok_or_else(|| format!("Unknown segment '{}'.", operands[0]))?;
+            let index = parse_number(operands[1])?;
+            if index > segment.max_index() {
+                return Err(format!(
+                    "Index {index} is out of range for segment '{}', which allows 0 to {}.",
+                    segment.name(),
+                    segment.max_index()
+                ));
+            }
 
-        match line[0].as_str() {
-            "add" | "sub" | "neg" | "eq" | "gt" | "lt" | "and" | "or" | "not" => {
-                CommandType::Arithmetic
+            if command == "push" {
+                Ok(VmCommand::Push(segment, index))
+            } else if segment == Segment::Constant {
+                Err("The 'constant' segment is virtual. It cannot be popped to.".to_string())
+            } else {
+                Ok(VmCommand::Pop(segment, index))
             }
-            "push" => CommandType::Push,
-            "pop" => CommandType::Pop,
-            "label" => CommandType::Label,
-            "goto" => CommandType::Goto,
-            "if-goto" => CommandType::If,
-            "function" => CommandType::Function,
-            "call" => CommandType::Call,
-            "return" => CommandType::Return,
-            c => panic!("Invalid command {} at line {}", c, self.line + 1),
         }
+        "label" | "goto" | "if-goto" => {
+            expect_operands(command, operands, 1)?;
+            let label = parse_symbol(operands[0])?;
+            Ok(match command {
+                "label" => VmCommand::Label(label),
+                "goto" => VmCommand::Goto(label),
+                _ => VmCommand::If(label),
+            })
+        }
+        "function" | "call" => {
+            expect_operands(command, operands, 2)?;
+            let name = parse_symbol(operands[0])?;
+            let count = parse_number(operands[1])?;
+            Ok(if command == "function" {
+                VmCommand::Function(name, count)
+            } else {
+                VmCommand::Call(name, count)
+            })
+        }
+        "return" => {
+            expect_operands(command, operands, 0)?;
+            Ok(VmCommand::Return)
+        }
+        c => Err(format!("Invalid command '{c}'.")),
     }
+}
 
-    pub fn arg1(&self) -> &str {
-        let line = &self.source[self.line];
-        match line[0].as_str() {
-            "add" | "sub" | "neg" | "eq" | "gt" | "lt" | "and" | "or" | "not" => line[0].as_str(),
-            "pop" | "push" | "label" | "goto" | "if-goto" | "function" | "call" => line[1].as_str(),
-            "return" => panic!("Invalid call to arg1 for return operation"),
-            c => panic!("Invalid command {} at line {}", c, self.line + 1),
-        }
+fn expect_operands(command: &str, operands: &[&str], count: usize) -> Result<(), String> {
+    if operands.len() == count {
+        Ok(())
+    } else {
+        Err(format!(
+            "'{command}' expects {count} operand(s), found {}.",
+            operands.len()
+        ))
     }
+}
 
-    pub fn arg2(&self) -> u16 {
-        let line = &self.source[self.line];
-        match line[0].as_str() {
-            "add" | "sub" | "neg" | "eq" | "gt" | "lt" | "and" | "or" | "not" | "label"
-            | "goto" | "if-goto" => {
-                panic!("Invalid call to arg2 for operation {}", line[0])
-            }
-            "pop" | "push" | "function" | "call" => line[2]
-                .parse()
-                .expect("Expected integer for second argument."),
-            s => panic!("Invalid instruction {s}"),
-        }
+fn parse_number(word: &str) -> Result<u16, String> {
+    word.parse()
+        .map_err(|_| format!("Expected a non-negative integer, found '{word}'."))
+}
+
+// a symbol is a sequence of letters, digits, '_', '.' and ':' that does not begin with a digit
+fn parse_symbol(word: &str) -> Result<String, String> {
+    let mut chars = word.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| !c.is_ascii_digit() && is_symbol_char(c))
+        && chars.all(is_symbol_char);
+
+    if valid {
+        Ok(word.to_string())
+    } else {
+        Err(format!("'{word}' is not a valid symbol."))
+    }
+}
+
+fn is_symbol_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '.' || c == ':' || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, Line};
+    use crate::command::{ArithmeticOp, Segment, VmCommand};
+
+    // the error messages of `source`, with their line numbers
+    fn errors(source: &str) -> Vec<String> {
+        parse(source)
+            .unwrap_err()
+            .iter()
+            .map(|error| error.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_comments() {
+        let source = "push constant 7 // seven\n// a whole line\n\n  add// no space\nlabel END//";
+        assert_eq!(
+            parse(source).unwrap(),
+            [
+                Line { line: 1, command: VmCommand::Push(Segment::Constant, 7) },
+                Line { line: 4, command: VmCommand::Arithmetic(ArithmeticOp::Add) },
+                Line { line: 5, command: VmCommand::Label("END".to_string()) },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_index_limits() {
+        let source = "push temp 7\npop pointer 1\npush static 239\npush local 32767";
+        assert_eq!(parse(source).unwrap().len(), 4);
+        assert_eq!(
+            errors("push temp 8\npop pointer 2\npush static 240\npush constant 32768"),
+            [
+                "line 1: Index 8 is out of range for segment 'temp', which allows 0 to 7.",
+                "line 2: Index 2 is out of range for segment 'pointer', which allows 0 to 1.",
+                "line 3: Index 240 is out of range for segment 'static', which allows 0 to 239.",
+                "line 4: Index 32768 is out of range for segment 'constant', which allows 0 to 32767.",
+            ]
+        );
     }
 
-    pub fn should_include_line(line: &Vec<String>) -> bool {
-        !(line.is_empty() || line[0].starts_with("//"))
+    #[test]
+    fn test_all_errors() {
+        // every malformed line is reported, not only the first one
+        let source = "\
+            function Main.main 0\n\
+            pop constant 1\n\
+            push local\n\
+            add 1\n\
+            return 0\n\
+            call Main.main\n\
+            push local 0\n\
+            push heap 0\n\
+            jump END\n\
+            goto 1END\n\
+            push local -1";
+        assert_eq!(
+            errors(source),
+            [
+                "line 2: The 'constant' segment is virtual. It cannot be popped to.",
+                "line 3: 'push' expects 2 operand(s), found 1.",
+                "line 4: 'add' expects 0 operand(s), found 1.",
+                "line 5: 'return' expects 0 operand(s), found 1.",
+                "line 6: 'call' expects 2 operand(s), found 1.",
+                "line 8: Unknown segment 'heap'.",
+                "line 9: Invalid command 'jump'.",
+                "line 10: '1END' is not a valid symbol.",
+                "line 11: Expected a non-negative integer, found '-1'.",
+            ]
+        );
     }
 }
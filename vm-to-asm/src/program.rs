@@ -0,0 +1,144 @@
+use crate::bootstrap::{Bootstrap, BootstrapMode};
+use crate::code_writer::CodeWriter;
+use crate::dead_code::eliminate;
+use crate::native_os;
+use crate::options::Options;
+use crate::parser::{parse, Line};
+use crate::stack_analysis::analyze_stack;
+use crate::validation::validate;
+
+/// A parsed program that passed the checks of `check`.
+pub struct Program {
+    pub mode: BootstrapMode,
+    pub modules: Vec<(String, Vec<Line>)>,
+}
+
+/// Parses the (module, source) pairs and checks the modules together, since calls may cross
+/// module boundaries. With `native_os`, the classes the routines work on must be those of the
+/// stock OS. Returns every error found, each parse error prefixed with its module.
+pub fn check(
+    sources: &[(String, String)],
+    options: &Options,
+    bootstrap: &Bootstrap,
+) -> Result<Program, Vec<String>> {
+    let mut errors = Vec::new();
+    let mut modules = Vec::new();
+    for (filename, input) in sources {
+        match parse(input) {
+            Ok(commands) => modules.push((filename.clone(), commands)),
+            Err(parse_errors) => errors.extend(parse_errors.iter().map(|e| format!("{filename}, {e}"))),
+        }
+    }
+
+    let mode = bootstrap.mode(&modules);
+    if errors.is_empty() {
+        errors = bootstrap.check(&modules);
+        errors.extend(validate(&modules, mode.runs_top_level()));
+        if options.native_os {
+            errors.extend(native_os::check(&modules));
+        }
+    }
+    // the stack analysis relies on every jump and call having a target
+    if errors.is_empty() {
+        errors = analyze_stack(&modules);
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(Program { mode, modules })
+}
+
+/// The functions `--eliminate-dead` starts from: the entry function of the full bootstrap.
+pub fn roots(mode: BootstrapMode, bootstrap: &Bootstrap) -> Vec<&str> {
+    if mode == BootstrapMode::Full {
+        vec![bootstrap.entry.as_str()]
+    } else {
+        Vec::new()
+    }
+}
+
+/// Translates a program held in memory into assembly, as the command line does for files.
+/// With `eliminate_dead`, only the functions the program calls are translated.
+pub fn translate(
+    sources: &[(String, String)],
+    options: &Options,
+    bootstrap: &Bootstrap,
+    eliminate_dead: bool,
+) -> Result<String, Vec<String>> {
+    let Program { mode, mut modules } = check(sources, options, bootstrap)?;
+    if eliminate_dead {
+        modules = eliminate(&modules, &roots(mode, bootstrap), &[])?.modules;
+    }
+
+    let mut code_writer = CodeWriter::with_output(Vec::new(), options);
+    code_writer.set_program(&modules);
+    code_writer.write_init(mode, bootstrap);
+    for (filename, commands) in &modules {
+        code_writer.write_module(filename, commands);
+    }
+    let asm = code_writer.into_output().unwrap_or_default();
+    Ok(String::from_utf8(asm).expect("The translation is ASCII."))
+}
+
+#[cfg(test)]
+mod tests {
+    use emulator::cpu::Cpu;
+
+    use super::translate;
+    use crate::bootstrap::Bootstrap;
+    use crate::code_writer::tests::assemble;
+    use crate::options::Options;
+
+    #[test]
+    fn test_translate() {
+        let sys = "
+            function Sys.init 0
+            push constant 6
+            push constant 7
+            call Main.multiply 2
+            pop static 0
+            label END
+            goto END
+        ";
+        let main = "
+            function Main.multiply 0
+            push constant 0
+            pop temp 0
+            label LOOP
+            push argument 1
+            push constant 0
+            eq
+            if-goto END
+            push temp 0
+            push argument 0
+            add
+            pop temp 0
+            push argument 1
+            push constant 1
+            sub
+            pop argument 1
+            goto LOOP
+            label END
+            push temp 0
+            return
+            function Main.unused 0
+            push constant 0
+            return
+        ";
+        let sources = [("Main.vm", main), ("Sys.vm", sys)]
+            .map(|(module, source)| (module.to_string(), source.to_string()));
+
+        let asm = translate(&sources, &Options::default(), &Bootstrap::default(), true).unwrap();
+        assert!(!asm.contains("(Main.unused)"));
+        let mut cpu = Cpu::new();
+        cpu.load(&assemble(&asm));
+        assert!(cpu.run_until_idle(100_000));
+        assert_eq!(cpu.ram[16], 42);
+
+        let sources = [("Sys.vm".to_string(), "function Sys.init 0\npop constant 1".to_string())];
+        assert_eq!(
+            translate(&sources, &Options::default(), &Bootstrap::default(), false).err().unwrap(),
+            ["Sys.vm, line 2: The 'constant' segment is virtual. It cannot be popped to."]
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use std::fmt;
+use std::ops::Range;
+
+/// The instructions a VM command was translated to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceMapEntry {
+    /// The ROM addresses of the instructions, as the assembler assigns them.
+    pub rom: Range<usize>,
+    pub module: String,
+    pub line: usize,
+    /// The function the command belongs to, or `None` for the top level of a program without
+    /// bootstrap.
+    pub function: Option<String>,
+}
+
+// `256..263 Main.vm:3 Main.main`
+impl fmt::Display for SourceMapEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}..{} {}:{}",
+            self.rom.start, self.rom.end, self.module, self.line
+        )?;
+        if let Some(function) = &self.function {
+            write!(f, " {function}")?;
+        }
+        Ok(())
+    }
+}
+
+// the module, line and command of a `// Main.vm:12: push local 0` comment
+fn source_comment(line: &str) -> Option<(&str, usize, &str)> {
+    let rest = line.trim().strip_prefix("// ")?;
+    let (module, rest) = rest.split_once(':')?;
+    let (line, command) = rest.split_once(": ")?;
+    if !module.ends_with(".vm") {
+        return None;
+    }
+    Some((module, line.parse().ok()?, command))
+}
+
+/// Maps the ROM addresses of assembly written with `verbose` back to the VM commands, in the
+/// order of the addresses. Addresses are counted as the assembler assigns them: every line
+/// with an instruction takes one, labels and comments none. Commands that take no instructions,
+/// such as labels, and the bootstrap code are left out.
+pub fn source_map(asm: &str) -> Vec<SourceMapEntry> {
+    let mut entries: Vec<SourceMapEntry> = Vec::new();
+    let mut function: Option<String> = None;
+    let mut address = 0;
+
+    for line in asm.lines() {
+        if let Some((module, line, command)) = source_comment(line) {
+            if entries.last().is_some_and(|entry| entry.module != module) {
+                function = None;
+            }
+            if let Some(name) = command.strip_prefix("function ") {
+                function = name.split_whitespace().next().map(str::to_string);
+            }
+            if entries.last().is_some_and(|entry| entry.rom.is_empty()) {
+                entries.pop();
+            }
+            entries.push(SourceMapEntry {
+                rom: address..address,
+                module: module.to_string(),
+                line,
+                function: function.clone(),
+            });
+            continue;
+        }
+
+        let code = line.split("//").next().unwrap().trim();
+        if code.is_empty() || code.starts_with('(') {
+            continue;
+        }
+        address += 1;
+        if let Some(entry) = entries.last_mut() {
+            entry.rom.end = address;
+        }
+    }
+
+    if entries.last().is_some_and(|entry| entry.rom.is_empty()) {
+        entries.pop();
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{source_map, SourceMapEntry};
+    use crate::bootstrap::{Bootstrap, BootstrapMode};
+    use crate::code_writer::tests::{assemble, write_asm};
+    use crate::options::Options;
+
+    const VERBOSE: Options = Options {
+        optimize: false,
+        native_os: false,
+        verbose: true,
+        compact: false,
+    };
+
+    const MAIN: &str = "
+        function Main.main 1
+        push constant 7
+        label LOOP
+        call Main.double 1
+        goto LOOP
+        function Main.double 0
+        push argument 0
+        push argument 0
+        add
+        return
+    ";
+
+    const SYS: &str = "
+        function Sys.init 0
+        call Main.main 0
+        return
+    ";
+
+    fn entry(rom: std::ops::Range<usize>, module: &str, line: usize, function: &str) -> SourceMapEntry {
+        SourceMapEntry {
+            rom,
+            module: module.to_string(),
+            line,
+            function: Some(function.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_verbose() {
+        let modules = [("Main.vm", MAIN), ("Sys.vm", SYS)];
+        let bootstrap = Bootstrap::default();
+        let verbose = write_asm("verbose", &modules, &VERBOSE, BootstrapMode::Full, &bootstrap);
+        assert!(verbose.contains("// Main.vm:3: push constant 7\n"));
+        assert!(verbose.contains("// Sys.vm:3: call Main.main 0\n"));
+
+        // the comments do not change the program
+        let plain = write_asm("verbose", &modules, &Options::default(), BootstrapMode::Full, &bootstrap);
+        assert_eq!(assemble(&verbose), assemble(&plain));
+    }
+
+    #[test]
+    fn test_source_map() {
+        let modules = [("Main.vm", MAIN), ("Sys.vm", SYS)];
+        let asm = write_asm("source-map", &modules, &VERBOSE, BootstrapMode::Full, &Bootstrap::default());
+        let program = assemble(&asm);
+        let map = source_map(&asm);
+
+        // the label of Main.main takes no instructions
+        let lines = map.iter().map(|entry| (entry.module.as_str(), entry.line)).collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                ("Main.vm", 2),
+                ("Main.vm", 3),
+                ("Main.vm", 5),
+                ("Main.vm", 6),
+                ("Main.vm", 8),
+                ("Main.vm", 9),
+                ("Main.vm", 10),
+                ("Main.vm", 11),
+                ("Sys.vm", 3),
+                ("Sys.vm", 4),
+            ]
+        );
+        assert_eq!(map[4].function.as_deref(), Some("Main.double"));
+        assert_eq!(map[8].function.as_deref(), Some("Sys.init"));
+
+        // the entries follow each other up to the end of the program, behind the bootstrap
+        for pair in map.windows(2) {
+            assert_eq!(pair[0].rom.end, pair[1].rom.start);
+        }
+        assert!(map[0].rom.start > 0);
+        assert_eq!(map.last().unwrap().rom.end, program.len());
+
+        // push constant 7
+        assert_eq!(map[1], entry(map[1].rom.start..map[1].rom.start + 7, "Main.vm", 3, "Main.main"));
+        assert_eq!(map[1].to_string(), format!("{}..{} Main.vm:3 Main.main", map[1].rom.start, map[1].rom.end));
+    }
+
+    #[test]
+    fn test_tail_call() {
+        // the `return` of Sys.init takes the jump into Main.main
+        let optimize = Options { optimize: true, ..VERBOSE };
+        let asm = write_asm("source-map-tail-call", &[("Sys.vm", SYS)], &optimize, BootstrapMode::None, &Bootstrap::default());
+        assert!(asm.contains("// Sys.vm:4: return\n"));
+        let map = source_map(&asm);
+        let lines = map.iter().map(|entry| entry.line).collect::<Vec<_>>();
+        assert_eq!(lines, [3, 4]);
+        assert_eq!(map[0].rom.end, map[1].rom.start);
+        assert_eq!(map[1].rom.len(), 8);
+    }
+
+    #[test]
+    fn test_top_level() {
+        let asm = write_asm(
+            "source-map-top-level",
+            &[("SimpleAdd.vm", "push constant 7\npush constant 8\nadd\n")],
+            &VERBOSE,
+            BootstrapMode::None,
+            &Bootstrap::default(),
+        );
+        let map = source_map(&asm);
+        assert_eq!(map.len(), 3);
+        assert_eq!(map[0].rom, 0..7);
+        assert_eq!(map[0].function, None);
+        assert_eq!(map[2].to_string(), "14..19 SimpleAdd.vm:3");
+    }
+}
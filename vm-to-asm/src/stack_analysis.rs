@@ -0,0 +1,264 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::command::{ArithmeticOp, Segment, VmCommand};
+use crate::parser::Line;
+
+/// Checks the stack discipline of a program, given as (module name, commands) pairs that passed
+/// validation. Returns one message per error.
+///
+/// The depth of a function's working stack is followed along every control-flow path, starting
+/// at 0 after the function's locals. Paths that reach a command with different depths, commands
+/// that take more values than the stack holds, and `return`s that do not leave exactly the
+/// return value are reported. A `call` must pass at least as many arguments as the callee uses.
+pub fn analyze_stack(modules: &[(String, Vec<Line>)]) -> Vec<String> {
+    let mut errors = Vec::new();
+    let mut arguments_used = HashMap::new();
+    let mut calls = Vec::new();
+
+    for (module, commands) in modules {
+        let starts = commands
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| matches!(line.command, VmCommand::Function(..)))
+            .map(|(i, _)| i)
+            .chain([commands.len()])
+            .collect::<Vec<usize>>();
+
+        // the commands before the first function, which validation only allows in programs
+        // without bootstrap, start on an empty stack as well
+        check_depths(&mut errors, module, &commands[..starts[0]]);
+
+        for bounds in starts.windows(2) {
+            let VmCommand::Function(name, _) = &commands[bounds[0]].command else {
+                unreachable!("Functions start with a function command.");
+            };
+            let body = &commands[bounds[0] + 1..bounds[1]];
+
+            check_depths(&mut errors, module, body);
+
+            let mut used = 0;
+            for Line { line, command } in body {
+                match command {
+                    VmCommand::Push(Segment::Argument, index)
+                    | VmCommand::Pop(Segment::Argument, index) => {
+                        used = used.max(index + 1);
+                    }
+                    VmCommand::Call(callee, args) => calls.push((module, *line, callee, *args)),
+                    _ => {}
+                }
+            }
+            arguments_used.insert(name.as_str(), used);
+        }
+    }
+
+    for (module, line, callee, args) in calls {
+        let used = arguments_used.get(callee.as_str()).copied().unwrap_or(0);
+        if args < used {
+            errors.push(format!(
+                "{module}, line {line}: 'call {callee} {args}' passes {args} argument(s), but {callee} uses argument {}.",
+                used - 1
+            ));
+        }
+    }
+
+    errors
+}
+
+fn check_depths(errors: &mut Vec<String>, module: &str, body: &[Line]) {
+    // (line, message), sorted before they are reported
+    let mut found = Vec::new();
+    let labels = body
+        .iter()
+        .enumerate()
+        .filter_map(|(i, line)| match &line.command {
+            VmCommand::Label(label) => Some((label.as_str(), i)),
+            _ => None,
+        })
+        .collect::<HashMap<&str, usize>>();
+
+    // depth of the stack before each command, for the commands reached so far
+    let mut depths: Vec<Option<usize>> = vec![None; body.len()];
+    let mut reported = HashSet::new();
+    let mut pending = Vec::new();
+    if !body.is_empty() {
+        depths[0] = Some(0);
+        pending.push(0);
+    }
+
+    while let Some(i) = pending.pop() {
+        let Line { line, command } = &body[i];
+        let depth = depths[i].expect("Pending commands have a depth.");
+
+        let (popped, pushed) = stack_effect(command);
+        if depth < popped {
+            if depth == 0 {
+                found.push((*line, format!("'{command}' on an empty stack.")));
+            } else {
+                found.push((
+                    *line,
+                    format!("'{command}' takes {popped} values, but the stack holds {depth}."),
+                ));
+            }
+            // the depth after an underflow is meaningless, so the path is not followed further
+            continue;
+        }
+        let depth = depth - popped + pushed;
+
+        let successors = match command {
+            VmCommand::Return => {
+                if depth != 0 {
+                    found.push((
+                        *line,
+                        format!(
+                            "'return' with {} values on the stack, expected 1.",
+                            depth + 1
+                        ),
+                    ));
+                }
+                vec![]
+            }
+            VmCommand::Goto(label) => vec![labels[label.as_str()]],
+            VmCommand::If(label) => vec![i + 1, labels[label.as_str()]],
+            _ => vec![i + 1],
+        };
+
+        for next in successors.into_iter().filter(|&next| next < body.len()) {
+            match depths[next] {
+                None => {
+                    depths[next] = Some(depth);
+                    pending.push(next);
+                }
+                Some(known) if known != depth && reported.insert(next) => {
+                    found.push((
+                        body[next].line,
+                        format!(
+                            "paths reach '{}' with stack depths {known} and {depth}.",
+                            body[next].command
+                        ),
+                    ));
+                }
+                Some(_) => {}
+            }
+        }
+    }
+
+    found.sort();
+    errors.extend(
+        found
+            .into_iter()
+            .map(|(line, message)| format!("{module}, line {line}: {message}")),
+    );
+}
+
+// (values popped, values pushed). `return` pops the return value.
+fn stack_effect(command: &VmCommand) -> (usize, usize) {
+    match command {
+        VmCommand::Arithmetic(ArithmeticOp::Neg | ArithmeticOp::Not) => (1, 1),
+        VmCommand::Arithmetic(_) => (2, 1),
+        VmCommand::Push(..) => (0, 1),
+        VmCommand::Pop(..) | VmCommand::If(_) | VmCommand::Return => (1, 0),
+        VmCommand::Call(_, args) => (*args as usize, 1),
+        VmCommand::Label(_) | VmCommand::Goto(_) | VmCommand::Function(..) => (0, 0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::analyze_stack;
+    use crate::parser::parse;
+
+    fn check(sources: &[(&str, &str)]) -> Vec<String> {
+        let modules = sources
+            .iter()
+            .map(|(module, source)| (module.to_string(), parse(source).unwrap()))
+            .collect::<Vec<_>>();
+        analyze_stack(&modules)
+    }
+
+    #[test]
+    fn test_join_depths() {
+        let main = "function Main.f 0
+            push argument 0
+            if-goto ELSE
+            push constant 1
+            push constant 2
+            goto END
+            label ELSE
+            push constant 3
+            label END
+            return";
+        assert_eq!(
+            check(&[("Main.vm", main)]),
+            ["Main.vm, line 9: paths reach 'label END' with stack depths 1 and 2."]
+        );
+        // a loop must come back to its label with the depth it entered with
+        let main = "function Main.g 0
+            push constant 0
+            label LOOP
+            push constant 1
+            goto LOOP";
+        assert_eq!(
+            check(&[("Main.vm", main)]),
+            ["Main.vm, line 3: paths reach 'label LOOP' with stack depths 1 and 2."]
+        );
+    }
+
+    #[test]
+    fn test_underflow() {
+        let main = "function Main.f 0
+            pop local 0
+            push constant 0
+            return
+            function Main.g 0
+            push constant 1
+            add
+            return";
+        assert_eq!(
+            check(&[("Main.vm", main)]),
+            [
+                "Main.vm, line 2: 'pop local 0' on an empty stack.",
+                "Main.vm, line 7: 'add' takes 2 values, but the stack holds 1.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_return_depth() {
+        let main = "function Main.f 0
+            push constant 1
+            push constant 2
+            return
+            function Main.g 0
+            return";
+        assert_eq!(
+            check(&[("Main.vm", main)]),
+            [
+                "Main.vm, line 4: 'return' with 2 values on the stack, expected 1.",
+                "Main.vm, line 6: 'return' on an empty stack.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_call_arguments() {
+        let main = "function Main.main 0
+            push constant 1
+            push constant 2
+            call Math.max 2
+            push constant 1
+            call Math.max 1
+            add
+            return";
+        let math = "function Math.max 0
+            push argument 0
+            push argument 1
+            gt
+            pop temp 0
+            push argument 1
+            return";
+        assert_eq!(
+            check(&[("Main.vm", main), ("Math.vm", math)]),
+            ["Main.vm, line 6: 'call Math.max 1' passes 1 argument(s), but Math.max uses argument 1."]
+        );
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::command::VmCommand;
+use crate::parser::Line;
+
+/// Checks the modules of a program, given as (module name, commands) pairs, before they are
+/// translated. Returns one message per error.
+///
+/// Labels are scoped to the function they appear in, so a jump may only target a label of its
+/// own function. Calls are checked against every function defined in the given modules, and a
+/// function may be defined only once among them.
+///
+/// Commands outside of any function are only allowed with `top_level`, for programs that start
+/// at their first command. Their labels are scoped to the module.
+pub fn validate(modules: &[(String, Vec<Line>)], top_level: bool) -> Vec<String> {
+    let mut errors = Vec::new();
+    let mut defined = HashMap::new();
+    let mut calls = Vec::new();
+
+    for (module, commands) in modules {
+        let mut scope = top_level.then(|| format!("the top level of {module}"));
+        let mut labels = HashSet::new();
+        let mut jumps = Vec::new();
+        let mut outside = false;
+
+        for Line { line, command } in commands {
+            if let VmCommand::Function(name, _) = command {
+                check_jumps(&mut errors, module, &labels, &jumps);
+                labels.clear();
+                jumps.clear();
+                scope = Some(format!("function {name}"));
+                if let Some((first_module, first_line)) = defined.get(name.as_str()) {
+                    errors.push(format!(
+                        "{module}, line {line}: function {name} is already defined in {first_module}, line {first_line}."
+                    ));
+                } else {
+                    defined.insert(name.as_str(), (module, *line));
+                }
+                continue;
+            }
+
+            let Some(current) = &scope else {
+                // the commands up to the first function are all outside, so report the first one only
+                if !outside {
+                    errors.push(format!(
+                        "{module}, line {line}: command outside of any function."
+                    ));
+                    outside = true;
+                }
+                continue;
+            };
+
+            match command {
+                VmCommand::Label(label) if !labels.insert(label.as_str()) => {
+                    errors.push(format!(
+                        "{module}, line {line}: label '{label}' is defined more than once in {current}."
+                    ));
+                }
+                VmCommand::Goto(label) | VmCommand::If(label) => {
+                    jumps.push((*line, current.clone(), label.as_str()));
+                }
+                VmCommand::Call(callee, _) => {
+                    calls.push((module, *line, callee.as_str()));
+                }
+                _ => {}
+            }
+        }
+
+        check_jumps(&mut errors, module, &labels, &jumps);
+    }
+
+    for (module, line, callee) in calls {
+        if !defined.contains_key(callee) {
+            errors.push(format!(
+                "{module}, line {line}: call to undefined function '{callee}'."
+            ));
+        }
+    }
+
+    errors
+}
+
+fn check_jumps(
+    errors: &mut Vec<String>,
+    module: &str,
+    labels: &HashSet<&str>,
+    jumps: &[(usize, String, &str)],
+) {
+    for (line, scope, label) in jumps {
+        if !labels.contains(label) {
+            errors.push(format!(
+                "{module}, line {line}: jump to label '{label}', which is not defined in {scope}."
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::validate;
+    use crate::parser::parse;
+
+    fn check(sources: &[(&str, &str)], top_level: bool) -> Vec<String> {
+        let modules = sources
+            .iter()
+            .map(|(module, source)| (module.to_string(), parse(source).unwrap()))
+            .collect::<Vec<_>>();
+        validate(&modules, top_level)
+    }
+
+    #[test]
+    fn test_duplicate_functions() {
+        let main = "function Main.main 0\ncall Math.abs 1\nreturn\nfunction Math.abs 0\npush argument 0\nreturn";
+        let math = "function Math.abs 0\npush argument 0\nreturn";
+        assert_eq!(
+            check(&[("Main.vm", main), ("Math.vm", math)], false),
+            ["Math.vm, line 1: function Math.abs is already defined in Main.vm, line 4."]
+        );
+        assert!(check(&[("Math.vm", math)], false).is_empty());
+    }
+
+    #[test]
+    fn test_labels() {
+        // the same label in two functions is fine, a jump into another function is not
+        let main = "\
+            function Main.f 0\n\
+            label LOOP\n\
+            goto LOOP\n\
+            function Main.g 0\n\
+            label LOOP\n\
+            label END\n\
+            if-goto LOOP\n\
+            function Main.h 0\n\
+            goto END\n\
+            label DONE\n\
+            label DONE";
+        assert_eq!(
+            check(&[("Main.vm", main)], false),
+            [
+                "Main.vm, line 11: label 'DONE' is defined more than once in function Main.h.",
+                "Main.vm, line 9: jump to label 'END', which is not defined in function Main.h.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_undefined_functions() {
+        let main = "function Main.main 0\ncall Math.abs 1\ncall Main.main 0\ncall Main.mian 0\nreturn";
+        assert_eq!(
+            check(&[("Main.vm", main)], false),
+            [
+                "Main.vm, line 2: call to undefined function 'Math.abs'.",
+                "Main.vm, line 4: call to undefined function 'Main.mian'.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_top_level() {
+        let simple = "push constant 1\nlabel LOOP\ngoto LOOP\ngoto MISSING";
+        assert_eq!(
+            check(&[("Loop.vm", simple)], true),
+            ["Loop.vm, line 4: jump to label 'MISSING', which is not defined in the top level of Loop.vm."]
+        );
+        assert_eq!(
+            check(&[("Loop.vm", simple)], false),
+            ["Loop.vm, line 1: command outside of any function."]
+        );
+    }
+}